@@ -0,0 +1,67 @@
+//! Compare the streamable and the seekable archives, in throughput and in output size.
+//!
+//! Run with `cargo bench`.
+
+use std::io::Cursor;
+
+use archflow::compress::std::archive::ZipArchive;
+use archflow::compress::FileOptions;
+use archflow::compression::CompressionMethod;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const FILE_SIZES: [usize; 3] = [1024, 64 * 1024, 1024 * 1024];
+const FILE_COUNT: usize = 10;
+
+fn payload(size: usize) -> Vec<u8> {
+    //somewhat compressible data
+    (0..size)
+        .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+        .collect()
+}
+
+fn build_archive(streamable: bool, method: CompressionMethod, data: &[u8]) -> u64 {
+    let cursor = Cursor::new(Vec::with_capacity(data.len() * FILE_COUNT));
+    let mut archive = if streamable {
+        ZipArchive::new_streamable(cursor)
+    } else {
+        ZipArchive::new(cursor)
+    };
+
+    let options = FileOptions::default().compression_method(method);
+    for i in 0..FILE_COUNT {
+        archive
+            .append(&format!("file{}.bin", i), &options, &mut { data })
+            .unwrap();
+    }
+
+    let (archive_size, _) = archive.finalize().unwrap();
+    archive_size
+}
+
+fn bench_archive(c: &mut Criterion) {
+    for method in [CompressionMethod::Store(), CompressionMethod::Deflate()] {
+        let mut group = c.benchmark_group(format!("archive_{}", method));
+
+        for size in FILE_SIZES {
+            let data = payload(size);
+            group.throughput(Throughput::Bytes((size * FILE_COUNT) as u64));
+
+            for (name, streamable) in [("seekable", false), ("streamable", true)] {
+                let archive_size = build_archive(streamable, method, &data);
+                println!(
+                    "{} {} {} files of {} bytes: archive size {} bytes",
+                    method, name, FILE_COUNT, size, archive_size
+                );
+
+                group.bench_with_input(BenchmarkId::new(name, size), &data, |b, data| {
+                    b.iter(|| build_archive(streamable, method, data))
+                });
+            }
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_archive);
+criterion_main!(benches);
@@ -0,0 +1,78 @@
+//! Compare the writes received by the sink of a streamable archive of many tiny entries, with and without
+//! coalescing them in a buffer.
+//!
+//! Run with `cargo bench --bench buffered`.
+
+use std::io::Write;
+
+use archflow::compress::std::archive::ZipArchive;
+use archflow::compress::FileOptions;
+use archflow::compression::CompressionMethod;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const FILE_COUNT: usize = 1000;
+const BUFFER_SIZES: [usize; 2] = [8 * 1024, 64 * 1024];
+
+/// A sink discarding the bytes, counting the writes, like the packets of a socket.
+#[derive(Default)]
+struct WriteCounter {
+    write_count: usize,
+}
+
+impl Write for WriteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_count += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn build_archive(buffer_size: Option<usize>) -> usize {
+    let mut archive = match buffer_size {
+        Some(capacity) => ZipArchive::new_streamable_buffered(WriteCounter::default(), capacity),
+        None => ZipArchive::new_streamable(WriteCounter::default()),
+    };
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+    for i in 0..FILE_COUNT {
+        archive
+            .append(&format!("file{}.txt", i), &options, &mut { &b"tiny"[..] })
+            .unwrap();
+    }
+
+    let (_, counter) = archive.finalize().unwrap();
+    counter.write_count
+}
+
+fn bench_buffered(c: &mut Criterion) {
+    let mut group = c.benchmark_group("streamable_tiny_entries");
+
+    let cases = [None].into_iter().chain(BUFFER_SIZES.into_iter().map(Some));
+    for buffer_size in cases {
+        let name = match buffer_size {
+            Some(_) => "buffered",
+            None => "unbuffered",
+        };
+        println!(
+            "{} {} tiny files, buffer {:?}: {} writes",
+            name,
+            FILE_COUNT,
+            buffer_size,
+            build_archive(buffer_size)
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new(name, buffer_size.unwrap_or(0)),
+            &buffer_size,
+            |b, buffer_size| b.iter(|| build_archive(*buffer_size)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_buffered);
+criterion_main!(benches);
@@ -1,10 +1,9 @@
 use std::any::Any;
+#[cfg(feature = "experimental")]
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
-use std::str;
-use std::u32;
-use std::u8;
 
 use chrono::NaiveDateTime;
 use chrono::{DateTime, Local, TimeZone, Utc};
@@ -12,17 +11,31 @@ use chrono::{DateTime, Local, TimeZone, Utc};
 use super::compression::CompressionMethod;
 
 use crate::constants::CENTRAL_DIRECTORY_END_SIGNATURE;
+#[cfg(feature = "experimental")]
+use crate::constants::COMPRESSED_PATCHED_DATA_FLAG;
+use crate::constants::ENCRYPTED_FLAG;
+use crate::constants::FILE_HEADER_BASE_SIZE;
+#[cfg(feature = "serde")]
+use crate::constants::METADATA_JSON_PREFIX;
 use crate::constants::MS_DIR;
+use crate::constants::STRONG_ENCRYPTION_FLAG;
 use crate::constants::S_IFDIR;
+#[cfg(feature = "experimental")]
+use crate::constants::UTF8_HEADER_FLAG;
 use crate::constants::VERSION_USES_ZIP64_FORMAT_EXTENSIONS;
 use crate::constants::X5455_EXTENDEDTIMESTAMP;
+#[cfg(feature = "experimental")]
+use crate::constants::X7075_INFOZIP_UNICODE_PATH;
+use crate::constants::X7855_INFOZIP_UNIX_NEW;
+use crate::constants::X7875_INFOZIP_UNIX_UID_GID;
+use crate::constants::XD935_ANDROID_ALIGNMENT;
 use crate::constants::ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE;
 
 use crate::constants::ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIGNATURE;
-#[cfg(any(feature = "experimental"))]
 use crate::error::ArchiveError;
 use crate::types::DateTimeCS;
 use crate::types::FileCompatibilitySystem;
+use crate::types::GeneralPurposeFlags;
 
 #[derive(Debug)]
 pub struct ArchiveDescriptor {
@@ -60,11 +73,7 @@ impl ArchiveDescriptor {
         self.buffer.extend_from_slice(val);
     }
 
-    pub fn write_zeros(&mut self, len: usize) {
-        self.buffer.resize(self.len() + len, 0);
-    }
-
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn finish(self) -> Vec<u8> {
         self.buffer
     }
@@ -73,7 +82,7 @@ impl ArchiveDescriptor {
         self.buffer.len()
     }
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -82,24 +91,22 @@ impl ArchiveDescriptor {
         &self.buffer
     }
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn read_file_descriptor(stream: &[u8]) -> Result<ArchiveFileEntry, ArchiveError> {
         let mut indexer = ArchiveDescriptorReader::new();
 
-        let _signature = indexer.read_u32(stream);
-        let version_needed = indexer.read_u16(stream) & 0xFF;
-        let general_purpose_flags = indexer.read_u16(stream);
-        let compression_method = indexer.read_u16(stream);
-        let time = indexer.read_u16(stream);
-        let date = indexer.read_u16(stream);
-        let crc = indexer.read_u32(stream);
-        let compressed_size = indexer.read_u32(stream) as u64;
-        let uncompressed_size = indexer.read_u32(stream) as u64;
-        let file_name_len = indexer.read_u16(stream);
-        let extra_field_length = indexer.read_u16(stream);
-        let file_name = indexer.read_utf8_string(stream, file_name_len as usize);
-
-        let file_name_as_bytes = file_name.as_bytes().to_owned();
+        let _signature = indexer.read_u32(stream)?;
+        let version_needed = indexer.read_u16(stream)? & 0xFF;
+        let general_purpose_flags = indexer.read_u16(stream)?;
+        let compression_method = indexer.read_u16(stream)?;
+        let time = indexer.read_u16(stream)?;
+        let date = indexer.read_u16(stream)?;
+        let crc = indexer.read_u32(stream)?;
+        let compressed_size = indexer.read_u32(stream)? as u64;
+        let uncompressed_size = indexer.read_u32(stream)? as u64;
+        let file_name_len = indexer.read_u16(stream)?;
+        let extra_field_length = indexer.read_u16(stream)?;
+        let file_name_as_bytes = indexer.read_bytes(stream, file_name_len as usize)?;
 
         let archive_file_entry = ArchiveFileEntry {
             version_made_by: 0,
@@ -118,9 +125,12 @@ impl ArchiveDescriptor {
             external_file_attributes: 0,
             file_disk_number: 0,
             compression_method,
-            compressor: CompressionMethod::from_compression_method(compression_method)?,
+            compressor: CompressionMethod::from_compression_method(compression_method)
+                .unwrap_or(CompressionMethod::Unknown(compression_method)),
             file_comment: None,
             extra_fields: Vec::new(),
+            raw_extra_field: Vec::new(),
+            force_zip64: false,
         };
 
         Ok(archive_file_entry)
@@ -135,41 +145,25 @@ impl ArchiveDescriptor {
     }
 }
 
-#[cfg(any(feature = "experimental"))]
+#[cfg(feature = "experimental")]
 #[derive(Default)]
 pub struct ArchiveDescriptorReader {
     index: usize,
 }
 
-#[cfg(any(feature = "experimental"))]
+#[cfg(feature = "experimental")]
 macro_rules! read_type {
     ($self:expr, $stream:expr, $typ:ty) => {{
-        let upper_bound = $self.index + ::std::mem::size_of::<$typ>();
+        let bytes = $self.next_bytes($stream, ::std::mem::size_of::<$typ>())?;
 
         let read: [u8; ::std::mem::size_of::<$typ>()] =
-            match $stream[$self.index..upper_bound].try_into() {
-                Ok(v) => v,
-                Err(e) => {
-                    eprintln!("slice with incorrect length {:?}", e);
-                    Default::default()
-                }
-            };
-        let value = <$typ>::from_le_bytes(read);
-
-        $self.index = upper_bound;
-/* 
-        let type_str = stringify!($typ);
-        println!(
-            "read_{} value: {:} new index {:}",
-            type_str, value, $self.index
-        ); 
-*/
-
-        value
+            bytes.try_into().expect("length checked by next_bytes");
+
+        Ok(<$typ>::from_le_bytes(read))
     }};
 }
 
-#[cfg(any(feature = "experimental"))]
+#[cfg(feature = "experimental")]
 impl ArchiveDescriptorReader {
     pub fn new() -> ArchiveDescriptorReader {
         ArchiveDescriptorReader { index: 0 }
@@ -179,70 +173,64 @@ impl ArchiveDescriptorReader {
         self.index
     }
 
-    pub fn read_u32(&mut self, stream: &[u8]) -> u32 {
+    pub fn set_index(&mut self, index: usize) {
+        self.index = index;
+    }
+
+    /// Returns the next `len` bytes of the stream, or [`ArchiveError::BadArchiveStructure`] if the stream is too
+    /// short (e.g. a truncated archive).
+    fn next_bytes<'s>(&mut self, stream: &'s [u8], len: usize) -> Result<&'s [u8], ArchiveError> {
+        let upper_bound = self
+            .index
+            .checked_add(len)
+            .filter(|upper_bound| *upper_bound <= stream.len())
+            .ok_or_else(|| {
+                ArchiveError::BadArchiveStructure(format!(
+                    "Truncated record: {} bytes expected at index {} of a {} bytes record",
+                    len,
+                    self.index,
+                    stream.len()
+                ))
+            })?;
+
+        let bytes = &stream[self.index..upper_bound];
+        self.index = upper_bound;
+        Ok(bytes)
+    }
+
+    pub fn read_u32(&mut self, stream: &[u8]) -> Result<u32, ArchiveError> {
         read_type!(self, stream, u32)
     }
 
-    pub fn read_i32(&mut self, stream: &[u8]) -> i32 {
+    pub fn read_i32(&mut self, stream: &[u8]) -> Result<i32, ArchiveError> {
         read_type!(self, stream, i32)
     }
 
-    pub fn read_u16(&mut self, stream: &[u8]) -> u16 {
+    pub fn read_u16(&mut self, stream: &[u8]) -> Result<u16, ArchiveError> {
         read_type!(self, stream, u16)
     }
 
-    pub fn read_u8(&mut self, stream: &[u8]) -> u8 {
+    pub fn read_u8(&mut self, stream: &[u8]) -> Result<u8, ArchiveError> {
         read_type!(self, stream, u8)
     }
 
-    pub fn read_u64(&mut self, stream: &[u8]) -> u64 {
+    pub fn read_u64(&mut self, stream: &[u8]) -> Result<u64, ArchiveError> {
         read_type!(self, stream, u64)
     }
 
-    pub fn read_utf8_string(&mut self, stream: &[u8], string_len: usize) -> String {
-        let upper_bound = self.index + string_len;
-
-/*         println!(
-            "read_utf8_string lb: {:?} up: {:} ({:} bytes) from a {:} length array.",
-            self.index,
-            upper_bound,
-            string_len,
-            stream.len()
-        ); */
-
-        let value = match str::from_utf8(&stream[self.index..upper_bound]) {
-            Ok(v) => v.to_owned(),
-            Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-        };
-
-        self.index = upper_bound;
-
-/*         println!(
-            "read_utf8_string value: {:?} new index {:}",
-            value, self.index
-        ); */
+    pub fn read_utf8_string(
+        &mut self,
+        stream: &[u8],
+        string_len: usize,
+    ) -> Result<String, ArchiveError> {
+        let bytes = self.next_bytes(stream, string_len)?;
 
-        value
+        //legacy names (e.g. CP437) aren't valid UTF-8, the invalid sequences are replaced
+        Ok(String::from_utf8_lossy(bytes).into_owned())
     }
 
-    pub fn read_bytes(&mut self, stream: &[u8], len: usize) -> Vec<u8> {
-        let upper_bound = self.index + len;
-
-/*         println!(
-            "read_bytes lb: {:?} up: {:} ({:} bytes) from a {:} length array.",
-            self.index,
-            upper_bound,
-            len,
-            stream.len()
-        ); */
-
-        let value = stream[self.index..upper_bound].to_owned();
-
-        self.index = upper_bound;
-/* 
-        println!("read_bytes value: {:?} new index {:}", value, self.index);
- */
-        value
+    pub fn read_bytes(&mut self, stream: &[u8], len: usize) -> Result<Vec<u8>, ArchiveError> {
+        Ok(self.next_bytes(stream, len)?.to_owned())
     }
 }
 #[derive(Debug, Default)]
@@ -260,7 +248,7 @@ pub struct CentralDirectoryEnd {
 }
 
 impl CentralDirectoryEnd {
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn zip_file_comment_length(&self) -> u16 {
         match &self.archive_comment {
             Some(comment) => comment.len() as u16,
@@ -272,12 +260,59 @@ impl CentralDirectoryEnd {
     ///
     /// This sets the raw bytes of the comment. The comment
     /// is typically expected to be encoded in UTF-8. Comment is truncated to 0xFFFF bytes.
+    pub fn try_set_archive_comment(&mut self, comment: &str) -> Result<(), ArchiveError> {
+        let bytes = comment.as_bytes();
+        if bytes.len() > u16::MAX as usize {
+            return Err(ArchiveError::CommentTooLong(bytes.len()));
+        }
+        self.archive_comment = Some(bytes.to_owned());
+        Ok(())
+    }
+
     pub fn set_archive_comment(&mut self, comment: &str) {
         let bytes = comment.as_bytes();
-        let len = std::cmp::min(bytes.len(), u16::MAX as usize);
+        let mut len = std::cmp::min(bytes.len(), u16::MAX as usize);
+        //keep the truncated comment valid UTF-8
+        while !comment.is_char_boundary(len) {
+            len -= 1;
+        }
         self.archive_comment = Some(bytes[0..len].to_owned());
     }
 
+    /// Set the archive comment to the JSON metadata, after a prefix telling it apart from a plain comment.
+    #[cfg(feature = "serde")]
+    pub fn set_metadata_json(&mut self, metadata: &serde_json::Value) -> Result<(), ArchiveError> {
+        self.try_set_archive_comment(&format!("{}{}", METADATA_JSON_PREFIX, metadata))
+    }
+
+    /// The JSON metadata of the archive comment, `None` if the comment doesn't start with the metadata prefix.
+    ///
+    /// Only the first JSON value is parsed, so a trailing archive digest is ignored.
+    #[cfg(all(feature = "serde", feature = "experimental"))]
+    pub fn metadata_json(&self) -> Result<Option<serde_json::Value>, ArchiveError> {
+        let json = match &self.archive_comment {
+            Some(comment) => match comment.strip_prefix(METADATA_JSON_PREFIX.as_bytes()) {
+                Some(json) => json,
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+
+        match serde_json::Deserializer::from_slice(json)
+            .into_iter::<serde_json::Value>()
+            .next()
+        {
+            Some(Ok(metadata)) => Ok(Some(metadata)),
+            Some(Err(error)) => Err(ArchiveError::BadArchiveStructure(format!(
+                "Invalid JSON metadata: {}",
+                error
+            ))),
+            None => Err(ArchiveError::BadArchiveStructure(
+                "Empty JSON metadata".to_owned(),
+            )),
+        }
+    }
+
     // Per spec 4.4.1.4 - a CentralDirectoryEnd field might be insufficient to hold the
     // required data. In this case the file SHOULD contain a ZIP64 format record
     // and the field of this record will be set to -1
@@ -313,9 +348,16 @@ impl CentralDirectoryEnd {
     pub fn create_end_of_central_directory_locator(
         &mut self,
         end_of_central_directory: &mut ArchiveDescriptor,
-    ) {
-        self.z64ecdl_relative_offset_of_the_zip64_end_of_central_directory_record =
-            self.offset_of_start_of_central_directory + self.central_directory_size;
+    ) -> Result<(), ArchiveError> {
+        self.z64ecdl_relative_offset_of_the_zip64_end_of_central_directory_record = self
+            .offset_of_start_of_central_directory
+            .checked_add(self.central_directory_size)
+            .ok_or_else(|| {
+                ArchiveError::BadArchiveStructure(format!(
+                    "Zip64 end of central directory offset overflows: {} + {}",
+                    self.offset_of_start_of_central_directory, self.central_directory_size
+                ))
+            })?;
 
         end_of_central_directory.write_u32(ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIGNATURE);
         end_of_central_directory.write_u32(
@@ -324,6 +366,7 @@ impl CentralDirectoryEnd {
         end_of_central_directory
             .write_u64(self.z64ecdl_relative_offset_of_the_zip64_end_of_central_directory_record);
         end_of_central_directory.write_u32(self.z64ecdl_total_number_of_disks);
+        Ok(())
     }
 
     pub fn create_end_of_central_directory(
@@ -404,6 +447,8 @@ pub struct ExtraFieldExtendedTimestamp {
     modify_time: Option<i32>,
     access_time: Option<i32>,
     create_time: Option<i32>,
+    /// Is the field written in the central header only
+    central_only: bool,
 }
 
 impl ExtraFieldExtendedTimestamp {
@@ -432,6 +477,12 @@ impl ExtraFieldExtendedTimestamp {
         default
     }
 
+    /// Write the field in the central header only, it holds only the modification time.
+    pub fn central_only(mut self) -> Self {
+        self.central_only = true;
+        self
+    }
+
     fn set_modify_time(&mut self, modify_time: Option<i32>) {
         self.modify_time = modify_time;
 
@@ -500,12 +551,12 @@ impl ExtraFieldExtendedTimestamp {
         }
     }
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn parse_extra_field(
         indexer: &mut ArchiveDescriptorReader,
         extra_field_as_bytes: &[u8],
         extra_field_data_size: u16,
-    ) -> Self {
+    ) -> Result<Self, ArchiveError> {
         let mut flags: u8 = 0;
         let mut modify_time: Option<i32> = None;
         let mut access_time: Option<i32> = None;
@@ -513,30 +564,31 @@ impl ExtraFieldExtendedTimestamp {
 
         match extra_field_data_size {
             0 => {}
-            1..=4 => flags = indexer.read_u8(extra_field_as_bytes),
+            1..=4 => flags = indexer.read_u8(extra_field_as_bytes)?,
             5..=8 => {
-                flags = indexer.read_u8(extra_field_as_bytes);
-                modify_time = Some(indexer.read_i32(extra_field_as_bytes))
+                flags = indexer.read_u8(extra_field_as_bytes)?;
+                modify_time = Some(indexer.read_i32(extra_field_as_bytes)?)
             }
             9..=13 => {
-                flags = indexer.read_u8(extra_field_as_bytes);
-                modify_time = Some(indexer.read_i32(extra_field_as_bytes));
-                access_time = Some(indexer.read_i32(extra_field_as_bytes))
+                flags = indexer.read_u8(extra_field_as_bytes)?;
+                modify_time = Some(indexer.read_i32(extra_field_as_bytes)?);
+                access_time = Some(indexer.read_i32(extra_field_as_bytes)?)
             }
             _ => {
-                flags = indexer.read_u8(extra_field_as_bytes);
-                modify_time = Some(indexer.read_i32(extra_field_as_bytes));
-                access_time = Some(indexer.read_i32(extra_field_as_bytes));
-                create_time = Some(indexer.read_i32(extra_field_as_bytes))
+                flags = indexer.read_u8(extra_field_as_bytes)?;
+                modify_time = Some(indexer.read_i32(extra_field_as_bytes)?);
+                access_time = Some(indexer.read_i32(extra_field_as_bytes)?);
+                create_time = Some(indexer.read_i32(extra_field_as_bytes)?)
             }
         }
 
-        Self {
+        Ok(Self {
             create_time,
             access_time,
             modify_time,
             flags,
-        }
+            central_only: false,
+        })
     }
 
     fn central_header_extra_write_data_common(
@@ -561,6 +613,9 @@ impl ExtraFieldExtendedTimestamp {
 
 impl ExtraField for ExtraFieldExtendedTimestamp {
     fn local_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        if self.central_only {
+            return 0;
+        }
         4 + self.file_header_extra_field_data_size()
     }
 
@@ -573,7 +628,7 @@ impl ExtraField for ExtraFieldExtendedTimestamp {
         archive_descriptor: &mut ArchiveDescriptor,
         _archive_file_entry: &ArchiveFileEntry,
     ) {
-        if self.flags == 0 {
+        if self.flags == 0 || self.central_only {
             return;
         }
 
@@ -646,6 +701,213 @@ impl ExtraField for ExtraFieldExtendedTimestamp {
     }
 }
 
+/// The "Info-ZIP Unix (new)" extra field, storing the Unix owner with 16 bits ids.
+///
+/// The local header holds the user id and the group id, the central header holds an empty field.
+///
+/// Use the field definition given in Info-Zip's source archive: zip-3.0.tar.gz/proginfo/extrafld.txt.
+#[derive(Debug, Default)]
+pub struct ExtraFieldInfoZipUnixNew {
+    uid: u16,
+    gid: u16,
+}
+
+impl ExtraFieldInfoZipUnixNew {
+    pub const HEADER_ID: u16 = X7855_INFOZIP_UNIX_NEW;
+
+    pub fn new(uid: u16, gid: u16) -> Self {
+        Self { uid, gid }
+    }
+}
+
+impl ExtraField for ExtraFieldInfoZipUnixNew {
+    fn local_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        4 + 4
+    }
+
+    fn central_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        4
+    }
+
+    fn local_header_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        _archive_file_entry: &ArchiveFileEntry,
+    ) {
+        archive_descriptor.write_u16(ExtraFieldInfoZipUnixNew::HEADER_ID);
+        archive_descriptor.write_u16(4);
+        archive_descriptor.write_u16(self.uid);
+        archive_descriptor.write_u16(self.gid);
+    }
+
+    fn central_header_extra_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        _archive_file_entry: &ArchiveFileEntry,
+    ) {
+        archive_descriptor.write_u16(ExtraFieldInfoZipUnixNew::HEADER_ID);
+        archive_descriptor.write_u16(0);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn display_central(&self) -> String {
+        format!(
+            "- A subfield with ID 0x{:04X} (Unix UID/GID (16-bit)) and 0 data bytes.",
+            ExtraFieldInfoZipUnixNew::HEADER_ID,
+        )
+    }
+}
+
+/// The "Info-ZIP Unix (newer UID/GID)" extra field, storing the Unix owner with ids of any size.
+///
+/// Both headers hold the same data: the field version (1), then the size and the value of the user id and of
+/// the group id. The ids are written on 4 bytes.
+///
+/// Use the field definition given in Info-Zip's source archive: zip-3.0.tar.gz/proginfo/extrafld.txt.
+#[derive(Debug, Default)]
+pub struct ExtraFieldInfoZipUnixUidGid {
+    uid: u32,
+    gid: u32,
+}
+
+impl ExtraFieldInfoZipUnixUidGid {
+    pub const HEADER_ID: u16 = X7875_INFOZIP_UNIX_UID_GID;
+
+    const VERSION: u8 = 1;
+
+    /// The version, the two sizes and the two 4 bytes ids.
+    const DATA_SIZE: u16 = 1 + 2 * (1 + 4);
+
+    pub fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+}
+
+impl ExtraField for ExtraFieldInfoZipUnixUidGid {
+    fn local_header_extra_field_size(&self, archive_file_entry: &ArchiveFileEntry) -> u16 {
+        self.central_header_extra_field_size(archive_file_entry)
+    }
+
+    fn central_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        4 + ExtraFieldInfoZipUnixUidGid::DATA_SIZE
+    }
+
+    fn local_header_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        archive_file_entry: &ArchiveFileEntry,
+    ) {
+        self.central_header_extra_write_data(archive_descriptor, archive_file_entry)
+    }
+
+    fn central_header_extra_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        _archive_file_entry: &ArchiveFileEntry,
+    ) {
+        archive_descriptor.write_u16(ExtraFieldInfoZipUnixUidGid::HEADER_ID);
+        archive_descriptor.write_u16(ExtraFieldInfoZipUnixUidGid::DATA_SIZE);
+        archive_descriptor.write_u8(ExtraFieldInfoZipUnixUidGid::VERSION);
+        archive_descriptor.write_u8(4);
+        archive_descriptor.write_u32(self.uid);
+        archive_descriptor.write_u8(4);
+        archive_descriptor.write_u32(self.gid);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn display_central(&self) -> String {
+        format!(
+            "- A subfield with ID 0x{:04X} (Unix UID/GID (any size)) and {} data bytes.",
+            ExtraFieldInfoZipUnixUidGid::HEADER_ID,
+            ExtraFieldInfoZipUnixUidGid::DATA_SIZE,
+        )
+    }
+}
+
+/// The Android alignment extra field, padding the local header so the entry's data starts at an aligned offset,
+/// as zipalign does for the stored entries.
+///
+/// The local header holds the alignment, then the zero padding. It must be the entry's last extra field, as its
+/// padding depends on the local header's length before it. The central header doesn't hold it.
+#[derive(Debug, Default)]
+pub struct ExtraFieldAlignment {
+    alignment: u16,
+}
+
+impl ExtraFieldAlignment {
+    pub const HEADER_ID: u16 = XD935_ANDROID_ALIGNMENT;
+
+    pub fn new(alignment: u16) -> Self {
+        Self { alignment }
+    }
+
+    /// The padding making the data start at an aligned offset, once the field header and alignment are written.
+    fn padding(&self, archive_file_entry: &ArchiveFileEntry) -> u16 {
+        let previous_fields: u64 = archive_file_entry
+            .extra_fields
+            .iter()
+            .filter(|extra_field| !extra_field.as_any().is::<ExtraFieldAlignment>())
+            .map(|extra_field| extra_field.local_header_extra_field_size(archive_file_entry) as u64)
+            .sum();
+
+        let data_offset = archive_file_entry.offset
+            + FILE_HEADER_BASE_SIZE
+            + archive_file_entry.file_name_len as u64
+            + previous_fields
+            + 6;
+
+        let alignment = self.alignment.max(1) as u64;
+        ((alignment - data_offset % alignment) % alignment) as u16
+    }
+}
+
+impl ExtraField for ExtraFieldAlignment {
+    fn local_header_extra_field_size(&self, archive_file_entry: &ArchiveFileEntry) -> u16 {
+        4 + 2 + self.padding(archive_file_entry)
+    }
+
+    fn central_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        0
+    }
+
+    fn local_header_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        archive_file_entry: &ArchiveFileEntry,
+    ) {
+        let padding = self.padding(archive_file_entry);
+        archive_descriptor.write_u16(ExtraFieldAlignment::HEADER_ID);
+        archive_descriptor.write_u16(2 + padding);
+        archive_descriptor.write_u16(self.alignment);
+        archive_descriptor.write_bytes(&vec![0; padding as usize]);
+    }
+
+    fn central_header_extra_write_data(
+        &self,
+        _archive_descriptor: &mut ArchiveDescriptor,
+        _archive_file_entry: &ArchiveFileEntry,
+    ) {
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn display_central(&self) -> String {
+        format!(
+            "- A subfield with ID 0x{:04X} (Android alignment on {} bytes), in the local header only.",
+            ExtraFieldAlignment::HEADER_ID,
+            self.alignment,
+        )
+    }
+}
+
 /// The following is the layout of the ZIP64 extended
 /// information "extra" block. If one of the size or
 /// offset fields in the Local or Central directory
@@ -674,62 +936,66 @@ pub struct ExtraFieldZIP64ExtendedInformation {
 
 impl ExtraFieldZIP64ExtendedInformation {
     pub const HEADER_ID: u16 = 0x0001;
-    const ZIP64_EXTRA_FIELD_SIZE: u16 = 8 * 3 + 4;
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn new(parsed_sized: u16) -> Self {
         Self { parsed_sized }
     }
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn parse_extra_field(
         indexer: &mut ArchiveDescriptorReader,
         extra_field_as_bytes: &[u8],
         extra_field_data_size: u16,
         archive_file_entry: &mut ArchiveFileEntry,
-    ) -> Self {
-        match extra_field_data_size {
-            0..=7 => { //Nothing worthy}
-            }
-            8..=15 => archive_file_entry.uncompressed_size = indexer.read_u64(extra_field_as_bytes),
-            16..=23 => {
-                archive_file_entry.uncompressed_size = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.compressed_size = indexer.read_u64(extra_field_as_bytes);
-            }
-            24..=31 => {
-                archive_file_entry.uncompressed_size = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.compressed_size = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.offset = indexer.read_u64(extra_field_as_bytes);
-            }
-            _ => {
-                archive_file_entry.uncompressed_size = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.compressed_size = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.offset = indexer.read_u64(extra_field_as_bytes);
-                archive_file_entry.file_disk_number = indexer.read_u32(extra_field_as_bytes);
-            }
+    ) -> Result<Self, ArchiveError> {
+        let end = indexer.get_index() + extra_field_data_size as usize;
+
+        // Per spec 4.5.3, a field is only present if the classic field is set to the sentinel value
+        if archive_file_entry.uncompressed_size == u32::MAX as u64 && indexer.get_index() + 8 <= end
+        {
+            archive_file_entry.uncompressed_size = indexer.read_u64(extra_field_as_bytes)?;
+        }
+        if archive_file_entry.compressed_size == u32::MAX as u64 && indexer.get_index() + 8 <= end {
+            archive_file_entry.compressed_size = indexer.read_u64(extra_field_as_bytes)?;
+        }
+        if archive_file_entry.offset == u32::MAX as u64 && indexer.get_index() + 8 <= end {
+            archive_file_entry.offset = indexer.read_u64(extra_field_as_bytes)?;
+        }
+        if archive_file_entry.file_disk_number == u16::MAX as u32 && indexer.get_index() + 4 <= end
+        {
+            archive_file_entry.file_disk_number = indexer.read_u32(extra_field_as_bytes)?;
         }
 
-        Self::new(extra_field_data_size)
+        //skip what is left, the next extra field starts after
+        if indexer.get_index() < end {
+            indexer.read_bytes(extra_field_as_bytes, end - indexer.get_index())?;
+        }
+
+        Ok(Self::new(extra_field_data_size))
     }
 }
 
 impl ExtraField for ExtraFieldZIP64ExtendedInformation {
     fn local_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
-        16
+        4 + 16
     }
 
     fn central_header_extra_field_size(&self, archive_file_entry: &ArchiveFileEntry) -> u16 {
-        let size: u16 = if archive_file_entry.file_disk_number >= u16::MAX as u32 {
-            28
-        } else if archive_file_entry.offset >= u32::MAX as u64 {
-            24
-        } else if archive_file_entry.compressed_size >= u32::MAX as u64 {
-            16
-        } else if archive_file_entry.uncompressed_size >= u32::MAX as u64 {
-            8
-        } else {
-            0
-        };
+        //only the fields whose classic counterpart holds the sentinel value are present
+        let mut size = 0;
+        if archive_file_entry.zip64_uncompressed_size() == u32::MAX {
+            size += 8;
+        }
+        if archive_file_entry.zip64_compressed_size() == u32::MAX {
+            size += 8;
+        }
+        if archive_file_entry.zip64_offset() == u32::MAX {
+            size += 8;
+        }
+        if archive_file_entry.zip64_file_disk_number() == u16::MAX {
+            size += 4;
+        }
         size
     }
 
@@ -738,17 +1004,11 @@ impl ExtraField for ExtraFieldZIP64ExtendedInformation {
         archive_descriptor: &mut ArchiveDescriptor,
         archive_file_entry: &ArchiveFileEntry,
     ) {
-        let size = self.local_header_extra_field_size(archive_file_entry);
-        //If uncompressed size == 0 it do zero padding
-        if archive_file_entry.uncompressed_size == 0 {
-            archive_descriptor.write_zeros(size as usize);
-        } else {
-            self.central_header_extra_write_data(archive_descriptor, archive_file_entry);
-            archive_descriptor.write_u16(ExtraFieldZIP64ExtendedInformation::HEADER_ID);
-            archive_descriptor.write_u16(size);
-            archive_descriptor.write_u64(archive_file_entry.uncompressed_size);
-            archive_descriptor.write_u64(archive_file_entry.compressed_size);
-        }
+        //Both sizes are required, they are zero until the payload is compressed
+        archive_descriptor.write_u16(ExtraFieldZIP64ExtendedInformation::HEADER_ID);
+        archive_descriptor.write_u16(16);
+        archive_descriptor.write_u64(archive_file_entry.uncompressed_size);
+        archive_descriptor.write_u64(archive_file_entry.compressed_size);
     }
 
     fn central_header_extra_write_data(
@@ -763,19 +1023,19 @@ impl ExtraField for ExtraFieldZIP64ExtendedInformation {
         }
 
         archive_descriptor.write_u16(ExtraFieldZIP64ExtendedInformation::HEADER_ID);
-        archive_descriptor
-            .write_u16(ExtraFieldZIP64ExtendedInformation::ZIP64_EXTRA_FIELD_SIZE.min(size));
+        archive_descriptor.write_u16(size);
 
-        archive_descriptor.write_u64(archive_file_entry.uncompressed_size);
-
-        if size >= 16 {
+        if archive_file_entry.zip64_uncompressed_size() == u32::MAX {
+            archive_descriptor.write_u64(archive_file_entry.uncompressed_size);
+        }
+        if archive_file_entry.zip64_compressed_size() == u32::MAX {
             archive_descriptor.write_u64(archive_file_entry.compressed_size);
-            if size >= 24 {
-                archive_descriptor.write_u64(archive_file_entry.offset);
-                if size >= 28 {
-                    archive_descriptor.write_u32(archive_file_entry.file_disk_number);
-                }
-            }
+        }
+        if archive_file_entry.zip64_offset() == u32::MAX {
+            archive_descriptor.write_u64(archive_file_entry.offset);
+        }
+        if archive_file_entry.zip64_file_disk_number() == u16::MAX {
+            archive_descriptor.write_u32(archive_file_entry.file_disk_number);
         }
     }
 
@@ -799,15 +1059,15 @@ pub struct ExtraFieldUnknown {
 }
 
 impl ExtraFieldUnknown {
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn parse_extra_field(
         indexer: &mut ArchiveDescriptorReader,
         extra_field_as_bytes: &[u8],
         extra_field_data_size: u16,
         header_id: u16,
-    ) -> Self {
-        let data = indexer.read_bytes(extra_field_as_bytes, extra_field_data_size as usize);
-        Self { header_id, data }
+    ) -> Result<Self, ArchiveError> {
+        let data = indexer.read_bytes(extra_field_as_bytes, extra_field_data_size as usize)?;
+        Ok(Self { header_id, data })
     }
 }
 
@@ -851,6 +1111,95 @@ impl ExtraField for ExtraFieldUnknown {
     }
 }
 
+/// The "Info-ZIP Unicode Path" extra field, holding the UTF-8 name of an entry whose header name is in a legacy
+/// encoding.
+///
+/// It holds the field version (1), the CRC-32 of the header's name, then the UTF-8 name. The CRC-32 tells if the
+/// field still matches the header's name, as a tool unaware of the field may have renamed the entry.
+///
+/// Use the field definition given in the spec section 4.6.9 "Info-ZIP Unicode Path Extra Field".
+#[cfg(feature = "experimental")]
+#[derive(Debug, Default)]
+pub struct ExtraFieldUnicodePath {
+    version: u8,
+    name_crc32: u32,
+    name: Vec<u8>,
+}
+
+#[cfg(feature = "experimental")]
+impl ExtraFieldUnicodePath {
+    pub const HEADER_ID: u16 = X7075_INFOZIP_UNICODE_PATH;
+
+    pub fn parse_extra_field(
+        indexer: &mut ArchiveDescriptorReader,
+        extra_field_as_bytes: &[u8],
+        extra_field_data_size: u16,
+    ) -> Result<Self, ArchiveError> {
+        let data = indexer.read_bytes(extra_field_as_bytes, extra_field_data_size as usize)?;
+        if data.len() < 5 {
+            return Ok(Self::default());
+        }
+
+        Ok(Self {
+            version: data[0],
+            name_crc32: u32::from_le_bytes([data[1], data[2], data[3], data[4]]),
+            name: data[5..].to_vec(),
+        })
+    }
+
+    /// The UTF-8 name, if the field's version is known and it matches the header's name.
+    pub fn name(&self, header_name: &[u8]) -> Option<String> {
+        if self.version == 1 && crc32fast::hash(header_name) == self.name_crc32 {
+            Some(String::from_utf8_lossy(&self.name).into_owned())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "experimental")]
+impl ExtraField for ExtraFieldUnicodePath {
+    fn local_header_extra_field_size(&self, archive_file_entry: &ArchiveFileEntry) -> u16 {
+        self.central_header_extra_field_size(archive_file_entry)
+    }
+
+    fn central_header_extra_field_size(&self, _archive_file_entry: &ArchiveFileEntry) -> u16 {
+        4 + 5 + self.name.len() as u16
+    }
+
+    fn local_header_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        archive_file_entry: &ArchiveFileEntry,
+    ) {
+        self.central_header_extra_write_data(archive_descriptor, archive_file_entry)
+    }
+
+    fn central_header_extra_write_data(
+        &self,
+        archive_descriptor: &mut ArchiveDescriptor,
+        _archive_file_entry: &ArchiveFileEntry,
+    ) {
+        archive_descriptor.write_u16(ExtraFieldUnicodePath::HEADER_ID);
+        archive_descriptor.write_u16(5 + self.name.len() as u16);
+        archive_descriptor.write_u8(self.version);
+        archive_descriptor.write_u32(self.name_crc32);
+        archive_descriptor.write_bytes(&self.name);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn display_central(&self) -> String {
+        format!(
+            "- A subfield with ID 0x{:04X} (Unicode path) and {} data bytes.",
+            ExtraFieldUnicodePath::HEADER_ID,
+            5 + self.name.len(),
+        )
+    }
+}
+
 /// The archive file complete information.
 ///
 /// Most of this information is located in the archive central registry and it's partly duplicated in thier respective file header.
@@ -875,6 +1224,9 @@ pub struct ArchiveFileEntry {
     pub external_file_attributes: u32,
     pub file_comment: Option<Vec<u8>>,
     pub extra_fields: Vec<Box<dyn ExtraField>>,
+    /// The extra fields as read from an archive, written back verbatim in the central directory if not empty.
+    pub raw_extra_field: Vec<u8>,
+    pub force_zip64: bool,
 }
 
 impl ArchiveFileEntry {
@@ -887,12 +1239,52 @@ impl ArchiveFileEntry {
         }
     }
 
-    fn extended_local_header(&self) -> bool {
-        self.general_purpose_flags & (1u16 << 3) != 0
+    /// The entry's general purpose bit flags, decoded.
+    pub fn flags(&self) -> GeneralPurposeFlags {
+        GeneralPurposeFlags::from(self.general_purpose_flags)
+    }
+
+    /// The minimum version of the specification needed to extract the entry, as major and minor numbers, e.g.
+    /// `(4, 5)` for the zip64 format extensions.
+    pub fn version_needed(&self) -> (u8, u8) {
+        self.minimum_version_needed_to_extract_pretty()
+    }
+
+    /// Tells if the entry's payload is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.general_purpose_flags & ENCRYPTED_FLAG != 0
     }
 
-    fn is_encrypted(&self) -> bool {
-        self.general_purpose_flags & (1u16 << 0) != 0
+    /// Tells if the entry's payload is encrypted with the PKWARE strong encryption.
+    pub fn uses_strong_encryption(&self) -> bool {
+        self.general_purpose_flags & STRONG_ENCRYPTION_FLAG != 0
+    }
+
+    /// Tells if the entry's payload is compressed patched data, which requires PKWARE's patching to be rebuilt.
+    #[cfg(feature = "experimental")]
+    pub fn is_patched(&self) -> bool {
+        self.general_purpose_flags & COMPRESSED_PATCHED_DATA_FLAG != 0
+    }
+
+    /// Check that the entry's payload can be decompressed by this crate.
+    ///
+    /// Returns [`ArchiveError::Unsupported`] for encrypted or patched entries, their payload can't be decompressed
+    /// as is.
+    #[cfg(feature = "experimental")]
+    pub fn check_decompressible(&self) -> Result<(), ArchiveError> {
+        if self.is_encrypted() || self.uses_strong_encryption() {
+            return Err(ArchiveError::Unsupported(format!(
+                "the entry '{}' is encrypted",
+                self.get_file_name()
+            )));
+        }
+        if self.is_patched() {
+            return Err(ArchiveError::Unsupported(format!(
+                "the entry '{}' is compressed patched data",
+                self.get_file_name()
+            )));
+        }
+        Ok(())
     }
 
     fn version_made_by_pretty(&self) -> (u8, u8) {
@@ -919,27 +1311,96 @@ impl ArchiveFileEntry {
         }
     }
 
-    fn system_origin(&self) -> String {
+    /// The file system or operating system of origin, stored in the "version made by".
+    pub fn system(&self) -> FileCompatibilitySystem {
         let system_code = self.version_made_by.to_be_bytes()[0];
-        FileCompatibilitySystem::from_u8(system_code).to_string()
+        FileCompatibilitySystem::from_u8(system_code)
+    }
+
+    fn system_origin(&self) -> String {
+        self.system().to_string()
+    }
+
+    /// The unix file mode, if the system of origin stores one in the external file attributes.
+    pub fn unix_mode(&self) -> Option<u32> {
+        if self.system().has_unix_attributes() {
+            Some((self.external_file_attributes >> 16) & 0xFFFF)
+        } else {
+            None
+        }
     }
 
-    #[cfg(any(feature = "experimental"))]
+    /// The entry's name.
+    ///
+    /// The name is the one of the Unicode Path extra field (0x7075), if the entry has one matching its header's
+    /// name. Otherwise, the header's name is decoded as UTF-8 when the entry's UTF-8 flag is set, from IBM Code
+    /// Page 437 when it isn't.
+    #[cfg(feature = "experimental")]
     pub fn get_file_name(&self) -> String {
-        String::from_utf8_lossy(&self.file_name_as_bytes).to_string()
+        let unicode_path = self.extra_fields.iter().find_map(|extra_field| {
+            extra_field
+                .as_any()
+                .downcast_ref::<ExtraFieldUnicodePath>()?
+                .name(&self.file_name_as_bytes)
+        });
+        if let Some(name) = unicode_path {
+            return name;
+        }
+
+        if self.general_purpose_flags & UTF8_HEADER_FLAG != 0 || self.file_name_as_bytes.is_ascii()
+        {
+            String::from_utf8_lossy(&self.file_name_as_bytes).into_owned()
+        } else {
+            crate::cp437::decode(&self.file_name_as_bytes)
+        }
+    }
+
+    /// The entry's comment, if any.
+    ///
+    /// The comment is decoded as UTF-8 when the entry's UTF-8 flag is set. Otherwise, the comment is
+    /// decoded from IBM Code Page 437, like the file name.
+    #[cfg(feature = "experimental")]
+    pub fn comment(&self) -> Option<Cow<'_, str>> {
+        let comment = self.file_comment.as_ref()?;
+
+        if self.general_purpose_flags & UTF8_HEADER_FLAG != 0 || comment.is_ascii() {
+            Some(String::from_utf8_lossy(comment))
+        } else {
+            Some(Cow::Owned(crate::cp437::decode(comment)))
+        }
     }
 
+    /// Tells if the entry uses the ZIP64 format extensions, i.e. its central directory header needs them.
     pub fn is_zip64(&self) -> bool {
-        self.uncompressed_size >= u32::MAX as u64
-            || self.offset >= u32::MAX as u64
+        self.needs_zip64_central()
+    }
+
+    /// Tells if the local header needs the ZIP64 extra field: it only holds the sizes, the offset doesn't matter.
+    pub fn needs_zip64_local(&self) -> bool {
+        self.force_zip64
+            || self.uncompressed_size >= u32::MAX as u64
             || self.compressed_size >= u32::MAX as u64
     }
 
+    /// Tells if the central directory header needs the ZIP64 extra field, for the sizes, the offset or the disk
+    /// number.
+    pub fn needs_zip64_central(&self) -> bool {
+        self.needs_zip64_local()
+            || self.offset >= u32::MAX as u64
+            || self.file_disk_number >= u16::MAX as u32
+    }
+
     pub fn zip64_compressed_size(&self) -> u32 {
+        if self.force_zip64 {
+            return u32::MAX;
+        }
         self.compressed_size.min(u32::MAX as u64) as u32
     }
 
     pub fn zip64_uncompressed_size(&self) -> u32 {
+        if self.force_zip64 {
+            return u32::MAX;
+        }
         self.uncompressed_size.min(u32::MAX as u64) as u32
     }
 
@@ -947,6 +1408,10 @@ impl ArchiveFileEntry {
         self.offset.min(u32::MAX as u64) as u32
     }
 
+    pub fn zip64_file_disk_number(&self) -> u16 {
+        self.file_disk_number.min(u16::MAX as u32) as u16
+    }
+
     const TEXT_INDICATOR: u16 = 1;
 
     pub fn apparently_text_file(&mut self, is_text: bool) {
@@ -957,7 +1422,7 @@ impl ArchiveFileEntry {
         }
     }
 
-    #[cfg(any(feature = "experimental"))]
+    #[cfg(feature = "experimental")]
     pub fn is_dir(&mut self) {
         self.internal_file_attributes &= !ArchiveFileEntry::TEXT_INDICATOR
     }
@@ -992,9 +1457,17 @@ impl ArchiveFileEntry {
     }
 
     pub fn need_to_add_zip64_extra_field(&mut self) {
-        if !self.has_zip64_extra_field() && self.is_zip64() {
+        if !self.has_zip64_extra_field() && self.needs_zip64_central() {
             let zip_extra_field = ExtraFieldZIP64ExtendedInformation::default();
-            self.extra_fields.push(Box::new(zip_extra_field));
+
+            //the alignment padding stays the last field
+            let position = self
+                .extra_fields
+                .iter()
+                .position(|extra_field| extra_field.as_any().is::<ExtraFieldAlignment>())
+                .unwrap_or(self.extra_fields.len());
+            self.extra_fields
+                .insert(position, Box::new(zip_extra_field));
         }
     }
 }
@@ -1055,7 +1528,10 @@ impl Display for ArchiveFileEntry {
 
         writeln!(f, "{: <padding$}{}", "compression method:", label)?;
 
-        let extended_local_header = if self.is_encrypted() {
+        let flags = self.flags();
+        let extended_local_header = if flags.strong_encryption {
+            "encrypted (strong encryption)"
+        } else if flags.encrypted {
             "encrypted"
         } else {
             "not encrypted"
@@ -1067,7 +1543,7 @@ impl Display for ArchiveFileEntry {
             "file security status:", extended_local_header
         )?;
 
-        let extended_local_header = if self.extended_local_header() {
+        let extended_local_header = if flags.has_data_descriptor {
             "yes"
         } else {
             "no"
@@ -1157,14 +1633,22 @@ impl Display for ArchiveFileEntry {
 
         writeln!(f, "{: <padding$}{:}", "apparent file type:", file_type)?;
 
-        let unix_file_attributes = (self.external_file_attributes >> 16) & 0xFFFF;
-        let label = format!("Unix file attributes ({:06o} octal):", unix_file_attributes);
-        writeln!(
-            f,
-            "{: <padding$}{:}",
-            label,
-            readable_file_unix_attributes(unix_file_attributes)
-        )?;
+        if let Some(unix_file_attributes) = self.unix_mode() {
+            let label = format!("Unix file attributes ({:06o} octal):", unix_file_attributes);
+            writeln!(
+                f,
+                "{: <padding$}{:}",
+                label,
+                readable_file_unix_attributes(unix_file_attributes)
+            )?;
+        } else {
+            let non_dos_file_attributes = (self.external_file_attributes >> 16) & 0xFFFF;
+            writeln!(
+                f,
+                "{: <padding$}{:06X} hex",
+                "non-MSDOS external file attributes:", non_dos_file_attributes
+            )?;
+        }
 
         let dos_file_attributes = self.external_file_attributes & 0xFF;
         let label = format!("MS-DOS file attributes ({:0X} hex): ", dos_file_attributes);
@@ -1245,6 +1729,22 @@ fn readable_file_unix_attributes(file_attributes: u32) -> String {
         s.replace_range(1..2, "r");
     }
 
+    //the setuid, setgid and sticky bits replace the execute ones, in uppercase if not executable
+    for (bit, position, executable, not_executable) in [
+        (0o4000, 3, "s", "S"),
+        (0o2000, 6, "s", "S"),
+        (0o1000, 9, "t", "T"),
+    ] {
+        if file_attributes & bit != 0 {
+            let letter = if &s[position..position + 1] == "x" {
+                executable
+            } else {
+                not_executable
+            };
+            s.replace_range(position..position + 1, letter);
+        }
+    }
+
     if file_attributes & S_IFDIR != 0 {
         s.replace_range(0..1, "d");
     }
@@ -1270,7 +1770,7 @@ mod external_fields_tests;
 mod test {
 
     use super::*;
-    use crate::constants::LOCAL_FILE_HEADER_SIGNATURE;
+    use crate::constants::{EXTENDED_LOCAL_HEADER_FLAG, LOCAL_FILE_HEADER_SIGNATURE};
 
     #[test]
     fn test_write_file_header() {
@@ -1283,7 +1783,7 @@ mod test {
         let compressed_size = 0u32;
         let uncompressed_size = 0u32;
         let file_name = "file1.txt";
-        let file_name_len = file_name.as_bytes().len() as u16;
+        let file_name_len = file_name.len() as u16;
 
         println!("file_name {:?} length: {:}", file_name, file_name_len);
         let extra_field_length = 0u16;
@@ -1310,6 +1810,46 @@ mod test {
         print!("{:#?}", entry)
     }
 
+    #[test]
+    fn test_read_non_utf8_file_name() {
+        //"réadme.txt" encoded with CP437
+        let file_name = b"r\x82adme.txt";
+
+        let mut desc = ArchiveDescriptor::new(100);
+        desc.write_u32(LOCAL_FILE_HEADER_SIGNATURE);
+        desc.write_bytes(&[0; 22]);
+        desc.write_u16(file_name.len() as u16);
+        desc.write_u16(0);
+        desc.write_bytes(file_name);
+        let vec = desc.finish();
+
+        let entry = ArchiveDescriptor::read_file_descriptor(&vec).unwrap();
+        assert_eq!(entry.file_name_as_bytes, file_name);
+
+        let mut indexer = ArchiveDescriptorReader::new();
+        let name = indexer
+            .read_utf8_string(file_name, file_name.len())
+            .unwrap();
+        assert_eq!(name, "r\u{FFFD}adme.txt");
+    }
+
+    #[test]
+    fn test_read_truncated_file_descriptor() {
+        let mut desc = ArchiveDescriptor::new(100);
+        desc.write_u32(LOCAL_FILE_HEADER_SIGNATURE);
+        desc.write_bytes(&[0; 22]);
+        desc.write_u16(9);
+        desc.write_u16(0);
+        desc.write_bytes(b"file");
+        let vec = desc.finish();
+
+        let entry = ArchiveDescriptor::read_file_descriptor(&vec);
+        assert!(matches!(entry, Err(ArchiveError::BadArchiveStructure(_))));
+
+        let entry = ArchiveDescriptor::read_file_descriptor(&vec[..10]);
+        assert!(matches!(entry, Err(ArchiveError::BadArchiveStructure(_))));
+    }
+
     #[test]
     fn test_mem_dump() {
         let vec: Vec<u8> = vec![
@@ -1413,4 +1953,70 @@ mod test {
         let val = 0o644;
         println!("{:o} {}", val, readable_file_unix_attributes(val));
     }
+
+    #[test]
+    fn test_encryption_and_patched_flags() {
+        let mut entry = ArchiveFileEntry {
+            file_name_as_bytes: b"file.txt".to_vec(),
+            ..Default::default()
+        };
+        assert!(entry.check_decompressible().is_ok());
+
+        entry.general_purpose_flags = ENCRYPTED_FLAG | STRONG_ENCRYPTION_FLAG;
+        assert!(entry.is_encrypted());
+        assert!(entry.uses_strong_encryption());
+        assert!(!entry.is_patched());
+        assert!(matches!(
+            entry.check_decompressible(),
+            Err(ArchiveError::Unsupported(_))
+        ));
+
+        entry.general_purpose_flags = COMPRESSED_PATCHED_DATA_FLAG;
+        assert!(!entry.is_encrypted());
+        assert!(entry.is_patched());
+        assert!(matches!(
+            entry.check_decompressible(),
+            Err(ArchiveError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_flags_and_version_needed() {
+        let entry = ArchiveFileEntry {
+            minimum_version_needed_to_extract: 45,
+            general_purpose_flags: ENCRYPTED_FLAG | EXTENDED_LOCAL_HEADER_FLAG | UTF8_HEADER_FLAG,
+            ..Default::default()
+        };
+
+        assert_eq!(entry.version_needed(), (4, 5));
+        assert_eq!(
+            entry.flags(),
+            GeneralPurposeFlags {
+                encrypted: true,
+                has_data_descriptor: true,
+                strong_encryption: false,
+                utf8: true,
+            }
+        );
+        assert_eq!(
+            ArchiveFileEntry::default().flags(),
+            GeneralPurposeFlags::default()
+        );
+    }
+
+    #[test]
+    fn test_comment_without_utf8_flag() {
+        let entry = ArchiveFileEntry {
+            file_comment: Some(vec![b'a', 0x82, b'b']),
+            ..Default::default()
+        };
+        assert_eq!(entry.comment().as_deref(), Some("aéb"));
+
+        let entry = ArchiveFileEntry {
+            general_purpose_flags: UTF8_HEADER_FLAG,
+            file_comment: Some("é".as_bytes().to_vec()),
+            ..Default::default()
+        };
+        assert_eq!(entry.comment().as_deref(), Some("é"));
+    }
 }
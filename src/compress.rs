@@ -48,12 +48,68 @@ pub mod std;
 #[cfg(feature = "tokio")]
 pub mod tokio;
 
-mod common;
+pub mod blocking;
+pub(crate) mod common;
+pub mod sink;
+pub mod tee;
 
+#[cfg(unix)]
+use crate::constants::FILE_DEFAULT;
+#[cfg(feature = "digest")]
+use crate::digest::DigestKind;
 use crate::{
+    archive_common::ArchiveFileEntry,
     compression::{CompressionMethod, Level},
-    types::{FileCompatibilitySystem, FileDateTime},
+    constants::DEFAULT_VERSION,
+    crc32::{new_hasher, Crc32, Crc32Factory},
+    types::{DateTimeCS, FileCompatibilitySystem, FileDateTime, PathSeparator, UidGidFormat},
 };
+use ::std::time::Duration;
+
+/// The default number of leading payload bytes compressed to sample an entry's compression ratio, see
+/// [`FileOptions::adaptive`].
+pub const DEFAULT_ADAPTIVE_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// The default compression ratio (compressed size over uncompressed size) above which a sampled entry is
+/// stored, see [`FileOptions::adaptive`].
+pub const DEFAULT_ADAPTIVE_THRESHOLD: f64 = 0.95;
+
+/// Information about an entry appended to the archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppendOutcome {
+    /// The CRC-32 of the entry's uncompressed payload.
+    pub crc32: u32,
+
+    /// The entry's size once compressed.
+    pub compressed_size: u64,
+
+    /// The entry's original size.
+    pub uncompressed_size: u64,
+
+    /// The digest of the entry's uncompressed payload, if one was requested with
+    /// `FileOptions::with_digest`.
+    pub digest: Option<Vec<u8>>,
+
+    /// The entry's index in the archive, in the append order, e.g. to update its comment with
+    /// `ZipArchive::set_entry_comment` before the archive is finalized.
+    pub index: usize,
+}
+
+/// Information about a finalized archive.
+#[derive(Debug)]
+pub struct FinalizeReport<W> {
+    /// The archive size (bytes).
+    pub size: u64,
+
+    /// The writer passed at the archive creation.
+    pub writer: W,
+
+    /// The number of entries in the central directory.
+    pub entry_count: usize,
+
+    /// Are the zip64 format extensions used, by an entry or for the end of central directory.
+    pub used_zip64: bool,
+}
 
 /// Metadata for a file to be archived
 #[derive(Clone)]
@@ -76,20 +132,147 @@ pub struct FileOptions<'a> {
     /// Unix permissions.
     pub unix_permissions: Option<u32>,
 
+    /// Unix permissions of the directory entries, overriding `unix_permissions`.
+    pub dir_permissions: Option<u32>,
+
+    /// The Unix owner of the entry, its user id and group id.
+    pub unix_owner: Option<(u32, u32)>,
+
+    /// The extra field storing the Unix owner.
+    pub unix_uid_gid_format: UidGidFormat,
+
+    /// The alignment of the stored entries' data, in bytes.
+    pub alignment: Option<u16>,
+
     /// The system of origin.
     pub system: FileCompatibilitySystem,
 
+    /// The ZIP specification version of the producer, the low byte of "version made by".
+    pub made_by_version: u8,
+
+    /// The separator of the path components in the entry's name.
+    pub path_separator: PathSeparator,
+
     /// File comment.
-    pub comment: Option<&'a str>,
+    pub comment: Option<&'a [u8]>,
 
     /// Indicator of fize size > (u32::MAX)
     pub large_file: bool,
 
     /// Is the compressor will check the apparent file type
     pub detect_file_type: bool,
+
+    /// The apparent file type set by the caller, skipping its detection
+    pub text_file: Option<bool>,
+
+    /// Is an empty payload stored instead of being compressed
+    pub store_empty: bool,
+
+    /// The maximum uncompressed size of the entry
+    pub max_entry_size: Option<u64>,
+
+    /// The maximum time spent appending the entry before it is stored instead
+    pub time_budget: Option<Duration>,
+
+    /// Is the compression method selected from a sampled compression ratio
+    pub adaptive: bool,
+
+    /// The number of leading payload bytes compressed to sample the compression ratio
+    pub adaptive_sample_size: usize,
+
+    /// The sampled compression ratio above which the entry is stored
+    pub adaptive_threshold: f64,
+
+    /// Is the data descriptor preceded by its (optional) signature
+    pub data_descriptor_signature: bool,
+
+    /// Is the extended timestamp extra field written when a Unix timestamp is available
+    pub extended_timestamp: bool,
+
+    /// Is the extended timestamp extra field written in the central header only
+    pub minimal_local_extra: bool,
+
+    /// Are the sizes of a stored entry of known length written in the local header of a streamable archive
+    pub inline_store_size: bool,
+
+    /// The payload CRC-32 provided by the caller, skipping its computation.
+    pub known_crc32: Option<u32>,
+
+    /// Is the CRC-32 provided by the caller checked against the computed one
+    pub verify_known_crc32: bool,
+
+    /// Creates the hasher computing the entry's CRC-32.
+    pub(crate) crc32_hasher: Crc32Factory,
+
+    /// Additional digest computed over the uncompressed payload.
+    #[cfg(feature = "digest")]
+    pub digest: Option<DigestKind>,
 }
 
 impl<'a> FileOptions<'a> {
+    /// Create the options recreating a parsed entry, e.g. to re-pack it in a new archive.
+    ///
+    /// It maps the entry's compression method, its MS-DOS modification time, its unix permissions (the high 16
+    /// bits of the external file attributes, if the system of origin stores them) and its comment, kept byte for
+    /// byte. An unknown compression method is replaced by the default one. The other options have their default
+    /// value.
+    pub fn from_entry(entry: &'a ArchiveFileEntry) -> FileOptions<'a> {
+        let mut options = FileOptions::default();
+
+        if !matches!(entry.compressor, CompressionMethod::Unknown(_)) {
+            options.compression_method = entry.compressor;
+        }
+
+        options.last_modified_time = FileDateTime::Custom(DateTimeCS::from_msdos(
+            entry.last_mod_file_date,
+            entry.last_mod_file_time,
+        ));
+
+        if let Some(mode) = entry.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+
+        options.comment = entry.file_comment.as_deref();
+
+        options
+    }
+
+    /// The options with the modification time and the execute bits of a file.
+    ///
+    /// The modification time is taken unless another time than the default one is set. It is kept as is if the
+    /// file system can't tell it or it can't be stored as a Unix timestamp. On Unix, unless the permissions are
+    /// set, the file's execute bits are added to the default `0o644` permissions, so a `0o755` script stays
+    /// runnable.
+    pub(crate) fn with_file_metadata(
+        &self,
+        metadata: ::std::io::Result<::std::fs::Metadata>,
+    ) -> FileOptions<'a> {
+        let mut options = self.clone();
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(_) => return options,
+        };
+
+        let timestamp = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(::std::time::UNIX_EPOCH).ok())
+            .and_then(|since_epoch| i32::try_from(since_epoch.as_secs()).ok());
+        if let Some(timestamp) = timestamp {
+            if options.last_modified_time == FileDateTime::Now {
+                options.last_modified_time = FileDateTime::UnixCustom(timestamp);
+            }
+        }
+
+        #[cfg(unix)]
+        if options.unix_permissions.is_none() {
+            use ::std::os::unix::fs::PermissionsExt;
+            options.unix_permissions = Some(FILE_DEFAULT | (metadata.permissions().mode() & 0o111));
+        }
+
+        options
+    }
+
     /// Set the compression method for the new file
     ///
     /// The default is `CompressionMethod::Deflated`.
@@ -100,6 +283,8 @@ impl<'a> FileOptions<'a> {
     }
 
     /// Set the compression level for the new file
+    ///
+    /// The default is `Level::Inherit`, the archive's default level.
     pub fn compression_level(mut self, level: Level) -> FileOptions<'a> {
         self.compression_level = level;
         self
@@ -127,8 +312,102 @@ impl<'a> FileOptions<'a> {
         self
     }
 
+    /// Set the full file mode bits for the new file: the permissions and the setuid (`0o4000`), setgid
+    /// (`0o2000`) and sticky (`0o1000`) bits, e.g. for a backup.
+    ///
+    /// Unlike [`FileOptions::unix_permissions`], it preserves the mode via a `& 0o7777`. As the file type is
+    /// still set by the archive, it can't denote an entry as a symlink or other special file type either.
+    pub fn unix_mode_full(mut self, mode: u32) -> FileOptions<'a> {
+        self.unix_permissions = Some(mode & 0o7777);
+        self
+    }
+
+    /// Set the permissions for the new directory, so files and directories appended with the same options
+    /// can have different permissions.
+    ///
+    /// If not set, a directory gets the [`FileOptions::unix_permissions`] if set, `0o755` otherwise. As for the
+    /// files, only the permissions bits are preserved (via a `& 0o777`).
+    pub fn dir_permissions(mut self, mode: u32) -> FileOptions<'a> {
+        self.dir_permissions = Some(mode & 0o777);
+        self
+    }
+
+    /// Set the Unix owner of the entry, its user id and group id, stored in an Info-ZIP extra field.
+    ///
+    /// The default is no owner, the field isn't written.
+    pub fn unix_owner(mut self, uid: u32, gid: u32) -> FileOptions<'a> {
+        self.unix_owner = Some((uid, gid));
+        self
+    }
+
+    /// Set the extra field storing the Unix owner set by [`Self::unix_owner`].
+    ///
+    /// `UidGidFormat::Unix16` is for the extractors only knowing the 0x7855 field, its ids are 16 bits: an owner
+    /// not fitting in them is stored in the 0x7875 field instead.
+    ///
+    /// The default is `UidGidFormat::AnySize`.
+    pub fn unix_uid_gid_format(mut self, format: UidGidFormat) -> FileOptions<'a> {
+        self.unix_uid_gid_format = format;
+        self
+    }
+
+    /// Align the stored entries' data on a byte boundary, e.g. `4` as zipalign does, so they can be accessed from
+    /// a memory-mapped archive.
+    ///
+    /// The local header is padded with the Android alignment extra field (0xD935), sized so the data starts at an
+    /// offset multiple of `alignment`. The compressed entries and the directories aren't aligned.
+    ///
+    /// The default is no alignment.
+    pub fn align(mut self, alignment: u16) -> FileOptions<'a> {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// Set the system of origin, stored as the host OS in the entry's "version made by".
+    ///
+    /// The system tells how the external file attributes are interpreted: for _Unix_ and _OsX_
+    /// the unix permissions are stored in the high 16 bits, otherwise only the MS-DOS attributes
+    /// are stored.
+    ///
+    /// The default is `FileCompatibilitySystem::Unix`.
+    pub fn system(mut self, system: FileCompatibilitySystem) -> FileOptions<'a> {
+        self.system = system;
+        self
+    }
+
+    /// Set the ZIP specification version of the producer, stored as the low byte of the entry's "version made by",
+    /// e.g. `63` to mimic a producer implementing the specification 6.3 for compatibility testing.
+    ///
+    /// The high byte stays the [`Self::system`] of origin. The version needed to extract isn't affected.
+    ///
+    /// The default is `46`, for the specification 4.6.
+    pub fn made_by_version(mut self, version: u8) -> FileOptions<'a> {
+        self.made_by_version = version;
+        self
+    }
+
+    /// Set the separator of the path components in the entry's name.
+    ///
+    /// With `PathSeparator::Backward`, the forward slashes of the name are replaced by backslashes. It goes
+    /// against the ZIP specification, it is only meant for legacy tools expecting them.
+    ///
+    /// The default is `PathSeparator::Forward`, the name is written as given.
+    pub fn path_separator(mut self, path_separator: PathSeparator) -> FileOptions<'a> {
+        self.path_separator = path_separator;
+        self
+    }
+
     /// Set the file comment.
     pub fn set_file_comment(mut self, comment: &'a str) -> FileOptions<'a> {
+        self.comment = Some(comment.as_bytes());
+        self
+    }
+
+    /// Set the file comment as raw bytes, e.g. a legacy comment in another encoding than UTF-8.
+    ///
+    /// The bytes are written as is. The entry is flagged as UTF-8 encoded only if the comment is valid, non
+    /// ASCII, UTF-8 text (or the name is non ASCII).
+    pub fn set_file_comment_bytes(mut self, comment: &'a [u8]) -> FileOptions<'a> {
         self.comment = Some(comment);
         self
     }
@@ -139,7 +418,8 @@ impl<'a> FileOptions<'a> {
     /// (as for a streamable zip archive).
     ///
     /// If set to `true`, the local header will include the zip64 extra field. If the file does not exceed the limit, 20 bytes
-    /// will be wasted.
+    /// will be wasted. In a streamable archive, the field holds placeholder sizes and the entry is followed by a zip64
+    /// data descriptor: the archive is still written forward only.
     ///
     /// The default value is `false`.
     pub fn large_file(mut self, large: bool) -> FileOptions<'a> {
@@ -154,12 +434,187 @@ impl<'a> FileOptions<'a> {
     ///
     /// More information detailed there: [txtvsbin.txt](https://github.com/LuaDist/zip/blob/master/proginfo/txtvsbin.txt)
     ///
+    /// Without detection, the entry is recorded as a binary file unless set otherwise with [`Self::text_file`].
+    ///
     /// Default value: true
     pub fn detect_file_type(mut self, detect_file_type: bool) -> FileOptions<'a> {
         self.detect_file_type = detect_file_type;
         self
     }
 
+    /// Set the apparent file type of the entry, a plain text or a binary file, without reading its payload.
+    ///
+    /// It takes precedence over [`Self::detect_file_type`]: no detection is done at all, whatever its value.
+    pub fn text_file(mut self, text_file: bool) -> FileOptions<'a> {
+        self.text_file = Some(text_file);
+        self
+    }
+
+    /// Compute an additional digest of the entry's uncompressed payload.
+    ///
+    /// The digest is computed in the same pass as the CRC-32 and returned in the
+    /// [`AppendOutcome`] of the `append` call.
+    ///
+    /// # Features
+    ///
+    /// Requires `digest` feature
+    #[cfg(feature = "digest")]
+    pub fn with_digest(mut self, kind: DigestKind) -> FileOptions<'a> {
+        self.digest = Some(kind);
+        self
+    }
+
+    /// Set whether an empty payload is stored instead of being compressed.
+    ///
+    /// Most compression methods emit a few bytes even for an empty input. If set to `true`, an entry with an
+    /// empty payload is recorded with the _store_ method and a zero compressed size.
+    ///
+    /// The default value is `false`.
+    pub fn store_empty(mut self, store_empty: bool) -> FileOptions<'a> {
+        self.store_empty = store_empty;
+        self
+    }
+
+    /// Set whether the compression method is selected from a sampled compression ratio.
+    ///
+    /// If set to `true`, the first [`adaptive_sample_size`](Self::adaptive_sampling) bytes of the payload are
+    /// buffered in memory and compressed with the selected method. If the ratio of the compressed size over the
+    /// sample size is above the [`adaptive_threshold`](Self::adaptive_sampling), the payload is considered
+    /// incompressible and the whole entry is recorded with the _store_ method.
+    ///
+    /// Unlike [`CompressionMethod::Auto`], it works for any data, whatever the entry's name. Both can be
+    /// combined: the data is only sampled if the name doesn't already select the _store_ method.
+    ///
+    /// The default value is `false`.
+    pub fn adaptive(mut self, adaptive: bool) -> FileOptions<'a> {
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// Set the tunables of the [`adaptive`](Self::adaptive) compression method selection: the number of leading
+    /// payload bytes sampled and the compression ratio above which the entry is stored.
+    ///
+    /// A larger sample gives a better estimate but is held in memory and compressed twice.
+    ///
+    /// The default values are [`DEFAULT_ADAPTIVE_SAMPLE_SIZE`] and [`DEFAULT_ADAPTIVE_THRESHOLD`].
+    pub fn adaptive_sampling(mut self, sample_size: usize, threshold: f64) -> FileOptions<'a> {
+        self.adaptive_sample_size = sample_size;
+        self.adaptive_threshold = threshold;
+        self
+    }
+
+    /// Set the maximum uncompressed size of the entry, to bound the resources used by untrusted payloads.
+    ///
+    /// The append fails with [`ArchiveError::EntrySizeLimitExceeded`](crate::error::ArchiveError) as soon as the
+    /// payload exceeds it. The partially written entry is left out of the central directory, so the archive can
+    /// still be finalized and other entries appended.
+    ///
+    /// The default value is `None`, no limit.
+    pub fn max_entry_size(mut self, max_entry_size: Option<u64>) -> FileOptions<'a> {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// Set the maximum time an append of a reader spends compressing the entry, to bound the tail latencies.
+    ///
+    /// In a seekable archive, once the budget is exceeded, the entry is rolled back and appended again as
+    /// _stored_: the payload read so far is kept in memory to be written again, up to 64 MiB, past it the append
+    /// fails. Only the budget rolls the entry back, the other errors are returned. The sink isn't truncated: if the
+    /// stored entry is shorter than the compressed bytes already written, e.g. for an incompressible payload, the
+    /// entry is left out with
+    /// [`ArchiveError::InconsistentArchive`](crate::error::ArchiveError::InconsistentArchive).
+    ///
+    /// In a streamable archive, the written bytes can't be rolled back, so the budget is best effort: it is
+    /// checked only before the compression starts, e.g. after the adaptive sampling.
+    ///
+    /// The default value is `None`, no limit.
+    pub fn time_budget(mut self, budget: Duration) -> FileOptions<'a> {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Set whether the data descriptor written after a streamed entry starts with its signature.
+    ///
+    /// The signature is optional per the specification, but most extractors expect it. If set to `false`, the
+    /// data descriptor only holds the CRC-32 and the sizes, on 64 bits for a zip64 entry.
+    ///
+    /// The default value is `true`.
+    pub fn data_descriptor_signature(mut self, signature: bool) -> FileOptions<'a> {
+        self.data_descriptor_signature = signature;
+        self
+    }
+
+    /// Set the CRC-32 of the entry's uncompressed payload, when it is already known
+    /// (e.g. stored in a database).
+    ///
+    /// The archiver will not compute the CRC-32 while compressing the payload, it will use the provided
+    /// value instead. The value is __not__ verified, unless [`Self::verify_known_crc32`] is set: a wrong value
+    /// produces an entry that extractors will report as corrupted.
+    pub fn known_crc32(mut self, crc32: u32) -> FileOptions<'a> {
+        self.known_crc32 = Some(crc32);
+        self
+    }
+
+    /// Set whether the CRC-32 provided with [`Self::known_crc32`] is checked against the one of the payload.
+    ///
+    /// The CRC-32 is then computed anyway, a mismatch fails the append with
+    /// [`ArchiveError::Crc32Mismatch`](crate::error::ArchiveError::Crc32Mismatch) and the entry is left out of
+    /// the central directory.
+    ///
+    /// The default value is `false`.
+    pub fn verify_known_crc32(mut self, verify: bool) -> FileOptions<'a> {
+        self.verify_known_crc32 = verify;
+        self
+    }
+
+    /// Set the implementation used to compute the entry's CRC-32.
+    ///
+    /// The default implementation is [`crc32fast::Hasher`].
+    pub fn crc32_hasher<C: Crc32 + Send + 'static>(mut self) -> FileOptions<'a> {
+        self.crc32_hasher = new_hasher::<C>;
+        self
+    }
+
+    /// Set whether the extended timestamp extra field (0x5455) is written.
+    ///
+    /// When a Unix timestamp is available, from [`Self::time_stamp`] or the last modified time, it is written
+    /// in an extended timestamp extra field. If set to `false`, the field is never written, whatever the
+    /// timestamp settings: the entry only records the MS-DOS time, saving the field's bytes in both headers.
+    ///
+    /// The default value is `true`.
+    pub fn extended_timestamp(mut self, extended_timestamp: bool) -> FileOptions<'a> {
+        self.extended_timestamp = extended_timestamp;
+        self
+    }
+
+    /// Set whether the extended timestamp extra field (0x5455) is left out of the local header.
+    ///
+    /// The field is written in both headers, the local one holding all the times and the central one only the
+    /// modification time. If set to `true`, it is written in the central header only, saving its bytes in each
+    /// local header, e.g. for an archive of many small files. The loss of fidelity is slight: the access and
+    /// creation times are no longer recorded, and an extractor reading the local headers only (e.g. streaming)
+    /// gets the MS-DOS modification time.
+    ///
+    /// The default value is `false`.
+    pub fn minimal_local_extra(mut self, minimal_local_extra: bool) -> FileOptions<'a> {
+        self.minimal_local_extra = minimal_local_extra;
+        self
+    }
+
+    /// Set whether a stored entry of known length gets its CRC-32 and sizes in the local header of a streamable
+    /// archive, so it is self-describing without a data descriptor.
+    ///
+    /// It applies to the `append_sized` entries with the [`CompressionMethod::Store`] method, whose payload is at
+    /// most 1 MiB: the payload is read in memory, then appended as by `append_bytes`. If set to `false`, or for a
+    /// larger payload, the local header has zero sizes and the entry is followed by a data descriptor, as for any
+    /// streamed entry. The `append_bytes` entries always have a complete local header, their sizes being known.
+    ///
+    /// The default value is `true`.
+    pub fn inline_store_size(mut self, inline_store_size: bool) -> FileOptions<'a> {
+        self.inline_store_size = inline_store_size;
+        self
+    }
+
     /// Set the entry unix timestamp.
     ///
     /// The time values are in standard Unix signed-long format, indicating
@@ -188,15 +643,37 @@ impl<'a> Default for FileOptions<'a> {
     fn default() -> Self {
         Self {
             compression_method: CompressionMethod::Deflate(),
-            compression_level: Level::Default,
+            compression_level: Level::Inherit,
             last_modified_time: FileDateTime::Now,
             unix_permissions: None,
+            dir_permissions: None,
+            unix_owner: None,
+            unix_uid_gid_format: UidGidFormat::AnySize,
+            alignment: None,
             system: FileCompatibilitySystem::Unix,
+            made_by_version: DEFAULT_VERSION,
+            path_separator: PathSeparator::Forward,
             comment: None,
             large_file: false,
             detect_file_type: true,
+            text_file: None,
+            store_empty: false,
+            max_entry_size: None,
+            time_budget: None,
+            adaptive: false,
+            adaptive_sample_size: DEFAULT_ADAPTIVE_SAMPLE_SIZE,
+            adaptive_threshold: DEFAULT_ADAPTIVE_THRESHOLD,
+            data_descriptor_signature: true,
+            extended_timestamp: true,
+            minimal_local_extra: false,
+            inline_store_size: true,
+            known_crc32: None,
+            verify_known_crc32: false,
+            crc32_hasher: new_hasher::<crc32fast::Hasher>,
             last_creation_time: None,
             last_access_time: None,
+            #[cfg(feature = "digest")]
+            digest: None,
         }
     }
 }
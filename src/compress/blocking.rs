@@ -0,0 +1,179 @@
+//! An [`AsyncWrite`] over a blocking [`Write`], for the tokio archive.
+//!
+//! Some destinations only offer a blocking writer, e.g. an SDK expecting a blocking body. Instead of bridging
+//! them with a [`duplex`](tokio::io::duplex) pipe and a dedicated thread, a [`BlockingSink`] runs each write on
+//! tokio's blocking thread pool, with [`spawn_blocking`](tokio::task::spawn_blocking).
+//!
+//!```rust
+//! use archflow::{
+//!     compress::blocking::BlockingSink, compress::tokio::archive::ZipArchive, compress::FileOptions,
+//!     error::ArchiveError,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), ArchiveError> {
+//! let mut archive = ZipArchive::new_streamable(BlockingSink::new(Vec::new()));
+//! archive
+//!     .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+//!     .await?;
+//! let (archive_size, sink) = archive.finalize().await?;
+//!
+//! let buffer = sink.into_inner().await?;
+//! # assert_eq!(buffer.len() as u64, archive_size);
+//! # Ok(())
+//! # }
+//!```
+
+use std::future::Future;
+use std::io::{Error, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::AsyncWrite;
+use tokio::task::JoinHandle;
+
+enum State<W> {
+    /// No operation is running, the writer is available (unless it was lost by a panic)
+    Idle(Option<W>),
+    /// An operation is running on the blocking thread pool
+    Busy(JoinHandle<(W, Result<(), Error>)>),
+}
+
+/// A blocking [`Write`] exposed as an [`AsyncWrite`], each operation running on tokio's blocking thread pool.
+///
+/// As for [`tokio::fs::File`], a write copies the buffer and completes right away: its error, if any, is returned
+/// by the next operation. The archive's [`finalize`](crate::compress::tokio::archive::ZipArchive::finalize())
+/// flushes the sink, so every error is reported by then.
+///
+/// It must be used within a tokio runtime.
+pub struct BlockingSink<W> {
+    state: State<W>,
+    /// Is the running operation a flush
+    flushing: bool,
+}
+
+impl<W: Write + Send + Unpin + 'static> BlockingSink<W> {
+    pub fn new(writer: W) -> BlockingSink<W> {
+        BlockingSink {
+            state: State::Idle(Some(writer)),
+            flushing: false,
+        }
+    }
+
+    /// Get back the blocking writer, once the running operation is complete.
+    pub async fn into_inner(mut self) -> Result<W, Error> {
+        std::future::poll_fn(|cx| self.poll_complete(cx)).await?;
+        match self.state {
+            State::Idle(Some(writer)) => Ok(writer),
+            _ => Err(lost_writer()),
+        }
+    }
+
+    /// Wait for the running operation, if any, returns its error.
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if let State::Busy(handle) = &mut self.state {
+            let joined = ready!(Pin::new(handle).poll(cx));
+            return match joined {
+                Ok((writer, result)) => {
+                    self.state = State::Idle(Some(writer));
+                    Poll::Ready(result)
+                }
+                Err(error) => {
+                    self.state = State::Idle(None);
+                    Poll::Ready(Err(Error::other(error)))
+                }
+            };
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Run the operation on the blocking thread pool, the sink must be idle.
+    fn spawn<F>(&mut self, operation: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut W) -> Result<(), Error> + Send + 'static,
+    {
+        let mut writer = match &mut self.state {
+            State::Idle(writer) => writer.take().ok_or_else(lost_writer)?,
+            State::Busy(_) => return Err(Error::other("an operation is already running")),
+        };
+
+        self.state = State::Busy(tokio::task::spawn_blocking(move || {
+            let result = operation(&mut writer);
+            (writer, result)
+        }));
+        Ok(())
+    }
+
+    /// Flush the writer and wait for the flush.
+    fn poll_flush_writer(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if !self.flushing {
+            ready!(self.poll_complete(cx))?;
+            self.spawn(|writer| writer.flush())?;
+            self.flushing = true;
+        }
+
+        let flushed = ready!(self.poll_complete(cx));
+        self.flushing = false;
+        Poll::Ready(flushed)
+    }
+}
+
+fn lost_writer() -> Error {
+    Error::other("the blocking writer was lost by a panicking operation")
+}
+
+impl<W: Write + Send + Unpin + 'static> AsyncWrite for BlockingSink<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        let sink = self.get_mut();
+        ready!(sink.poll_complete(cx))?;
+
+        let bytes = buf.to_vec();
+        sink.spawn(move |writer| writer.write_all(&bytes))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_flush_writer(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut().poll_flush_writer(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BlockingSink;
+    use std::io::{Error, ErrorKind, Write};
+    use tokio::io::AsyncWriteExt;
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(Error::new(ErrorKind::BrokenPipe, "broken"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn blocking_sink_write() {
+        let mut sink = BlockingSink::new(Vec::new());
+        sink.write_all(b"Some string ").await.unwrap();
+        sink.write_all(b"data").await.unwrap();
+        sink.flush().await.unwrap();
+        assert_eq!(sink.into_inner().await.unwrap(), b"Some string data");
+
+        //the write error is reported by the next operation
+        let mut sink = BlockingSink::new(FailingWriter);
+        sink.write_all(b"Some string data").await.unwrap();
+        let error = sink.flush().await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+    }
+}
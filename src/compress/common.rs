@@ -1,17 +1,25 @@
+#[cfg(feature = "digest")]
+use crate::digest::DigestHasher;
 use crate::{
     archive_common::{
-        ArchiveDescriptor, ArchiveFileEntry, CentralDirectoryEnd, ExtraField,
-        ExtraFieldExtendedTimestamp, ExtraFieldZIP64ExtendedInformation,
+        ArchiveDescriptor, ArchiveFileEntry, CentralDirectoryEnd, ExtraField, ExtraFieldAlignment,
+        ExtraFieldExtendedTimestamp, ExtraFieldInfoZipUnixNew, ExtraFieldInfoZipUnixUidGid,
+        ExtraFieldZIP64ExtendedInformation,
     },
-    compression::CompressionMethod,
+    compression::{CompressionMethod, Level},
     constants::{
-        CENTRAL_DIRECTORY_ENTRY_SIGNATURE, DATA_DESCRIPTOR_SIGNATURE, DIR_DEFAULT,
-        EXTENDED_LOCAL_HEADER_FLAG, FILE_DEFAULT, FILE_HEADER_BASE_SIZE,
-        LOCAL_FILE_HEADER_SIGNATURE, MS_DIR, S_IFDIR, S_IFREG, UTF8_HEADER_FLAG, VERSION_MADE_BY,
-        ZIP64_DESCRIPTOR_SIZE,
+        ARCHIVE_DIGEST_PREFIX, ARCHIVE_DIGEST_SUFFIX_SIZE, CENTRAL_DIRECTORY_ENTRY_SIGNATURE,
+        DATA_DESCRIPTOR_SIGNATURE, DIR_DEFAULT, EXTENDED_LOCAL_HEADER_FLAG, FILE_DEFAULT,
+        FILE_HEADER_BASE_SIZE, LOCAL_FILE_HEADER_SIGNATURE, MS_DIR, S_IFDIR, S_IFREG,
+        UTF8_HEADER_FLAG, VERSION_USES_ZIP64_FORMAT_EXTENSIONS, ZIP64_DESCRIPTOR_SIZE,
     },
+    crc32::DynCrc32,
+    error::ArchiveError,
+    types::UidGidFormat,
 };
 
+use std::time::Instant;
+
 /// Fast routine for detection of plain text
 ///  (ASCII or an ASCII-compatible extension such as ISO-8859, UTF-8, etc.)
 /// Author: Cosmin Truta.
@@ -29,16 +37,173 @@ pub fn is_text_buf(buffer: &[u8]) -> bool {
     result
 }
 
+/// The payload of an append with a time budget.
+///
+/// Once the deadline is passed, a read fails and the reader is marked exceeded. The bytes read before are kept,
+/// so the entry can be appended again from them and the rest of the payload. They are kept up to
+/// [`BUDGET_REPLAY_MAX_SIZE`], past it the read fails. Without a deadline, it only forwards the reads.
+pub struct BudgetReader<R> {
+    pub(crate) inner: R,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) consumed: Vec<u8>,
+    pub(crate) exceeded: bool,
+}
+
+impl<R> BudgetReader<R> {
+    pub fn new(inner: R, deadline: Option<Instant>) -> BudgetReader<R> {
+        BudgetReader {
+            inner,
+            deadline,
+            consumed: Vec::new(),
+            exceeded: false,
+        }
+    }
+
+    /// The bytes read so far and the rest of the payload.
+    pub fn into_parts(self) -> (Vec<u8>, R) {
+        (self.consumed, self.inner)
+    }
+
+    /// Checks the deadline before a read.
+    pub(crate) fn check_deadline(&mut self) -> std::io::Result<()> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.exceeded = true;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "the compression time budget is exceeded",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Tells if the error is the one raised by the deadline, so the entry can be appended again.
+    pub fn timed_out(&self, error: &ArchiveError) -> bool {
+        self.exceeded
+            && matches!(error, ArchiveError::IoError(error) if error.kind() == std::io::ErrorKind::TimedOut)
+    }
+
+    /// Keeps the bytes read, if a deadline is set.
+    pub(crate) fn record(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if self.deadline.is_some() {
+            if (self.consumed.len() + bytes.len()) as u64 > BUDGET_REPLAY_MAX_SIZE {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::OutOfMemory,
+                    "the payload read within the time budget exceeds the replay buffer",
+                ));
+            }
+            self.consumed.extend_from_slice(bytes);
+        }
+        Ok(())
+    }
+}
+
+/// The error of a time budget rollback whose stored entry is shorter than the compressed bytes already written:
+/// the sink can't be truncated, the entry is left out and the next one is written after these bytes.
+pub fn rollback_overrun() -> std::io::Error {
+    std::io::Error::other(
+        "the stored entry is shorter than the compressed bytes written before the time budget was exceeded",
+    )
+}
+
+/// Hashes the entry's uncompressed payload while it is compressed.
+///
+/// Always computes the CRC-32 and, if requested in the [`FileOptions`], an additional digest. It also checks
+/// the payload doesn't exceed the maximum entry size and tells its apparent file type.
+pub struct EntryHasher {
+    crc32: Box<dyn DynCrc32 + Send>,
+    known_crc32: Option<u32>,
+    verify_known_crc32: bool,
+    max_entry_size: Option<u64>,
+    text_file: Option<bool>,
+    #[cfg(feature = "digest")]
+    digest: Option<DigestHasher>,
+}
+
+impl EntryHasher {
+    pub fn new(options: &FileOptions) -> Self {
+        Self {
+            crc32: (options.crc32_hasher)(),
+            known_crc32: options.known_crc32,
+            verify_known_crc32: options.verify_known_crc32,
+            max_entry_size: options.max_entry_size,
+            text_file: match options.text_file {
+                Some(text_file) => Some(text_file),
+                None if !options.detect_file_type => Some(false),
+                None => None,
+            },
+            #[cfg(feature = "digest")]
+            digest: options.digest.map(DigestHasher::new),
+        }
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        //no need to compute what the caller already provided, unless it is verified
+        if self.known_crc32.is_none() || self.verify_known_crc32 {
+            self.crc32.update(buf);
+        }
+        #[cfg(feature = "digest")]
+        if let Some(digest) = &mut self.digest {
+            digest.update(buf);
+        }
+    }
+
+    /// Fails if the payload read so far exceeds the maximum entry size.
+    pub fn check_entry_size(&self, total_read: u64) -> Result<(), ArchiveError> {
+        match self.max_entry_size {
+            Some(max) if total_read > max => Err(ArchiveError::EntrySizeLimitExceeded(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tells if the payload, starting with `sample`, is a plain text file, unless its type is already known.
+    pub fn is_text(&self, sample: &[u8]) -> bool {
+        match self.text_file {
+            Some(text_file) => text_file,
+            None => is_text_buf(sample),
+        }
+    }
+
+    /// Returns the CRC-32 and the additional digest, if any. Fails if the provided CRC-32 is verified and
+    /// differs from the computed one.
+    pub fn finalize(self) -> Result<(u32, Option<Vec<u8>>), ArchiveError> {
+        #[cfg(feature = "digest")]
+        let digest = self.digest.map(DigestHasher::finalize);
+        #[cfg(not(feature = "digest"))]
+        let digest = None;
+
+        let crc32 = match self.known_crc32 {
+            Some(expected) if self.verify_known_crc32 => {
+                let actual = self.crc32.finalize();
+                if actual != expected {
+                    return Err(ArchiveError::Crc32Mismatch { expected, actual });
+                }
+                expected
+            }
+            Some(crc32) => crc32,
+            None => self.crc32.finalize(),
+        };
+
+        Ok((crc32, digest))
+    }
+}
+
+/// Size of the buffer the payload is read with, the apparent file type is detected on the first one.
+pub const BUFFER_SIZE: usize = 4096;
+
 macro_rules! compress_common {
     ( $encoder:expr, $hasher:expr, $reader:ident $($_await:tt)*) => {{
-        let mut buf = vec![0; 4096];
+        let mut buf = vec![0; $crate::compress::common::BUFFER_SIZE];
         let mut total_read: u64 = 0;
 
         let mut read = $reader.read(&mut buf)$($_await)*?;
-        let is_text = is_text_buf(&buf[..read]);
+        let is_text = $hasher.is_text(&buf[..read]);
 
         while read != 0 {
             total_read += read as u64;
+            $hasher.check_entry_size(total_read)?;
             $hasher.update(&buf[..read]);
             $encoder.write_all(&buf[..read])$($_await)*?;
             read = $reader.read(&mut buf)$($_await)*?;
@@ -86,7 +251,8 @@ pub(crate) use compress_common_std;
 pub(crate) use write_async;
 pub(crate) use write_std;
 
-use super::FileOptions;
+use super::{AppendOutcome, FileOptions};
+use std::collections::HashMap;
 
 #[derive(Debug, Default)]
 pub struct SubZipArchiveData {
@@ -94,6 +260,15 @@ pub struct SubZipArchiveData {
     central_directory_end: CentralDirectoryEnd,
     pub archive_size: u64,
     pub base_flags: u16,
+    pub force_zip64: bool,
+    pub archive_digest: bool,
+    pub sort_central_directory: bool,
+    /// The level of the entries whose options inherit it
+    pub default_compression_level: Level,
+    /// The first entry's name per content (CRC-32, uncompressed size and name stem), if duplicates are reported
+    pub contents: Option<HashMap<(u32, u64, String), String>>,
+    /// The reported duplicates, the entry's name and the name of the first entry with the same content
+    pub duplicates: Vec<(String, String)>,
     is_big_archive: bool,
 }
 
@@ -102,16 +277,203 @@ impl SubZipArchiveData {
         self.central_directory_end.set_archive_comment(comment)
     }
 
+    pub fn try_set_archive_comment(&mut self, comment: &str) -> Result<(), ArchiveError> {
+        self.central_directory_end.try_set_archive_comment(comment)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn set_metadata_json(&mut self, metadata: &serde_json::Value) -> Result<(), ArchiveError> {
+        self.central_directory_end.set_metadata_json(metadata)
+    }
+
+    /// Tells if a stored entry of known length is read in memory, to have its sizes in the local header of a
+    /// streamable archive.
+    pub fn inlines_store_size(&self, options: &FileOptions, declared_len: u64) -> bool {
+        options.inline_store_size
+            && matches!(options.compression_method, CompressionMethod::Store())
+            && is_streaming(self.base_flags)
+            && declared_len <= IN_MEMORY_MAX_SIZE
+    }
+
+    /// Make room at the end of the archive comment for the archive digest suffix.
+    pub fn reserve_archive_digest(&mut self) -> Result<(), ArchiveError> {
+        let comment = self
+            .central_directory_end
+            .archive_comment
+            .get_or_insert_with(Vec::new);
+        let len = comment.len() + ARCHIVE_DIGEST_SUFFIX_SIZE;
+        if len > u16::MAX as usize {
+            return Err(ArchiveError::CommentTooLong(len));
+        }
+        comment.resize(len, b'0');
+        Ok(())
+    }
+
     pub fn add_archive_file_entry(&mut self, archive_file_entry: ArchiveFileEntry) {
+        if let Some(contents) = &mut self.contents {
+            report_duplicate(contents, &mut self.duplicates, &archive_file_entry);
+        }
         self.is_big_archive |= archive_file_entry.is_zip64();
         self.files_info.push(archive_file_entry)
     }
 
+    /// Remove the last appended entry, it no longer counts as a duplicate's first content.
+    pub fn pop_archive_file_entry(&mut self) -> Option<ArchiveFileEntry> {
+        let archive_file_entry = self.files_info.pop()?;
+
+        if let Some(contents) = &mut self.contents {
+            let file_name = String::from_utf8_lossy(&archive_file_entry.file_name_as_bytes);
+            contents.retain(|_, first| *first != file_name);
+            self.duplicates
+                .retain(|(duplicate, first)| *duplicate != file_name && *first != file_name);
+        }
+        self.is_big_archive = self.files_info.iter().any(ArchiveFileEntry::is_zip64);
+
+        Some(archive_file_entry)
+    }
+
+    /// The entry's compression level, the archive's default one if the options inherit it.
+    pub fn compression_level(&self, options: &FileOptions) -> Level {
+        match options.compression_level {
+            Level::Inherit => self.default_compression_level,
+            level => level,
+        }
+    }
+
+    /// The entry's compression method, _store_ if its level is `Level::None`.
+    pub fn compressor(&self, file_name: &str, options: &FileOptions) -> CompressionMethod {
+        if self.compression_level(options) == Level::None {
+            CompressionMethod::Store()
+        } else {
+            options.compression_method.resolve(file_name)
+        }
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.files_info.len()
+    }
+
+    /// Set the comment of the entry at the index, in the append order.
+    pub fn set_entry_comment(&mut self, index: usize, comment: &str) -> Result<(), ArchiveError> {
+        if comment.len() > u16::MAX as usize {
+            return Err(ArchiveError::CommentTooLong(comment.len()));
+        }
+        let entry = self
+            .files_info
+            .get_mut(index)
+            .ok_or(ArchiveError::EntryNotFound(index))?;
+
+        if !comment.is_ascii() {
+            entry.general_purpose_flags |= UTF8_HEADER_FLAG; //set utf8 flag
+        }
+        entry.file_comment = Some(comment.as_bytes().to_vec());
+        Ok(())
+    }
+
+    /// Tells if the zip64 format extensions are used, once the end of central directory is built.
+    pub fn used_zip64(&self) -> bool {
+        self.force_zip64
+            || self.is_big_archive
+            || self.central_directory_end.needs_zip64_format_extensions()
+    }
+
+    /// Sort the entries by name, they keep their local header offset.
+    pub fn sort_by_name(&mut self) {
+        self.files_info
+            .sort_by(|a, b| a.file_name_as_bytes.cmp(&b.file_name_as_bytes));
+    }
+
     pub fn iter(&mut self) -> std::slice::IterMut<'_, ArchiveFileEntry> {
         self.files_info.iter_mut()
     }
+
+    /// One line per entry appended so far: the name, the uncompressed size and the CRC-32, separated by tabs.
+    pub fn build_manifest(&self) -> String {
+        self.files_info
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}\t{}\t{:08x}\n",
+                    String::from_utf8_lossy(&entry.file_name_as_bytes),
+                    entry.uncompressed_size,
+                    entry.crc32
+                )
+            })
+            .collect()
+    }
 }
 
+/// Reports the entry if a previous one has the same CRC-32, uncompressed size and name stem, its compressed bytes
+/// could have been reused. With the `log` feature, it is logged at info level too.
+fn report_duplicate(
+    contents: &mut HashMap<(u32, u64, String), String>,
+    duplicates: &mut Vec<(String, String)>,
+    archive_file_entry: &ArchiveFileEntry,
+) {
+    //empty files and directories are alike, but have nothing to share
+    if archive_file_entry.uncompressed_size == 0 {
+        return;
+    }
+
+    let file_name = String::from_utf8_lossy(&archive_file_entry.file_name_as_bytes);
+    let base_name = file_name.rsplit('/').next().unwrap_or(&file_name);
+    let stem = match base_name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => base_name,
+    };
+
+    let key = (
+        archive_file_entry.crc32,
+        archive_file_entry.uncompressed_size,
+        stem.to_owned(),
+    );
+    match contents.get(&key) {
+        Some(first) => {
+            #[cfg(feature = "log")]
+            log::info!(
+                "The entry {:?} has the same content as {:?}, its compressed bytes could be reused with append_raw",
+                file_name,
+                first
+            );
+            duplicates.push((file_name.into_owned(), first.clone()));
+        }
+        None => {
+            contents.insert(key, file_name.into_owned());
+        }
+    }
+}
+
+/// Build the entry and its complete local header, for a payload already compressed: the CRC-32 and the sizes
+/// are known, no data descriptor follows the payload.
+pub fn build_complete_file_header(
+    file_name: &str,
+    options: &FileOptions,
+    compressor: CompressionMethod,
+    offset: u64,
+    data: &SubZipArchiveData,
+    outcome: &AppendOutcome,
+    is_text: bool,
+) -> Result<(ArchiveDescriptor, ArchiveFileEntry), ArchiveError> {
+    let (_, mut archive_file_entry) =
+        build_file_header(file_name, options, compressor, offset, data, false)?;
+
+    archive_file_entry.crc32 = outcome.crc32;
+    archive_file_entry.compressed_size = outcome.compressed_size;
+    archive_file_entry.uncompressed_size = outcome.uncompressed_size;
+    archive_file_entry.apparently_text_file(is_text);
+    archive_file_entry.general_purpose_flags &= !EXTENDED_LOCAL_HEADER_FLAG;
+
+    //a large offset alone doesn't need the zip64 extra field in the local header, only in the central one
+    if archive_file_entry.needs_zip64_local() {
+        archive_file_entry.need_to_add_zip64_extra_field();
+    }
+    let file_header = build_local_file_header(&mut archive_file_entry);
+    archive_file_entry.need_to_add_zip64_extra_field();
+
+    Ok((file_header, archive_file_entry))
+}
+
+#[allow(dead_code)]
 pub trait ZipArchiveCommon {
     fn get_archive_size(&self) -> u64;
     fn get_data(&self) -> &SubZipArchiveData;
@@ -129,20 +491,23 @@ pub fn build_file_header(
     offset: u64,
     data: &SubZipArchiveData,
     is_dir: bool,
-) -> (ArchiveDescriptor, ArchiveFileEntry) {
+) -> Result<(ArchiveDescriptor, ArchiveFileEntry), ArchiveError> {
+    let file_name = options.path_separator.apply(file_name);
     let file_nameas_bytes = file_name.as_bytes();
     let file_name_as_bytes_own = file_nameas_bytes.to_owned();
-    let file_name_len = file_name_as_bytes_own.len() as u16;
+    //the length is on 16 bits, a longer name would corrupt the header
+    let file_name_len = u16::try_from(file_name_as_bytes_own.len())
+        .map_err(|_| ArchiveError::EntryNameTooLong(file_name_as_bytes_own.len()))?;
 
     let (date, time) = options.last_modified_time.ms_dos();
     let mut general_purpose_flags: u16 = data.base_flags;
-    if file_name_as_bytes_own.len() > file_name.len() {
+    if !file_name.is_ascii() {
         general_purpose_flags |= UTF8_HEADER_FLAG; //set utf8 flag
     }
 
     let file_comment = if let Some(comment) = options.comment {
-        let file_comment_as_bytes_own = comment.as_bytes().to_owned();
-        if file_comment_as_bytes_own.len() > comment.len() {
+        let file_comment_as_bytes_own = comment.to_owned();
+        if !comment.is_ascii() && std::str::from_utf8(comment).is_ok() {
             general_purpose_flags |= UTF8_HEADER_FLAG; //set utf8 flag
         }
         Some(file_comment_as_bytes_own)
@@ -151,30 +516,62 @@ pub fn build_file_header(
     };
 
     general_purpose_flags = compressor
-        .update_general_purpose_bit_flag(general_purpose_flags, options.compression_level);
+        .update_general_purpose_bit_flag(general_purpose_flags, data.compression_level(options));
 
     let mut minimum_version_needed_to_extract = compressor.zip_version_needed();
-    let version_made_by = options.system.update_version_needed(VERSION_MADE_BY);
+    let version_made_by = options
+        .system
+        .update_version_needed(options.made_by_version as u16);
 
     let mut extra_fields: Vec<Box<dyn ExtraField>> = Vec::new();
 
-    if options.large_file && !is_streaming(data.base_flags) {
+    //the local header of a large or forced zip64 entry has the zip64 extra field, even when streamed: its sizes
+    //are placeholders, a streamed entry's sizes follow in a zip64 data descriptor
+    if options.large_file || data.force_zip64 {
+        minimum_version_needed_to_extract =
+            minimum_version_needed_to_extract.max(VERSION_USES_ZIP64_FORMAT_EXTENSIONS);
+
         let zip64_extrafield: ExtraFieldZIP64ExtendedInformation =
             ExtraFieldZIP64ExtendedInformation::default();
 
         extra_fields.push(Box::new(zip64_extrafield));
     }
 
-    if options.last_modified_time.extended_timestamp()
-        || options.last_creation_time.is_some()
-        || options.last_access_time.is_some()
+    if options.extended_timestamp
+        && (options.last_modified_time.extended_timestamp()
+            || options.last_creation_time.is_some()
+            || options.last_access_time.is_some())
     {
         let ts = ExtraFieldExtendedTimestamp::new(
             options.last_modified_time.timestamp(),
             options.last_access_time,
             options.last_creation_time,
         );
-        extra_fields.push(Box::new(ts));
+        if options.minimal_local_extra {
+            extra_fields.push(Box::new(ts.central_only()));
+        } else {
+            extra_fields.push(Box::new(ts));
+        }
+    }
+
+    if let Some((uid, gid)) = options.unix_owner {
+        match (
+            options.unix_uid_gid_format,
+            u16::try_from(uid),
+            u16::try_from(gid),
+        ) {
+            (UidGidFormat::Unix16, Ok(uid), Ok(gid)) => {
+                extra_fields.push(Box::new(ExtraFieldInfoZipUnixNew::new(uid, gid)))
+            }
+            _ => extra_fields.push(Box::new(ExtraFieldInfoZipUnixUidGid::new(uid, gid))),
+        }
+    }
+
+    //the padding depends on the fields before it, it is the last one
+    if let Some(alignment) = options.alignment {
+        if alignment > 1 && compressor == CompressionMethod::Store() && !is_dir {
+            extra_fields.push(Box::new(ExtraFieldAlignment::new(alignment)));
+        }
     }
 
     let (unix_ftype, default_permission, ms_dos_attr) = if is_dir {
@@ -185,13 +582,20 @@ pub fn build_file_header(
         (S_IFREG, FILE_DEFAULT, 0)
     };
 
-    let unix_permissions = if let Some(permissions) = options.unix_permissions {
-        permissions | unix_ftype
+    //a directory's own permissions take precedence over the ones shared with the files
+    let permissions = if is_dir {
+        options.dir_permissions.or(options.unix_permissions)
     } else {
-        unix_ftype | default_permission
+        options.unix_permissions
     };
+    let unix_permissions = unix_ftype | permissions.unwrap_or(default_permission);
 
-    let external_file_attributes: u32 = (unix_permissions << 16) + ms_dos_attr;
+    //the host OS set in the version made by tells how to interpret the external attributes
+    let external_file_attributes: u32 = if options.system.has_unix_attributes() {
+        (unix_permissions << 16) + ms_dos_attr
+    } else {
+        ms_dos_attr
+    };
 
     let mut archive_file_entry = ArchiveFileEntry {
         version_made_by,
@@ -212,17 +616,41 @@ pub fn build_file_header(
         external_file_attributes,
         file_disk_number: 0,
         extra_fields,
+        raw_extra_field: Vec::new(),
         file_comment,
+        force_zip64: data.force_zip64,
     };
 
+    let local_header = build_local_file_header(&mut archive_file_entry);
+
+    Ok((local_header, archive_file_entry))
+}
+
+/// Build the local file header from the entry's current values, updating its extra field length.
+pub fn build_local_file_header(archive_file_entry: &mut ArchiveFileEntry) -> ArchiveDescriptor {
+    //with a data descriptor, the sizes are zero in the local header
+    let (compressed_size, uncompressed_size) =
+        if is_streaming(archive_file_entry.general_purpose_flags) {
+            (
+                archive_file_entry.compressed_size as u32,
+                archive_file_entry.uncompressed_size as u32,
+            )
+        } else {
+            (
+                archive_file_entry.zip64_compressed_size(),
+                archive_file_entry.zip64_uncompressed_size(),
+            )
+        };
+
     let mut extended_data_buffer = ArchiveDescriptor::new(500);
     for extra_field in &archive_file_entry.extra_fields {
-        extra_field.local_header_write_data(&mut extended_data_buffer, &archive_file_entry)
+        extra_field.local_header_write_data(&mut extended_data_buffer, archive_file_entry)
     }
 
     archive_file_entry.extra_field_length = extended_data_buffer.len() as u16;
 
-    let mut local_header = ArchiveDescriptor::new(FILE_HEADER_BASE_SIZE + file_name_len as u64);
+    let mut local_header =
+        ArchiveDescriptor::new(FILE_HEADER_BASE_SIZE + archive_file_entry.file_name_len as u64);
     local_header.write_u32(LOCAL_FILE_HEADER_SIGNATURE);
     local_header.write_u16(archive_file_entry.minimum_version_needed_to_extract);
     local_header.write_u16(archive_file_entry.general_purpose_flags);
@@ -230,14 +658,14 @@ pub fn build_file_header(
     local_header.write_u16(archive_file_entry.last_mod_file_time);
     local_header.write_u16(archive_file_entry.last_mod_file_date);
     local_header.write_u32(archive_file_entry.crc32); // CRC-32
-    local_header.write_u32(archive_file_entry.compressed_size as u32); // compressed size
-    local_header.write_u32(archive_file_entry.uncompressed_size as u32); // uncompressed size
+    local_header.write_u32(compressed_size); // compressed size
+    local_header.write_u32(uncompressed_size); // uncompressed size
     local_header.write_u16(archive_file_entry.file_name_len); // file name length
     local_header.write_u16(archive_file_entry.extra_field_length); // extra field length
     local_header.write_bytes(&archive_file_entry.file_name_as_bytes); // file name
     local_header.write_bytes(extended_data_buffer.bytes()); // extra fields
 
-    (local_header, archive_file_entry)
+    local_header
 }
 
 pub fn build_central_directory_file_header(
@@ -246,8 +674,18 @@ pub fn build_central_directory_file_header(
 ) {
     let mut extra_field_buffer = ArchiveDescriptor::new(file_info.extra_field_length as u64);
 
-    for extra_field in &file_info.extra_fields {
-        extra_field.central_header_extra_write_data(&mut extra_field_buffer, file_info)
+    //extra fields read from another archive are kept byte for byte, but the zip64 one: the entry's offset changes
+    if file_info.raw_extra_field.is_empty() {
+        for extra_field in &file_info.extra_fields {
+            extra_field.central_header_extra_write_data(&mut extra_field_buffer, file_info)
+        }
+    } else {
+        ExtraFieldZIP64ExtendedInformation::default()
+            .central_header_extra_write_data(&mut extra_field_buffer, file_info);
+        extra_field_buffer.write_bytes(&without_extra_field(
+            &file_info.raw_extra_field,
+            ExtraFieldZIP64ExtendedInformation::HEADER_ID,
+        ));
     }
 
     file_info.extra_field_length = extra_field_buffer.len() as u16;
@@ -265,7 +703,7 @@ pub fn build_central_directory_file_header(
     central_directory_header.write_u16(file_info.file_name_len); // Filename length.
     central_directory_header.write_u16(file_info.extra_field_length); // Extra field length.
     central_directory_header.write_u16(file_info.file_comment_length()); // File comment length.
-    central_directory_header.write_u16(file_info.file_disk_number as u16); // File's Disk number.
+    central_directory_header.write_u16(file_info.zip64_file_disk_number()); // File's Disk number.
     central_directory_header.write_u16(file_info.internal_file_attributes); // Internal file attributes.
     central_directory_header.write_u32(file_info.external_file_attributes); // External file attributes (regular file / rw-r--r--).
     central_directory_header.write_u32(file_info.zip64_offset()); // Offset from start of file to local file header.
@@ -278,12 +716,39 @@ pub fn build_central_directory_file_header(
     }
 }
 
-pub fn build_data_descriptor(archive_file_entry: &ArchiveFileEntry) -> ArchiveDescriptor {
+/// The extra fields without the ones of the header id. The bytes past a truncated extra field are kept as is.
+fn without_extra_field(raw_extra_field: &[u8], header_id: u16) -> Vec<u8> {
+    let mut kept = Vec::with_capacity(raw_extra_field.len());
+    let mut rest = raw_extra_field;
+
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let end = 4 + u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        if end > rest.len() {
+            break;
+        }
+        if id != header_id {
+            kept.extend_from_slice(&rest[..end]);
+        }
+        rest = &rest[end..];
+    }
+
+    kept.extend_from_slice(rest);
+    kept
+}
+
+pub fn build_data_descriptor(
+    archive_file_entry: &ArchiveFileEntry,
+    options: &FileOptions,
+) -> ArchiveDescriptor {
     let mut file_descriptor = ArchiveDescriptor::new(ZIP64_DESCRIPTOR_SIZE);
-    file_descriptor.write_u32(DATA_DESCRIPTOR_SIGNATURE); //This is optional
+    if options.data_descriptor_signature {
+        file_descriptor.write_u32(DATA_DESCRIPTOR_SIGNATURE); //This is optional
+    }
     file_descriptor.write_u32(archive_file_entry.crc32);
 
-    if archive_file_entry.is_zip64() {
+    //with the zip64 extra field in the local header, the sizes are on 8 bytes
+    if archive_file_entry.needs_zip64_local() || archive_file_entry.has_zip64_extra_field() {
         file_descriptor.write_u64(archive_file_entry.compressed_size);
         file_descriptor.write_u64(archive_file_entry.uncompressed_size);
     } else {
@@ -308,7 +773,7 @@ pub fn build_central_directory_end(
     data: &mut SubZipArchiveData,
     central_directory_offset: u64,
     central_directory_size: u64,
-) -> ArchiveDescriptor {
+) -> Result<ArchiveDescriptor, ArchiveError> {
     data.central_directory_end.number_of_this_disk = 0;
     data.central_directory_end
         .number_of_the_disk_with_central_directory = 0;
@@ -319,10 +784,11 @@ pub fn build_central_directory_end(
     data.central_directory_end.central_directory_size = central_directory_size;
     data.central_directory_end
         .offset_of_start_of_central_directory = central_directory_offset;
+    data.central_directory_end.z64ecdl_total_number_of_disks = 1;
 
     let mut end_of_central_directory = ArchiveDescriptor::new(500); //TODO calculate capacity size
 
-    if data.central_directory_end.needs_zip64_format_extensions() {
+    if data.force_zip64 || data.central_directory_end.needs_zip64_format_extensions() {
         //[zip64 end of central directory record]
 
         data.central_directory_end
@@ -331,7 +797,7 @@ pub fn build_central_directory_end(
         //[zip64 end of central directory locator]
         //------------------------------------------------
         data.central_directory_end
-            .create_end_of_central_directory_locator(&mut end_of_central_directory);
+            .create_end_of_central_directory_locator(&mut end_of_central_directory)?;
     }
 
     //4.4.1.5  The end of central directory record and the Zip64 end
@@ -340,7 +806,133 @@ pub fn build_central_directory_end(
     data.central_directory_end
         .create_end_of_central_directory(&mut end_of_central_directory);
 
-    end_of_central_directory
+    Ok(end_of_central_directory)
+}
+
+/// The archive digest suffix, ending the archive comment.
+pub fn build_archive_digest_suffix(crc32: u32) -> String {
+    format!("{}{:08x}", ARCHIVE_DIGEST_PREFIX, crc32)
+}
+
+/// Largest payload read in memory to be appended in a single pass.
+pub const IN_MEMORY_MAX_SIZE: u64 = 1024 * 1024;
+
+/// Largest payload kept in memory by an append with a time budget, to be appended again once the budget is
+/// exceeded.
+pub const BUDGET_REPLAY_MAX_SIZE: u64 = 64 * IN_MEMORY_MAX_SIZE;
+
+/// Tells if an entry of the given uncompressed size might need the zip64 format extensions, the compressed
+/// size being possibly a bit larger for incompressible data.
+pub fn might_need_zip64(size: u64) -> bool {
+    size.saturating_add(size / 100) >= u32::MAX as u64
+}
+
+/// Checks the bytes read from a payload against its declared length.
+pub struct DeclaredLength {
+    expected: u64,
+    read: u64,
+    crc32: crc32fast::Hasher,
+    /// Is a byte available past the declared length
+    overrun: bool,
+    /// Did the payload end before the declared length
+    truncated: bool,
+}
+
+impl DeclaredLength {
+    pub fn new(expected: u64) -> Self {
+        Self {
+            expected,
+            read: 0,
+            crc32: crc32fast::Hasher::new(),
+            overrun: false,
+            truncated: false,
+        }
+    }
+
+    /// The number of bytes left to read before the declared length.
+    pub fn remaining(&self) -> u64 {
+        self.expected - self.read
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        self.read += buf.len() as u64;
+        self.crc32.update(buf);
+    }
+
+    /// Record the end of the payload, before (`truncated`) or after (`overrun`) the declared length.
+    pub fn end(&mut self, overrun: bool) -> std::io::Error {
+        if overrun {
+            self.overrun = true;
+            std::io::Error::other("the payload is longer than its declared length")
+        } else {
+            self.truncated = true;
+            std::io::ErrorKind::UnexpectedEof.into()
+        }
+    }
+
+    pub fn is_overrun(&self) -> bool {
+        self.overrun
+    }
+
+    /// The length mismatch error, if any. The `extra` bytes are the ones left after the first byte past the
+    /// declared length.
+    pub fn mismatch(&self, extra: u64) -> Option<ArchiveError> {
+        let actual = if self.overrun {
+            self.expected + 1 + extra
+        } else if self.truncated {
+            self.read
+        } else {
+            return None;
+        };
+
+        Some(ArchiveError::LengthMismatch {
+            expected: self.expected,
+            actual,
+            crc32: self.crc32.clone().finalize(),
+        })
+    }
+}
+
+/// The zstd level of the compression level, a precise one must be in zstd's range.
+#[cfg(all(feature = "zstd", any(feature = "std", feature = "tokio")))]
+pub fn zstd_level(compression_level: Level) -> Result<i32, ArchiveError> {
+    let level = match compression_level {
+        Level::Fastest => 1,
+        Level::Best => 22,
+        Level::Default | Level::Inherit => zstd::DEFAULT_COMPRESSION_LEVEL,
+        Level::None => 0,
+        Level::Precise(val) => val,
+    };
+
+    let range = zstd::compression_level_range();
+    if !range.contains(&level) {
+        return Err(ArchiveError::UnsuportedCompressionLevel(
+            CompressionMethod::Zstd(),
+            format!("the level {} is outside of the range {:?}", level, range),
+        ));
+    }
+    Ok(level)
+}
+
+/// The number of leading payload bytes to peek at before the header is written.
+pub fn head_size(compressor: CompressionMethod, options: &FileOptions) -> usize {
+    let mut size = 0;
+    if options.store_empty {
+        size = BUFFER_SIZE;
+    }
+    if options.adaptive && compressor != CompressionMethod::Store() {
+        size = size.max(options.adaptive_sample_size);
+    }
+    size
+}
+
+/// Tells if the payload sample is incompressible enough for the entry to be stored.
+pub fn is_incompressible(
+    options: &FileOptions,
+    sample_size: usize,
+    compressed_size: usize,
+) -> bool {
+    sample_size > 0 && compressed_size as f64 / sample_size as f64 > options.adaptive_threshold
 }
 
 pub fn is_streaming(flags: u16) -> bool {
@@ -349,7 +941,31 @@ pub fn is_streaming(flags: u16) -> bool {
 
 #[cfg(test)]
 mod test {
-    use super::is_text_buf;
+    use super::{
+        build_central_directory_end, build_central_directory_file_header,
+        build_complete_file_header, build_file_header, is_text_buf, might_need_zip64, BudgetReader,
+        SubZipArchiveData, BUDGET_REPLAY_MAX_SIZE,
+    };
+    use crate::archive_common::{ArchiveDescriptor, ArchiveFileEntry};
+    use crate::compress::{AppendOutcome, FileOptions};
+    use crate::compression::CompressionMethod;
+    use crate::constants::FILE_HEADER_BASE_SIZE;
+    use crate::error::ArchiveError;
+
+    #[test]
+    fn budget_reader_replay() {
+        let mut reader = BudgetReader::new(&b""[..], Some(std::time::Instant::now()));
+        assert!(reader.record(b"Some string data").is_ok());
+        assert!(reader
+            .record(&vec![0; BUDGET_REPLAY_MAX_SIZE as usize])
+            .is_err());
+
+        //only the deadline's error rolls the entry back
+        let error = ArchiveError::IoError(reader.check_deadline().unwrap_err());
+        assert!(reader.timed_out(&error));
+        let error = ArchiveError::IoError(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(!reader.timed_out(&error));
+    }
 
     #[test]
     fn all_text() {
@@ -367,4 +983,194 @@ mod test {
 
         assert!(!res)
     }
+
+    #[test]
+    fn central_directory_end_offset_overflow() {
+        let mut data = SubZipArchiveData::default();
+
+        let res = build_central_directory_end(&mut data, u64::MAX - 10, 100);
+
+        assert!(matches!(res, Err(ArchiveError::BadArchiveStructure(_))));
+    }
+
+    #[test]
+    fn central_directory_end_large_offset() {
+        let mut data = SubZipArchiveData::default();
+
+        let res = build_central_directory_end(&mut data, u64::MAX - 100, 100);
+
+        assert!(res.is_ok());
+        assert_eq!(
+            data.central_directory_end
+                .z64ecdl_relative_offset_of_the_zip64_end_of_central_directory_record,
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn might_need_zip64_margin() {
+        assert!(!might_need_zip64(1024));
+        assert!(!might_need_zip64(4_000_000_000));
+        assert!(might_need_zip64(4_260_000_000));
+        assert!(might_need_zip64(u64::MAX));
+    }
+
+    #[test]
+    fn central_directory_header_raw_extra_field() {
+        //a NTFS extra field, not parsed by the archiver
+        let raw_extra_field = vec![
+            0x0a, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 1, 2, 3, 4, 5,
+            6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8,
+        ];
+        let mut entry = ArchiveFileEntry {
+            file_name_as_bytes: b"file.txt".to_vec(),
+            file_name_len: 8,
+            raw_extra_field: raw_extra_field.clone(),
+            ..Default::default()
+        };
+
+        let mut header = ArchiveDescriptor::new(100);
+        build_central_directory_file_header(&mut header, &mut entry);
+
+        assert_eq!(entry.extra_field_length as usize, raw_extra_field.len());
+        assert_eq!(&header.bytes()[46 + 8..], raw_extra_field.as_slice());
+    }
+
+    #[test]
+    fn central_directory_header_raw_extra_field_zip64() {
+        //a stale zip64 extra field with a 32 bits offset, then a NTFS one
+        let mut raw_extra_field = vec![0x01, 0x00, 0x08, 0x00, 1, 2, 3, 4, 5, 6, 7, 8];
+        let ntfs = vec![
+            0x0a, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 1, 2, 3, 4, 5,
+            6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 4, 5, 6, 7, 8,
+        ];
+        raw_extra_field.extend_from_slice(&ntfs);
+
+        let offset = u32::MAX as u64 + 10;
+        let mut entry = ArchiveFileEntry {
+            file_name_as_bytes: b"file.txt".to_vec(),
+            file_name_len: 8,
+            offset,
+            raw_extra_field,
+            ..Default::default()
+        };
+
+        let mut header = ArchiveDescriptor::new(100);
+        build_central_directory_file_header(&mut header, &mut entry);
+
+        //the zip64 extra field is regenerated with the entry's offset, the other ones are kept
+        let extra_field = &header.bytes()[46 + 8..];
+        assert_eq!(entry.extra_field_length as usize, 12 + ntfs.len());
+        assert_eq!(&extra_field[..4], &[0x01, 0x00, 0x08, 0x00]);
+        assert_eq!(&extra_field[4..12], &offset.to_le_bytes());
+        assert_eq!(&extra_field[12..], ntfs.as_slice());
+        assert_eq!(&header.bytes()[42..46], &u32::MAX.to_le_bytes());
+    }
+
+    #[test]
+    fn needs_zip64_local_or_central() {
+        let mut entry = ArchiveFileEntry {
+            offset: 5 * 1024 * 1024 * 1024,
+            ..Default::default()
+        };
+        assert!(!entry.needs_zip64_local());
+        assert!(entry.needs_zip64_central());
+
+        entry.offset = 0;
+        entry.file_disk_number = u16::MAX as u32;
+        assert!(!entry.needs_zip64_local());
+        assert!(entry.needs_zip64_central());
+
+        entry.file_disk_number = 0;
+        entry.compressed_size = u32::MAX as u64;
+        assert!(entry.needs_zip64_local());
+        assert!(entry.needs_zip64_central());
+    }
+
+    #[test]
+    fn file_header_name_too_long() {
+        let file_name = "a".repeat(u16::MAX as usize + 1);
+        let result = build_file_header(
+            &file_name,
+            &FileOptions::default(),
+            CompressionMethod::Store(),
+            0,
+            &SubZipArchiveData::default(),
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(ArchiveError::EntryNameTooLong(len)) if len == u16::MAX as usize + 1
+        ));
+
+        let file_name = "a".repeat(u16::MAX as usize);
+        assert!(build_file_header(
+            &file_name,
+            &FileOptions::default(),
+            CompressionMethod::Store(),
+            0,
+            &SubZipArchiveData::default(),
+            false,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn file_header_minimal_local_extra() {
+        let options = FileOptions::default()
+            .time_stamp(Some(1_700_000_000), Some(1_700_000_100), None)
+            .minimal_local_extra(true);
+        let (local_header, mut entry) = build_file_header(
+            "file.txt",
+            &options,
+            CompressionMethod::Store(),
+            0,
+            &SubZipArchiveData::default(),
+            false,
+        )
+        .unwrap();
+
+        //the extended timestamp is in the central header only, with the modification time
+        assert_eq!(local_header.len(), FILE_HEADER_BASE_SIZE as usize + 8);
+
+        let mut header = ArchiveDescriptor::new(100);
+        build_central_directory_file_header(&mut header, &mut entry);
+        assert_eq!(entry.extra_field_length, 4 + 5);
+        assert_eq!(&header.bytes()[46 + 8..46 + 8 + 4], &[0x55, 0x54, 5, 0]);
+    }
+
+    #[test]
+    fn complete_file_header_large_offset() {
+        const OFFSET: u64 = 5 * 1024 * 1024 * 1024;
+
+        let options = FileOptions::default().extended_timestamp(false);
+        let outcome = AppendOutcome {
+            crc32: 0,
+            compressed_size: 10,
+            uncompressed_size: 10,
+            digest: None,
+            index: 0,
+        };
+        let (local_header, mut entry) = build_complete_file_header(
+            "file.txt",
+            &options,
+            CompressionMethod::Store(),
+            OFFSET,
+            &SubZipArchiveData::default(),
+            &outcome,
+            false,
+        )
+        .unwrap();
+
+        //no zip64 extra field in the local header, only the offset in the central one
+        assert_eq!(local_header.len(), FILE_HEADER_BASE_SIZE as usize + 8);
+
+        let mut header = ArchiveDescriptor::new(100);
+        build_central_directory_file_header(&mut header, &mut entry);
+        assert_eq!(entry.extra_field_length, 4 + 8);
+        assert_eq!(
+            &header.bytes()[46 + 8 + 4..],
+            OFFSET.to_le_bytes().as_slice()
+        );
+    }
 }
@@ -0,0 +1,148 @@
+//! A sink counting the bytes of an archive without storing them.
+//!
+//! Building an archive against a [`SinkCounter`] gives its exact size without producing any output, e.g. to set
+//! the `Content-Length` header before streaming the actual archive.
+//!
+//! For the _store_ method, the size of an entry only depends on its payload length: the payloads can be replaced
+//! by readers of the same length, such as [`std::io::repeat`]. For the other methods, the compressed size depends
+//! on the content: it is exact only when the same payloads are compressed with the same options, otherwise it is
+//! an approximation.
+//!
+//!```rust
+//! use archflow::{
+//!     compress::sink::SinkCounter, compress::std::archive::ZipArchive, compress::FileOptions,
+//!     compression::CompressionMethod, error::ArchiveError,
+//! };
+//! use std::io::Read;
+//!
+//! # fn main() -> Result<(), ArchiveError> {
+//! let options = FileOptions::default().compression_method(CompressionMethod::Store());
+//!
+//! let mut archive = ZipArchive::new_streamable(SinkCounter::new());
+//! archive.append("file1.txt", &options, &mut std::io::repeat(0).take(1000))?;
+//! archive.append("file2.txt", &options, &mut std::io::repeat(0).take(2000))?;
+//! let (_, counter) = archive.finalize()?;
+//!
+//! let content_length = counter.count();
+//! # assert!(content_length > 3000);
+//! # Ok(())
+//! # }
+//!```
+
+use std::io::{Seek, SeekFrom, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncSeek, AsyncWrite};
+
+/// A writer discarding the bytes, but counting them.
+///
+/// It can be seeked, to be used for a seekable archive: the count is then the size of the data as if it was
+/// written in a file.
+#[derive(Debug, Default)]
+pub struct SinkCounter {
+    position: u64,
+    len: u64,
+}
+
+impl SinkCounter {
+    pub fn new() -> SinkCounter {
+        SinkCounter::default()
+    }
+
+    /// The number of bytes written
+    pub fn count(&self) -> u64 {
+        self.len
+    }
+
+    fn advance(&mut self, written: usize) {
+        self.position += written as u64;
+        self.len = self.len.max(self.position);
+    }
+
+    fn move_to(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl Write for SinkCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.advance(buf.len());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SinkCounter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.move_to(pos)
+    }
+}
+
+impl AsyncWrite for SinkCounter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        self.get_mut().advance(buf.len());
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for SinkCounter {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        self.get_mut().move_to(position).map(|_| ())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(Ok(self.position))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SinkCounter;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn count_with_seek() -> std::io::Result<()> {
+        let mut counter = SinkCounter::new();
+        counter.write_all(&[0; 100])?;
+        counter.seek(SeekFrom::Start(10))?;
+        counter.write_all(&[0; 20])?;
+        assert_eq!(counter.count(), 100);
+
+        counter.seek(SeekFrom::End(0))?;
+        counter.write_all(&[0; 5])?;
+        assert_eq!(counter.count(), 105);
+        assert!(counter.seek(SeekFrom::Current(-200)).is_err());
+
+        Ok(())
+    }
+}
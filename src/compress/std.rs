@@ -1,3 +1,3 @@
 pub mod archive;
-mod compressor;
-mod write_wrapper;
+pub(crate) mod compressor;
+pub(crate) mod write_wrapper;
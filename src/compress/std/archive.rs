@@ -1,17 +1,28 @@
 use super::compressor::compress;
-use super::write_wrapper::{CommonWrapper, WriteSeekWrapper, WriteWrapper};
+use super::write_wrapper::{BufferedSink, CommonWrapper, WriteSeekWrapper, WriteWrapper};
 
 use crate::archive_common::{ArchiveDescriptor, ExtraField, ExtraFieldZIP64ExtendedInformation};
 use crate::compress::common::{
-    build_central_directory_end, build_central_directory_file_header, build_data_descriptor,
-    build_file_header, build_file_sizes_update, is_streaming, SubZipArchiveData, ZipArchiveCommon,
+    build_archive_digest_suffix, build_central_directory_end, build_central_directory_file_header,
+    build_complete_file_header, build_data_descriptor, build_file_header, build_file_sizes_update,
+    head_size, is_incompressible, is_streaming, might_need_zip64, rollback_overrun, BudgetReader,
+    DeclaredLength, EntryHasher, SubZipArchiveData, ZipArchiveCommon, BUFFER_SIZE,
+    IN_MEMORY_MAX_SIZE,
 };
-use crate::compress::FileOptions;
+use crate::compress::{AppendOutcome, FileOptions, FinalizeReport};
 use crate::compression::{CompressionMethod, Level};
-use crate::constants::{EXTENDED_LOCAL_HEADER_FLAG, FILE_HEADER_BASE_SIZE, FILE_HEADER_CRC_OFFSET};
+use crate::constants::{
+    ARCHIVE_DIGEST_SUFFIX_SIZE, EXTENDED_LOCAL_HEADER_FLAG, FILE_HEADER_BASE_SIZE,
+    FILE_HEADER_CRC_OFFSET,
+};
 use crate::error::ArchiveError;
-use crc32fast::Hasher;
-use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(feature = "deflate")]
+use flate2::read::MultiGzDecoder;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Instant;
 
 /// A zip archive.
 ///
@@ -57,10 +68,25 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
         }
     }
 
+    /// Create a new zip archive, like [`Self::new_streamable`], coalescing the writes to the underlying [`Write`]
+    /// in a buffer of `capacity` bytes.
+    ///
+    /// The archive writes many small buffers, e.g. the headers and descriptors of tiny entries: they are written
+    /// downstream once the buffer is full, which reduces the syscalls and the small packets of a socket. The
+    /// buffer is flushed by [`Self::finalize`].
+    pub fn new_streamable_buffered(sink: W, capacity: usize) -> Self {
+        let mut data = SubZipArchiveData::default();
+        data.base_flags = EXTENDED_LOCAL_HEADER_FLAG; //extended local header
+        Self {
+            sink: Box::new(BufferedSink::new(sink, capacity)),
+            data,
+        }
+    }
+
     /// Create a new zip archive (non streamable), using the underlying [`Write`] + [`Seek`] to
     /// write files' header and payload.
     ///
-    /// _Note:_ a non streamable archive save few bytes per files.
+    /// _Note:_ a non streamable archive save few bytes per files (the 16 bytes data descriptor).
     pub fn new<S: Write + Seek + 'a>(sink: S) -> ZipArchive<'a, W>
     where
         WriteSeekWrapper<S>: CommonWrapper<W>,
@@ -74,6 +100,14 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
         }
     }
 
+    /// The offset where the next entry's local header will be written, e.g. to build an external index of the
+    /// entries while appending them.
+    ///
+    /// It is the size of the archive so far, the entries' headers and payloads.
+    pub fn current_offset(&self) -> u64 {
+        self.data.archive_size
+    }
+
     /// Get archive current total bytes written.
     pub fn get_archive_size(&mut self) -> Result<u64, ArchiveError> {
         Ok(self.sink.get_written_bytes_count()?)
@@ -92,14 +126,82 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
         file_name: &str,
         options: &FileOptions,
         payload: &mut R,
-    ) -> Result<(), ArchiveError>
+    ) -> Result<AppendOutcome, ArchiveError>
     where
         W: Write,
         R: Read,
     {
         let file_header_offset = self.data.archive_size;
-        let mut hasher = Hasher::new();
-        let compressor = options.compression_method;
+        let deadline = options.time_budget.map(|budget| Instant::now() + budget);
+        let compressor = self.data.compressor(file_name, options);
+
+        //peek at the payload, so the compression method can be adapted before the header is written
+        let head_size = head_size(compressor, options);
+        let mut head = Vec::with_capacity(head_size);
+        (&mut *payload)
+            .take(head_size as u64)
+            .read_to_end(&mut head)?;
+        let compressor = adapt_compressor(
+            compressor,
+            options,
+            self.data.compression_level(options),
+            &head,
+        )?;
+
+        //a streamed entry can't be rolled back, its budget is only checked before the compression starts
+        let streaming = is_streaming(self.data.base_flags);
+        let compressor = match deadline {
+            Some(deadline) if streaming && Instant::now() >= deadline => CompressionMethod::Store(),
+            _ => compressor,
+        };
+
+        let mut payload = BudgetReader::new(
+            head.as_slice().chain(payload),
+            deadline.filter(|_| !streaming),
+        );
+        match self.append_payload(file_name, options, compressor, &mut payload) {
+            Err(error) if payload.timed_out(&error) => {
+                //the entry is rolled back and appended again, stored
+                let written_end = self.data.archive_size;
+                self.sink.seek(SeekFrom::Start(file_header_offset))?;
+                self.data.archive_size = file_header_offset;
+
+                let (consumed, rest) = payload.into_parts();
+                let mut replay = Cursor::new(consumed).chain(rest);
+                let outcome = self.append_payload(
+                    file_name,
+                    options,
+                    CompressionMethod::Store(),
+                    &mut replay,
+                )?;
+
+                if self.data.archive_size < written_end {
+                    //the compressed bytes past the stored entry can't be truncated
+                    self.data.pop_archive_file_entry();
+                    self.sink.seek(SeekFrom::Start(written_end))?;
+                    self.data.archive_size = written_end;
+                    return Err(ArchiveError::InconsistentArchive(rollback_overrun()));
+                }
+                Ok(outcome)
+            }
+            outcome => outcome,
+        }
+    }
+
+    /// Write the entry, its header and its payload compressed with the method.
+    fn append_payload<P>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        compressor: CompressionMethod,
+        payload: &mut P,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        P: Read,
+    {
+        let file_header_offset = self.data.archive_size;
+        let mut hasher = EntryHasher::new(options);
 
         let (file_header, mut archive_file_entry) = build_file_header(
             file_name,
@@ -108,45 +210,56 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
             file_header_offset,
             &self.data,
             false,
-        );
+        )?;
 
         self.sink.write_all(file_header.buffer())?;
 
         let file_begin = self.sink.stream_position()?;
 
-        let (uncompressed_size, is_text) = compress(
+        let (uncompressed_size, is_text) = match compress(
             compressor,
             &mut self.sink,
             payload,
             &mut hasher,
-            Level::Default,
-        )?;
+            self.data.compression_level(options),
+        ) {
+            Ok(sizes) => sizes,
+            Err(error) => {
+                //the partial entry is left out of the central directory, the next one is written after it
+                self.data.archive_size = self.sink.get_written_bytes_count()?;
+                return Err(error);
+            }
+        };
 
         let archive_size = self.sink.stream_position()?;
         let compressed_size = archive_size - file_begin;
 
-        let crc32 = hasher.finalize();
+        let (crc32, digest) = match hasher.finalize() {
+            Ok(hashes) => hashes,
+            Err(error) => {
+                //the entry is left out of the central directory, the next one is written after it
+                self.data.archive_size = archive_size;
+                return Err(error);
+            }
+        };
         archive_file_entry.crc32 = crc32;
         archive_file_entry.compressed_size = compressed_size;
         archive_file_entry.uncompressed_size = uncompressed_size;
         archive_file_entry.apparently_text_file(is_text);
 
         if is_streaming(archive_file_entry.general_purpose_flags) {
-            let data_descriptor = build_data_descriptor(&archive_file_entry);
+            let data_descriptor = build_data_descriptor(&archive_file_entry, options);
             self.sink.write_all(data_descriptor.buffer())?;
         } else {
             let sizes_update = build_file_sizes_update(&archive_file_entry);
 
-            //position in the the file header
-            self.sink
-                .seek(SeekFrom::Start(file_header_offset + FILE_HEADER_CRC_OFFSET))?;
-
-            self.sink.write_all(sizes_update.buffer())?;
+            self.patch_sink(
+                file_header_offset + FILE_HEADER_CRC_OFFSET,
+                sizes_update.buffer(),
+                archive_size,
+            )?;
 
-            //position back at the end
-            self.sink.seek(SeekFrom::Start(archive_size))?;
-
-            if archive_file_entry.is_zip64() {
+            if archive_file_entry.needs_zip64_local() {
                 if archive_file_entry.has_zip64_extra_field() {
                     let mut file_descriptor = ArchiveDescriptor::new(30);
 
@@ -155,32 +268,674 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
                     zip64_extra_field
                         .local_header_write_data(&mut file_descriptor, &archive_file_entry);
 
-                    self.sink
-                        .seek(SeekFrom::Start(file_header_offset + FILE_HEADER_BASE_SIZE))?;
-
-                    self.sink.write_all(file_descriptor.buffer())?;
-                    //position back at the end
-                    self.sink.seek(SeekFrom::Start(archive_size))?;
+                    //the zip64 extra field is the first one, right after the file name
+                    self.patch_sink(
+                        file_header_offset
+                            + FILE_HEADER_BASE_SIZE
+                            + archive_file_entry.file_name_len as u64,
+                        file_descriptor.buffer(),
+                        archive_size,
+                    )?;
+                } else {
+                    //it wasn't identified as zip64 from option, but it can be as stream
+                    let data_descriptor = build_data_descriptor(&archive_file_entry, options);
+                    self.sink.write_all(data_descriptor.buffer())?;
                 }
-            } else {
-                //it wasn't identified as zip64 from option, but it can be as stream
-                let data_descriptor = build_data_descriptor(&archive_file_entry);
-                self.sink.write_all(data_descriptor.buffer())?;
             }
         }
 
         archive_file_entry.need_to_add_zip64_extra_field();
 
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            digest,
+            index: self.data.entry_count(),
+        };
+
         self.data.add_archive_file_entry(archive_file_entry);
 
         self.data.archive_size = self.sink.get_written_bytes_count()?;
 
+        Ok(outcome)
+    }
+
+    /// Append several entities to the archive, sharing the same options.
+    ///
+    /// Each entry is appended with [`Self::append`], in the iterator order. It stops at the first error.
+    ///
+    /// # Arguments
+    /// * `entries` - The entries' name and payload as a [`Read`]
+    /// * `options` - The options applied to every entry
+    ///
+    pub fn append_all<'r, I>(
+        &mut self,
+        entries: I,
+        options: &FileOptions,
+    ) -> Result<Vec<AppendOutcome>, ArchiveError>
+    where
+        W: Write,
+        I: IntoIterator<Item = (String, Box<dyn Read + 'r>)>,
+    {
+        let mut outcomes = Vec::new();
+        for (file_name, mut payload) in entries {
+            outcomes.push(self.append(&file_name, options, &mut payload)?);
+        }
+        Ok(outcomes)
+    }
+
+    /// Append a new entity to the archive, taking the payload by value.
+    ///
+    /// It is [`Self::append`] for a payload built inline, e.g. a `Box<dyn Read>` or a reader produced by an
+    /// iterator, which then doesn't need to be bound to a mutable variable first.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The entity's payload as a [`Read`], dropped once appended
+    ///
+    pub fn append_reader_owned<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        mut payload: R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        R: Read,
+    {
+        self.append(file_name, options, &mut payload)
+    }
+
+    /// Overwrite the bytes at the position, to patch an entry's local header, then seek back at the end.
+    ///
+    /// If a seek or the write fails, the sink is sought back at the end on a best effort basis, so the next
+    /// entries aren't written over this one, and [`ArchiveError::InconsistentArchive`] is returned.
+    fn patch_sink(&mut self, position: u64, bytes: &[u8], end: u64) -> Result<(), ArchiveError>
+    where
+        W: Write,
+    {
+        let patched = self
+            .sink
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| self.sink.write_all(bytes))
+            .and_then(|_| self.sink.seek(SeekFrom::Start(end)));
+
+        match patched {
+            Ok(_) => Ok(()),
+            Err(error) => {
+                let _ = self.sink.seek(SeekFrom::Start(end));
+                self.data.archive_size = end;
+                Err(ArchiveError::InconsistentArchive(error))
+            }
+        }
+    }
+
+    /// Append a new entity to the archive, from a payload whose length is known through [`Seek`].
+    ///
+    /// The payload's remaining length (from its current position) is read upfront. A small payload is read in
+    /// memory and appended with [`Self::append_bytes`], so the local header is complete: no patch nor data
+    /// descriptor is needed. A larger one is appended with [`Self::append`], with the `large_file` option set if
+    /// its sizes might not fit on 32 bits.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The entity's payload, it is left at its end
+    ///
+    pub fn append_seekable<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        payload: &mut R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        R: Read + Seek,
+    {
+        let start = payload.stream_position()?;
+        let end = payload.seek(SeekFrom::End(0))?;
+        payload.seek(SeekFrom::Start(start))?;
+        let size = end.saturating_sub(start);
+
+        if size <= IN_MEMORY_MAX_SIZE {
+            let mut bytes = Vec::with_capacity(size as usize);
+            payload.read_to_end(&mut bytes)?;
+            self.append_bytes(file_name, options, &bytes)
+        } else {
+            let options = options
+                .clone()
+                .large_file(options.large_file || might_need_zip64(size));
+            self.append(file_name, &options, payload)
+        }
+    }
+
+    /// Append a new entity to the archive, from a payload whose length is declared upfront, e.g. an upload's
+    /// content length.
+    ///
+    /// The payload must hold exactly `declared_len` bytes: it is read up to this length then checked for a
+    /// trailing byte. A shorter or longer payload fails the append with [`ArchiveError::LengthMismatch`], telling
+    /// the actual length and the CRC-32 of the bytes read. A longer payload is read to its end to tell its length.
+    /// As for any failed append, the partially written entry is left out of the central directory.
+    ///
+    /// The `large_file` option is set if the sizes might not fit on 32 bits.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `declared_len` - The payload's declared length (bytes)
+    /// * `payload` - The entity's payload as a [`Read`]
+    ///
+    pub fn append_sized<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        declared_len: u64,
+        payload: &mut R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        R: Read,
+    {
+        let options = options
+            .clone()
+            .large_file(options.large_file || might_need_zip64(declared_len));
+        let mut sized = SizedReader {
+            reader: payload,
+            length: DeclaredLength::new(declared_len),
+        };
+        let result = if self.data.inlines_store_size(&options, declared_len) {
+            let mut bytes = Vec::with_capacity(declared_len as usize);
+            match sized.read_to_end(&mut bytes) {
+                Ok(_) => self.append_bytes(file_name, &options, &bytes),
+                Err(error) => Err(error.into()),
+            }
+        } else {
+            self.append(file_name, &options, &mut sized)
+        };
+
+        let extra = if sized.length.is_overrun() {
+            std::io::copy(sized.reader, &mut std::io::sink())?
+        } else {
+            0
+        };
+        match sized.length.mismatch(extra) {
+            Some(mismatch) => Err(mismatch),
+            None => result,
+        }
+    }
+
+    /// Append a new entity to the archive, reading the payload from the file at the given path.
+    ///
+    /// With [`CompressionMethod::Auto`], the compression method is selected from the entry name's extension,
+    /// so already compressed files are stored. The entry takes the file's modification time, like
+    /// [`Self::append_file`].
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `path` - The path of the file to be archived
+    ///
+    pub fn append_path<P>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        path: P,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        self.append_file(file_name, options, &mut file)
+    }
+
+    /// Append a new entity to the archive, reading the payload from an opened file.
+    ///
+    /// Unless the options set another modification time than the default one, the entry takes the file's
+    /// modification time.
+    /// On Unix, unless the options set the permissions, the entry keeps the file's execute bits, e.g. a `0o755`
+    /// script stays runnable once extracted.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `file` - The file to be archived
+    ///
+    pub fn append_file(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        file: &mut File,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+    {
+        let options = options.with_file_metadata(file.metadata());
+        self.append(file_name, &options, file)
+    }
+
+    /// Append a new entity to the archive, decompressing the provided gzip stream on the fly.
+    ///
+    /// The entry holds the decompressed content. A stream of concatenated gzip members (e.g. appended log
+    /// files) is decompressed as a whole.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `gz_reader` - The gzip compressed payload
+    ///
+    /// # Features
+    ///
+    /// Requires `deflate` feature
+    #[cfg(feature = "deflate")]
+    pub fn append_gz_decompressed<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        gz_reader: R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        R: Read,
+    {
+        let mut decoder = MultiGzDecoder::new(gz_reader);
+        self.append(file_name, options, &mut decoder)
+    }
+
+    /// Append a new entity to the archive like [`Self::append`], writing a copy of its uncompressed payload to
+    /// `capture` at the same time.
+    ///
+    /// The payload is read once: each chunk is written to `capture` before being compressed. If the append fails,
+    /// `capture` holds the payload read so far.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The entity's payload as a [`Read`]
+    /// * `capture` - The [`Write`] receiving the uncompressed payload
+    ///
+    pub fn append_and_capture<R, C>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions,
+        payload: &mut R,
+        capture: &mut C,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        R: Read,
+        C: Write,
+    {
+        let mut payload = CaptureReader {
+            reader: payload,
+            capture,
+        };
+        let outcome = self.append(file_name, options, &mut payload)?;
+        payload.capture.flush()?;
+        Ok(outcome)
+    }
+
+    /// Append a new entity to the archive using the provided name, options and in-memory payload.
+    ///
+    /// The payload is compressed before anything is written, so the local header is written with the final
+    /// CRC-32 and sizes: the archive doesn't need to seek back and no data descriptor is needed, even for a
+    /// streamable archive.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `bytes` - The entity's payload
+    ///
+    pub fn append_bytes(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        bytes: &[u8],
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+    {
+        let mut buffer = Vec::new();
+        let (compressor, outcome, is_text, compressed) =
+            self.compress_bytes(file_name, options, bytes, &mut buffer)?;
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compressor,
+            &outcome,
+            is_text,
+            [compressed],
+        )?;
+
+        Ok(outcome)
+    }
+
+    /// Append the same in-memory payload under several names, compressing it once.
+    ///
+    /// The payload is compressed as by [`Self::append_bytes`], the compression method being chosen for the first
+    /// name, then its compressed bytes are written for each name, as by [`Self::append_raw`]. ZIP has no aliasing:
+    /// each name gets its own entry, with a distinct local header and its own copy of the compressed bytes, and
+    /// extractors create a file per name, e.g. `index.html` and `default.html` with identical content.
+    ///
+    /// It returns the outcome of each entry, in the order of the names. Without any name, nothing is written.
+    ///
+    /// # Arguments
+    /// * `names` - The names of the archive entries
+    /// * `options` - Entries' archive options
+    /// * `bytes` - The entities' payload
+    ///
+    pub fn append_bytes_multi(
+        &mut self,
+        names: &[&str],
+        options: &FileOptions<'a>,
+        bytes: &[u8],
+    ) -> Result<Vec<AppendOutcome>, ArchiveError>
+    where
+        W: Write,
+    {
+        let Some(first_name) = names.first() else {
+            return Ok(Vec::new());
+        };
+
+        let mut buffer = Vec::new();
+        let (compressor, mut outcome, is_text, compressed) =
+            self.compress_bytes(first_name, options, bytes, &mut buffer)?;
+
+        let mut outcomes = Vec::with_capacity(names.len());
+        for file_name in names {
+            outcome.index = self.data.entry_count();
+            self.write_complete_entry(
+                file_name,
+                options,
+                compressor,
+                &outcome,
+                is_text,
+                [compressed],
+            )?;
+            outcomes.push(outcome.clone());
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Compress an in-memory payload, returns the compression method, the entry's outcome, if the payload is
+    /// text, and the compressed bytes: the payload itself if it is stored, the buffer otherwise.
+    fn compress_bytes<'b>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        bytes: &'b [u8],
+        buffer: &'b mut Vec<u8>,
+    ) -> Result<(CompressionMethod, AppendOutcome, bool, &'b [u8]), ArchiveError> {
+        let mut hasher = EntryHasher::new(options);
+        hasher.check_entry_size(bytes.len() as u64)?;
+        let compressor = adapt_compressor(
+            self.data.compressor(file_name, options),
+            options,
+            self.data.compression_level(options),
+            bytes,
+        )?;
+
+        let (uncompressed_size, is_text, compressed) = if compressor == CompressionMethod::Store() {
+            //no encoder nor copy needed for a stored payload
+            hasher.update(bytes);
+            let sample = &bytes[..bytes.len().min(BUFFER_SIZE)];
+            (bytes.len() as u64, hasher.is_text(sample), bytes)
+        } else {
+            let (uncompressed_size, is_text) = compress(
+                compressor,
+                buffer,
+                &mut { bytes },
+                &mut hasher,
+                self.data.compression_level(options),
+            )?;
+            (uncompressed_size, is_text, buffer.as_slice())
+        };
+
+        let (crc32, digest) = hasher.finalize()?;
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size: compressed.len() as u64,
+            uncompressed_size,
+            digest,
+            index: self.data.entry_count(),
+        };
+
+        Ok((compressor, outcome, is_text, compressed))
+    }
+
+    /// Append a new entity to the archive from its payload already compressed, written verbatim.
+    ///
+    /// The payload must have been compressed with `compression_method`, `crc32` and `uncompressed_size` describing
+    /// the uncompressed payload: they are recorded as is, without any check. As the CRC-32 and sizes are known, the
+    /// local header is complete and no data descriptor is needed, even for a streamable archive.
+    ///
+    /// It allows to reuse the compressed bytes of identical entries, e.g. those reported by
+    /// [`Self::report_duplicates`]. ZIP has no aliasing: each entry holds its own copy of the bytes, and
+    /// extractors create a file per entry.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options, its compression method is ignored
+    /// * `compression_method` - The method the payload was compressed with
+    /// * `crc32` - The CRC-32 of the uncompressed payload
+    /// * `uncompressed_size` - The size of the uncompressed payload
+    /// * `compressed` - The compressed payload
+    ///
+    pub fn append_raw(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compression_method: CompressionMethod,
+        crc32: u32,
+        uncompressed_size: u64,
+        compressed: &[u8],
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+    {
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size: compressed.len() as u64,
+            uncompressed_size,
+            digest: None,
+            index: self.data.entry_count(),
+        };
+
+        //only a stored payload can be checked for text
+        let sample = if compression_method == CompressionMethod::Store() {
+            &compressed[..compressed.len().min(BUFFER_SIZE)]
+        } else {
+            &[]
+        };
+        let is_text = EntryHasher::new(options).is_text(sample);
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compression_method,
+            &outcome,
+            is_text,
+            [compressed],
+        )?;
+
+        Ok(outcome)
+    }
+
+    /// Append a new entity to the archive from its payload already compressed, supplied as frames written verbatim
+    /// one after the other, e.g. the zstd or xz frames of a separate compressor.
+    ///
+    /// It generalizes [`Self::append_raw`] to a compressed payload arriving piecemeal. As for it, the CRC-32 and
+    /// sizes describe the whole payload and are recorded as is, the local header is complete and no data
+    /// descriptor is needed. If the frames' total length isn't `compressed_size`, it returns
+    /// [`ArchiveError::LengthMismatch`] with the frames' length, and the entry is left out of the central
+    /// directory.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options, its compression method is ignored
+    /// * `compression_method` - The method the frames were compressed with
+    /// * `crc32` - The CRC-32 of the uncompressed payload
+    /// * `uncompressed_size` - The size of the uncompressed payload
+    /// * `compressed_size` - The total length of the frames
+    /// * `frames` - The compressed payload's frames
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub fn append_precompressed_frames<'f, I>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compression_method: CompressionMethod,
+        crc32: u32,
+        uncompressed_size: u64,
+        compressed_size: u64,
+        frames: I,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+        I: IntoIterator<Item = &'f [u8]>,
+    {
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            digest: None,
+            index: self.data.entry_count(),
+        };
+
+        //only a stored payload can be checked for text, from its first frame
+        let mut frames = frames.into_iter().peekable();
+        let sample = match frames.peek() {
+            Some(frame) if compression_method == CompressionMethod::Store() => {
+                &frame[..frame.len().min(BUFFER_SIZE)]
+            }
+            _ => &[],
+        };
+        let is_text = EntryHasher::new(options).is_text(sample);
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compression_method,
+            &outcome,
+            is_text,
+            frames,
+        )?;
+
+        Ok(outcome)
+    }
+
+    /// Write an entry whose payload is already compressed, with its complete local header.
+    ///
+    /// The frames are written in order, their total length must be the outcome's compressed size: otherwise, the
+    /// local header is wrong and the entry is left out of the central directory.
+    fn write_complete_entry<'f, I>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compressor: CompressionMethod,
+        outcome: &AppendOutcome,
+        is_text: bool,
+        frames: I,
+    ) -> Result<(), ArchiveError>
+    where
+        W: Write,
+        I: IntoIterator<Item = &'f [u8]>,
+    {
+        let (file_header, archive_file_entry) = build_complete_file_header(
+            file_name,
+            options,
+            compressor,
+            self.data.archive_size,
+            &self.data,
+            outcome,
+            is_text,
+        )?;
+
+        self.sink.write_all(file_header.buffer())?;
+
+        let mut written = 0;
+        for frame in frames {
+            self.sink.write_all(frame)?;
+            written += frame.len() as u64;
+        }
+
+        if written != outcome.compressed_size {
+            self.data.archive_size = self.sink.get_written_bytes_count()?;
+            return Err(ArchiveError::LengthMismatch {
+                expected: outcome.compressed_size,
+                actual: written,
+                crc32: outcome.crc32,
+            });
+        }
+
+        self.data.add_archive_file_entry(archive_file_entry);
+        self.data.archive_size = self.sink.get_written_bytes_count()?;
+
         Ok(())
     }
 
-    /// Append a directory entry to the archive.
+    /// Append a text listing of all the entries appended so far.
+    ///
+    /// Each line holds the entry name, its uncompressed size and its CRC-32 (in hexadecimal), separated by tabs.
+    /// It is meant to be called after all the other appends and before the archive is finalized.
+    ///
+    /// # Arguments
+    /// * `file_name` - The name of the listing entry, e.g. `MANIFEST`
+    /// * `options` - Entry's archive options
+    ///
+    pub fn append_manifest(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: Write,
+    {
+        let manifest = self.data.build_manifest();
+        self.append_bytes(file_name, options, manifest.as_bytes())
+    }
+
+    /// Append an in-memory tree where the keys are the entries' paths and the values their payload.
     ///
+    /// The entries are appended in the map's (sorted) order, so the archive is reproducible. A directory entry
+    /// is appended for each path prefix before its first entry. A key ending with a slash (`/`) is appended as
+    /// a directory, its value is ignored.
+    pub fn append_virtual_tree(
+        &mut self,
+        tree: &BTreeMap<String, Vec<u8>>,
+        options: &FileOptions<'a>,
+    ) -> Result<(), ArchiveError>
+    where
+        W: Write,
+    {
+        let mut directories = BTreeSet::new();
+
+        for (path, bytes) in tree {
+            //the prefixes, including the path itself if it is a directory
+            for (index, _) in path.match_indices('/') {
+                let directory = &path[..=index];
+                if directories.insert(directory.to_owned()) {
+                    self.append_directory(directory, options)?;
+                }
+            }
+
+            if !path.ends_with('/') {
+                self.append_bytes(path, options, bytes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Append a directory entry to the archive.
     ///
+    /// The directory honors the entries' metadata options as a file does: its modification time, extended
+    /// timestamp, comment, permissions and owner. It has no payload, so neither a compression method nor a data
+    /// descriptor.
     pub fn append_directory(
         &mut self,
         file_name: &str,
@@ -209,7 +964,7 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
             file_header_offset,
             &self.data,
             true,
-        );
+        )?;
 
         //Since no payload, there is no need for data descriptor
         archive_file_entry.general_purpose_flags &= !EXTENDED_LOCAL_HEADER_FLAG;
@@ -225,10 +980,67 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
 
     /// Finalize the archive by writing the necessary metadata to the end of the archive.
     ///
+    /// The central directory is written to the sink entry by entry, through a single reused buffer. The memory
+    /// used doesn't depend on the central directory size, only the appended entries' metadata is held until
+    /// then: a couple hundred bytes per entry, plus its name, comment and extra fields.
+    ///
+    /// Returns the archive size (bytes) and the [Write] object passed at creation.
+    pub fn finalize(self) -> Result<(u64, W), ArchiveError>
+    where
+        W: Write,
+    {
+        self.finalize_keep_writer()
+    }
+
+    /// Finalize the archive like [`finalize`](Self::finalize()), for formats layered on ZIP.
+    ///
+    /// The returned [Write] object is flushed and positioned right after the end of central
+    /// directory record, so the caller may append arbitrary trailing data (e.g. a signature block).
+    ///
+    /// Returns the archive size (bytes) and the [Write] object passed at creation.
+    pub fn finalize_keep_writer(self) -> Result<(u64, W), ArchiveError>
+    where
+        W: Write,
+    {
+        let report = self.finalize_report()?;
+        Ok((report.size, report.writer))
+    }
+
+    /// Finalize the archive like [`finalize_keep_writer`](Self::finalize_keep_writer()).
+    ///
+    /// Returns a [`FinalizeReport`] with the archive size (bytes), the [Write] object passed at creation, the
+    /// number of entries and whether the zip64 format extensions were needed.
+    pub fn finalize_report(self) -> Result<FinalizeReport<W>, ArchiveError>
+    where
+        W: Write,
+    {
+        self.finalize_report_with_progress(|_, _| {})
+    }
+
+    /// Finalize the archive like [`finalize`](Self::finalize()), reporting the progress of the central directory
+    /// write.
+    ///
+    /// With a huge number of entries, writing the central directory takes noticeable time. The callback is
+    /// invoked once each entry's central directory header is written, with the entry index and the number of
+    /// central directory bytes written so far.
+    ///
     /// Returns the archive size (bytes) and the [Write] object passed at creation.
-    pub fn finalize(mut self) -> Result<(u64, W), ArchiveError>
+    pub fn finalize_with_progress<F>(self, progress: F) -> Result<(u64, W), ArchiveError>
     where
         W: Write,
+        F: FnMut(usize, u64),
+    {
+        let report = self.finalize_report_with_progress(progress)?;
+        Ok((report.size, report.writer))
+    }
+
+    fn finalize_report_with_progress<F>(
+        mut self,
+        mut progress: F,
+    ) -> Result<FinalizeReport<W>, ArchiveError>
+    where
+        W: Write,
+        F: FnMut(usize, u64),
     {
         let central_directory_offset = self.sink.get_written_bytes_count()?;
         /*         println!(
@@ -237,7 +1049,12 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
         ); */
         let mut central_directory_header = ArchiveDescriptor::new(500);
 
-        for file_info in self.data.iter() {
+        if self.data.sort_central_directory {
+            self.data.sort_by_name();
+        }
+
+        let mut central_directory_written = 0;
+        for (index, file_info) in self.data.iter().enumerate() {
             /*
                        let off = self.sink.get_written_bytes_count()?;
                        println!("FILE OFFSET  {:?}  {:0X}", off, off);
@@ -245,29 +1062,311 @@ impl<'a, W: Write + 'a> ZipArchive<'a, W> {
             build_central_directory_file_header(&mut central_directory_header, file_info);
 
             self.sink.write_all(central_directory_header.buffer())?;
+            central_directory_written += central_directory_header.len() as u64;
+            progress(index, central_directory_written);
             central_directory_header.clear();
         }
 
         let current_archive_size = self.sink.get_written_bytes_count()?;
-        let central_directory_size = current_archive_size - central_directory_offset;
+        let central_directory_size = current_archive_size
+            .checked_sub(central_directory_offset)
+            .ok_or_else(|| {
+                ArchiveError::BadArchiveStructure(format!(
+                    "Central directory offset {} is past the end of the archive {}",
+                    central_directory_offset, current_archive_size
+                ))
+            })?;
+
+        if self.data.archive_digest {
+            self.data.reserve_archive_digest()?;
+        }
 
         let end_of_central_directory = build_central_directory_end(
             &mut self.data,
             central_directory_offset,
             central_directory_size,
-        );
+        )?;
 
-        self.sink.write_all(end_of_central_directory.buffer())?;
+        if self.data.archive_digest {
+            //the reserved suffix is written once the preceding bytes are hashed
+            let buffer = end_of_central_directory.buffer();
+            let digested = &buffer[..buffer.len() - ARCHIVE_DIGEST_SUFFIX_SIZE];
+            self.sink.write_all(digested)?;
 
-        self.sink.flush()?;
+            let crc32 = self.sink.written_crc32().ok_or_else(|| {
+                ArchiveError::Unsupported(
+                    "The archive digest requires a streamable archive".to_owned(),
+                )
+            })?;
+            self.sink
+                .write_all(build_archive_digest_suffix(crc32).as_bytes())?;
+        } else {
+            self.sink.write_all(end_of_central_directory.buffer())?;
+        }
 
         self.data.archive_size = self.sink.get_written_bytes_count()?;
 
-        Ok((self.data.archive_size, self.sink.get_into()))
+        //make sure the writer is left right after the end of central directory
+        self.sink.seek(SeekFrom::Start(self.data.archive_size))?;
+        self.sink.finalizing();
+        self.sink.flush()?;
+
+        Ok(FinalizeReport {
+            size: self.data.archive_size,
+            entry_count: self.data.entry_count(),
+            used_zip64: self.data.used_zip64(),
+            writer: self.sink.get_into(),
+        })
+    }
+
+    /// Set whether the CRC-32 of the whole archive is embedded at the end of the archive comment.
+    ///
+    /// It is meant to check the archive integrity after transport. When the archive is finalized, the archive
+    /// comment is suffixed with `archflow-crc32:` followed by the CRC-32, as 8 lowercase hexadecimal digits. The
+    /// CRC-32 is computed over all the archive bytes before this 23 bytes suffix, which ends the archive. A
+    /// verifier checks that the archive ends with the prefix and a CRC-32 matching the preceding bytes.
+    ///
+    /// It requires a __streamable__ archive and must be set before the first entry is appended, otherwise an
+    /// [`ArchiveError::Unsupported`] is returned.
+    ///
+    /// The default value is `false`.
+    pub fn with_archive_digest(&mut self, digest: bool) -> Result<(), ArchiveError> {
+        if digest {
+            if self.data.archive_size != 0 {
+                return Err(ArchiveError::Unsupported(
+                    "The archive digest must be enabled before the first entry".to_owned(),
+                ));
+            }
+            if !self.sink.start_hashing() {
+                return Err(ArchiveError::Unsupported(
+                    "The archive digest requires a streamable archive".to_owned(),
+                ));
+            }
+        }
+        self.data.archive_digest = digest;
+        Ok(())
+    }
+
+    /// Set whether the appended entries with the same content as a previous one are reported.
+    ///
+    /// An entry is reported, in [`Self::duplicates`], if a previous entry has the same CRC-32, uncompressed size
+    /// and name stem (the base name without extension). Its compressed bytes could have been reused with
+    /// [`Self::append_raw`]. Empty entries are never reported. With the `log` feature, the duplicates are logged
+    /// at info level too.
+    ///
+    /// The default value is `false`.
+    pub fn report_duplicates(&mut self, report: bool) {
+        self.data.contents = if report { Some(HashMap::new()) } else { None };
+        self.data.duplicates.clear();
+    }
+
+    /// The duplicates reported since [`Self::report_duplicates`] was set, in the append order: each entry's name
+    /// with the name of the first entry with the same content.
+    pub fn duplicates(&self) -> &[(String, String)] {
+        &self.data.duplicates
+    }
+
+    /// Set whether the central directory lists the entries sorted by name, instead of the append order.
+    ///
+    /// The entries are sorted by their name's bytes when the archive is finalized. Only the central directory
+    /// is affected: the local headers and payloads stay in the append order, each central directory entry
+    /// pointing to its local header.
+    ///
+    /// The default value is `false`.
+    pub fn sort_central_directory_by_name(&mut self, sort: bool) {
+        self.data.sort_central_directory = sort;
+    }
+
+    /// Set the compression level of the entries appended afterwards whose [`FileOptions`] leave it at
+    /// `Level::Inherit`, their default.
+    ///
+    /// The default value is `Level::Default`.
+    pub fn default_compression_level(&mut self, level: Level) {
+        self.data.default_compression_level = level;
+    }
+
+    /// Set whether the zip64 format extensions are used even if the archive doesn't need them.
+    ///
+    /// If set to `true`, the zip64 end of central directory record and locator are always written, and every entry
+    /// appended afterwards stores its sizes in a zip64 extra field. This is meant for toolchains that mandate zip64.
+    ///
+    /// The default value is `false`.
+    pub fn force_zip64(&mut self, force: bool) {
+        self.data.force_zip64 = force;
+    }
+
+    /// Roll the last appended entry back, e.g. after a speculative append to try another compression method.
+    ///
+    /// The sink is truncated at the entry's local header, where the next entry is written: the bytes of a failed
+    /// append after it are dropped too. Only a non streamable archive can roll an entry back, its sink must
+    /// support the truncation, see [`SetLen`].
+    ///
+    /// Returns `false` if there is no entry to roll back.
+    pub fn rollback_last_entry(&mut self) -> Result<bool, ArchiveError>
+    where
+        W: Seek + SetLen,
+    {
+        if is_streaming(self.data.base_flags) {
+            return Err(ArchiveError::Unsupported(
+                "A streamable archive can't roll an entry back".to_owned(),
+            ));
+        }
+
+        let offset = match self.data.pop_archive_file_entry() {
+            Some(archive_file_entry) => archive_file_entry.offset,
+            None => return Ok(false),
+        };
+
+        self.sink
+            .writer_mut()
+            .ok_or_else(|| {
+                ArchiveError::Unsupported("The buffered sink can't be truncated".to_owned())
+            })?
+            .set_len(offset)?;
+        self.sink.seek(SeekFrom::Start(offset))?;
+        self.data.archive_size = offset;
+
+        Ok(true)
+    }
+
+    /// Set the comment of an appended entry, identified by the [`AppendOutcome::index`] its append returned.
+    ///
+    /// The comment is written in the central directory, so it can be set until the archive is finalized. Returns
+    /// [`ArchiveError::EntryNotFound`] if no entry has this index, or [`ArchiveError::CommentTooLong`] if the
+    /// comment is longer than 0xFFFF bytes.
+    pub fn set_entry_comment(&mut self, index: usize, comment: &str) -> Result<(), ArchiveError> {
+        self.data.set_entry_comment(index, comment)
     }
 
     ///Set the archive comment
+    ///
+    /// The comment is silently truncated to 0xFFFF bytes, on a character boundary, use
+    /// [`Self::try_set_archive_comment`] to be notified instead.
+    ///
+    /// The archive comment is written as UTF-8 but, unlike the entries' names and comments, it can't be flagged
+    /// as such: the ZIP specification has no encoding flag for it. UTF-8 is the conventional encoding readers
+    /// assume.
     pub fn set_archive_comment(&mut self, comment: &str) {
         self.data.set_archive_comment(comment);
     }
+
+    ///Set the archive comment
+    ///
+    /// Returns [`ArchiveError::CommentTooLong`] with the comment length in bytes if the comment is
+    /// longer than 0xFFFF bytes, in this case the archive comment is left unchanged.
+    pub fn try_set_archive_comment(&mut self, comment: &str) -> Result<(), ArchiveError> {
+        self.data.try_set_archive_comment(comment)
+    }
+
+    /// Set the archive comment to JSON metadata, e.g. tooling information, to be read back with
+    /// `ArchiveReader::metadata_json`.
+    ///
+    /// The JSON is written after the `archflow-json:` prefix, replacing the archive comment. Returns
+    /// [`ArchiveError::CommentTooLong`] if the prefixed JSON is longer than 0xFFFF bytes, in this case the archive
+    /// comment is left unchanged. The metadata is compatible with the archive digest, which is appended after it.
+    ///
+    /// # Features
+    ///
+    /// Requires `serde` feature
+    #[cfg(feature = "serde")]
+    pub fn set_metadata_json(&mut self, metadata: &serde_json::Value) -> Result<(), ArchiveError> {
+        self.data.set_metadata_json(metadata)
+    }
+}
+
+/// A seekable sink whose length can be set, to truncate what the archive wrote.
+///
+/// See [`ZipArchive::rollback_last_entry`].
+pub trait SetLen {
+    /// Truncate or extend the sink to `len` bytes.
+    fn set_len(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl SetLen for File {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        File::set_len(self, len)
+    }
+}
+
+impl SetLen for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.get_mut().resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+/// Selects the entry's compression method from the payload's head, peeked before the header is written: an
+/// empty payload is stored with the `store_empty` option, an incompressible sample with the `adaptive` one. The
+/// sample is compressed at the entry's level.
+fn adapt_compressor(
+    compressor: CompressionMethod,
+    options: &FileOptions<'_>,
+    compression_level: Level,
+    head: &[u8],
+) -> Result<CompressionMethod, ArchiveError> {
+    if options.store_empty && head.is_empty() {
+        return Ok(CompressionMethod::Store());
+    }
+
+    if options.adaptive && compressor != CompressionMethod::Store() && !head.is_empty() {
+        let sample = &head[..head.len().min(options.adaptive_sample_size)];
+        let mut compressed = Vec::new();
+        let mut hasher = EntryHasher::new(&FileOptions::default());
+        compress(
+            compressor,
+            &mut compressed,
+            &mut { sample },
+            &mut hasher,
+            compression_level,
+        )?;
+        if is_incompressible(options, sample.len(), compressed.len()) {
+            return Ok(CompressionMethod::Store());
+        }
+    }
+
+    Ok(compressor)
+}
+
+/// A reader checking its payload holds exactly the declared length.
+struct SizedReader<'r, R: Read> {
+    reader: &'r mut R,
+    length: DeclaredLength,
+}
+
+impl<'r, R: Read> Read for SizedReader<'r, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.length.remaining();
+        if remaining == 0 {
+            //probe for a byte past the declared length
+            let read = self.reader.read(&mut [0; 1])?;
+            return if read == 0 {
+                Ok(0)
+            } else {
+                Err(self.length.end(true))
+            };
+        }
+
+        let max =
+            usize::try_from(remaining).map_or(buf.len(), |remaining| remaining.min(buf.len()));
+        let read = self.reader.read(&mut buf[..max])?;
+        if read == 0 && max > 0 {
+            return Err(self.length.end(false));
+        }
+        self.length.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// A reader writing what it reads to its capture.
+struct CaptureReader<'r, R: Read, C: Write> {
+    reader: &'r mut R,
+    capture: &'r mut C,
+}
+
+impl<'r, R: Read, C: Write> Read for CaptureReader<'r, R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.capture.write_all(&buf[..read])?;
+        Ok(read)
+    }
 }
@@ -1,34 +1,51 @@
 use std::io::{Read, Write};
 
+#[cfg(feature = "bzip2")]
 use bzip2::write::BzEncoder;
-use crc32fast::Hasher;
+#[cfg(feature = "deflate")]
 use flate2::{write::DeflateEncoder, Compression};
+#[cfg(feature = "xz")]
 use xz2::write::XzEncoder;
 
+#[cfg(feature = "zstd")]
+use crate::compress::common::zstd_level;
 use crate::{
-    compress::common::{compress_common, compress_common_std, is_text_buf, write_std},
+    compress::common::{
+        compress_common, compress_common_std, write_std, BudgetReader, EntryHasher,
+    },
     compression::{CompressionMethod, Level},
     error::ArchiveError,
 };
 
+impl<R: Read> Read for BudgetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.check_deadline()?;
+        let read = self.inner.read(buf)?;
+        self.record(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "deflate")]
 impl From<Level> for flate2::Compression {
     fn from(level: Level) -> Self {
         match level {
             Level::Fastest => Compression::fast(),
             Level::Best => Compression::best(),
-            Level::Default => Compression::default(),
+            Level::Default | Level::Inherit => Compression::default(),
             Level::Precise(val) => Compression::new(val as u32),
             Level::None => Compression::none(),
         }
     }
 }
 
+#[cfg(feature = "bzip2")]
 impl From<Level> for bzip2::Compression {
     fn from(level: Level) -> Self {
         match level {
             Level::Fastest => bzip2::Compression::fast(),
             Level::Best => bzip2::Compression::best(),
-            Level::Default => bzip2::Compression::default(),
+            Level::Default | Level::Inherit => bzip2::Compression::default(),
             Level::Precise(val) => bzip2::Compression::new(val as u32),
             Level::None => bzip2::Compression::none(),
         }
@@ -40,7 +57,7 @@ impl From<Level> for u32 {
         match level {
             Level::Fastest => 1,
             Level::Best => 9,
-            Level::Default => 6,
+            Level::Default | Level::Inherit => 6,
             Level::Precise(val) => val as u32,
             Level::None => 0,
         }
@@ -51,7 +68,7 @@ pub fn compress<'a, R, W>(
     compressor: CompressionMethod,
     writer: &'a mut W,
     reader: &'a mut R,
-    hasher: &'a mut Hasher,
+    hasher: &'a mut EntryHasher,
     compression_level: Level,
 ) -> Result<(u64, bool), ArchiveError>
 where
@@ -64,12 +81,28 @@ where
         compressor
     };
 
+    //the default level is defined by the compression method
+    #[cfg_attr(
+        not(any(
+            feature = "deflate",
+            feature = "bzip2",
+            feature = "zstd",
+            feature = "xz"
+        )),
+        allow(unused_variables)
+    )]
+    let compression_level = match compression_level {
+        Level::Default | Level::Inherit => Level::Precise(compression_method.default_level()),
+        level => level,
+    };
+
     match compression_method {
         CompressionMethod::Store() => {
             let total_read = write_std!(writer, hasher, reader);
             Ok(total_read)
         }
 
+        #[cfg(feature = "deflate")]
         CompressionMethod::Deflate() => {
             let mut encoder = DeflateEncoder::new(writer, compression_level.into());
 
@@ -78,6 +111,7 @@ where
             Ok(total_read)
         }
 
+        #[cfg(feature = "bzip2")]
         CompressionMethod::BZip2() => {
             let mut encoder = BzEncoder::new(writer, compression_level.into());
 
@@ -86,20 +120,16 @@ where
             Ok(total_read)
         }
 
+        #[cfg(feature = "zstd")]
         CompressionMethod::Zstd() => {
-            let zstd_compression_level = match compression_level {
-                Level::Fastest => 1,
-                Level::Best => 22,
-                Level::Default => zstd::DEFAULT_COMPRESSION_LEVEL,
-                Level::None => 0,
-                Level::Precise(val) => val,
-            };
+            let zstd_compression_level = zstd_level(compression_level)?;
 
             let mut encoder = zstd::stream::write::Encoder::new(writer, zstd_compression_level)?;
             let total_read = compress_common_std!(encoder, hasher, reader);
 
             Ok(total_read)
         }
+        #[cfg(feature = "xz")]
         CompressionMethod::Xz() => {
             let mut encoder = XzEncoder::new(writer, compression_level.into());
 
@@ -108,13 +138,22 @@ where
             Ok(total_read)
         }
 
+        #[allow(unreachable_patterns)]
+        CompressionMethod::Deflate()
+        | CompressionMethod::BZip2()
+        | CompressionMethod::Zstd()
+        | CompressionMethod::Xz() => Err(ArchiveError::CompressionMethodNotCompiled(
+            compression_method,
+        )),
+
         _ => Err(ArchiveError::UnsuportedCompressionMethod(compressor)),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "deflate"))]
 mod test {
     use crate::compress::std::write_wrapper::{CommonWrapper, WriteWrapper};
+    use crate::compress::FileOptions;
     use crate::compression::Level;
 
     use super::*;
@@ -152,7 +191,7 @@ mod test {
         let x = b"example";
 
         let compressor = CompressionMethod::Deflate();
-        let mut hasher = Hasher::new();
+        let mut hasher = EntryHasher::new(&FileOptions::default());
 
         //let a: AsyncRead = &x;
         let mut writer: Box<dyn CommonWrapper<Vec<u8>>> = Box::new(WriteWrapper::new(Vec::new()));
@@ -171,6 +210,113 @@ mod test {
         println!("{:X?}", temp);
     }
 
+    /// Tells if the bytes start with a zlib header: deflate method (8) and a header checksum multiple of 31.
+    fn has_zlib_header(bytes: &[u8]) -> bool {
+        bytes.len() >= 2
+            && bytes[0] & 0x0F == 8
+            && (u16::from(bytes[0]) << 8 | u16::from(bytes[1])) % 31 == 0
+    }
+
+    #[test]
+    fn test_deflate_is_raw() {
+        use flate2::read::DeflateDecoder;
+        use std::io::Read;
+
+        let x = b"Some string data, some string data, some more string data".repeat(20);
+
+        for level in [Level::Fastest, Level::Default, Level::Best] {
+            let mut hasher = EntryHasher::new(&FileOptions::default());
+            let mut writer: Box<dyn CommonWrapper<Vec<u8>>> =
+                Box::new(WriteWrapper::new(Vec::new()));
+
+            compress(
+                CompressionMethod::Deflate(),
+                &mut writer,
+                &mut x.as_slice(),
+                &mut hasher,
+                level,
+            )
+            .unwrap();
+            let compressed = writer.get_into();
+
+            //ZIP entries hold raw deflate data, without the zlib header and trailer
+            assert!(!has_zlib_header(&compressed), "{:02X?}", &compressed[..2]);
+
+            let mut decompressed = Vec::new();
+            DeflateDecoder::new(compressed.as_slice())
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, x);
+        }
+    }
+
+    #[test]
+    fn test_default_level() {
+        let x = b"Some string data, some string data, some more string data".repeat(20);
+
+        for compressor in [
+            CompressionMethod::Deflate(),
+            CompressionMethod::BZip2(),
+            CompressionMethod::Zstd(),
+            CompressionMethod::Xz(),
+        ]
+        .into_iter()
+        .filter(CompressionMethod::is_available)
+        {
+            let mut compressed = Vec::new();
+            for level in [Level::Default, Level::Precise(compressor.default_level())] {
+                let mut hasher = EntryHasher::new(&FileOptions::default());
+                let mut writer: Box<dyn CommonWrapper<Vec<u8>>> =
+                    Box::new(WriteWrapper::new(Vec::new()));
+
+                compress(
+                    compressor,
+                    &mut writer,
+                    &mut x.as_slice(),
+                    &mut hasher,
+                    level,
+                )
+                .unwrap();
+                compressed.push(writer.get_into());
+            }
+
+            assert_eq!(compressed[0], compressed[1], "{}", compressor);
+        }
+    }
+
+    #[test]
+    fn test_method_not_compiled() {
+        for compressor in [
+            CompressionMethod::BZip2(),
+            CompressionMethod::Zstd(),
+            CompressionMethod::Xz(),
+        ] {
+            let mut hasher = EntryHasher::new(&FileOptions::default());
+            let mut writer: Box<dyn CommonWrapper<Vec<u8>>> =
+                Box::new(WriteWrapper::new(Vec::new()));
+
+            let result = compress(
+                compressor,
+                &mut writer,
+                &mut b"example".as_slice(),
+                &mut hasher,
+                Level::Default,
+            );
+
+            match result {
+                Err(ArchiveError::CompressionMethodNotCompiled(method)) => {
+                    assert!(!compressor.is_available());
+                    assert_eq!(method, compressor);
+                }
+                result => {
+                    assert!(compressor.is_available());
+                    assert!(result.is_ok());
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "zstd")]
     #[test]
     fn test_zstd_level() {
         let range = zstd::compression_level_range();
@@ -179,6 +325,32 @@ mod test {
         let default = zstd::DEFAULT_COMPRESSION_LEVEL;
 
         println!("range min : {:?}", default);
+
+        for (level, valid) in [
+            (*range.end(), true),
+            (*range.start(), true),
+            (*range.end() + 1, false),
+            (*range.start() - 1, false),
+        ] {
+            let mut hasher = EntryHasher::new(&FileOptions::default());
+            let result = compress(
+                CompressionMethod::Zstd(),
+                &mut Vec::new(),
+                &mut b"Some string data".as_ref(),
+                &mut hasher,
+                Level::Precise(level),
+            );
+            match result {
+                Err(ArchiveError::UnsuportedCompressionLevel(
+                    CompressionMethod::Zstd(),
+                    detail,
+                )) => {
+                    assert!(!valid, "level {}", level);
+                    assert!(detail.contains(&format!("{:?}", range)));
+                }
+                result => assert_eq!(result.is_ok(), valid, "level {}", level),
+            }
+        }
     }
 }
 
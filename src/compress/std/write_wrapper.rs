@@ -1,12 +1,14 @@
+use crc32fast::Hasher;
 use std::{
     fmt::Debug,
-    io::{Error, Seek, Write},
+    io::{BufWriter, Error, Seek, Write},
 };
 
 #[derive(Debug)]
 pub struct WriteWrapper<W: Write> {
     writer: W,
     written_bytes_count: u64,
+    hasher: Option<Hasher>,
 }
 
 #[derive(Debug)]
@@ -19,6 +21,22 @@ pub trait CommonWrapper<W: Write + ?Sized>: Write + Seek {
     fn get_written_bytes_count(&mut self) -> Result<u64, Error>;
     fn set_written_bytes_count(&mut self, count: u64);
     fn get_into(self: Box<Self>) -> W;
+
+    /// The wrapped writer, `None` while bytes written to the wrapper are buffered before it.
+    fn writer_mut(&mut self) -> Option<&mut W>;
+
+    /// Start computing the CRC-32 of the written bytes, returns `false` if the wrapper can't.
+    fn start_hashing(&mut self) -> bool {
+        false
+    }
+
+    /// The CRC-32 of the bytes written since the hashing started.
+    fn written_crc32(&self) -> Option<u32> {
+        None
+    }
+
+    /// Tell the wrapper the archive is finalized, so its next flush is the last one.
+    fn finalizing(&mut self) {}
 }
 
 impl<W: Write> CommonWrapper<W> for WriteWrapper<W> {
@@ -33,6 +51,19 @@ impl<W: Write> CommonWrapper<W> for WriteWrapper<W> {
     fn get_into(self: Box<Self>) -> W {
         self.writer
     }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        Some(&mut self.writer)
+    }
+
+    fn start_hashing(&mut self) -> bool {
+        self.hasher.get_or_insert_with(Hasher::new);
+        true
+    }
+
+    fn written_crc32(&self) -> Option<u32> {
+        self.hasher.as_ref().map(|hasher| hasher.clone().finalize())
+    }
 }
 
 impl<W: Write> WriteWrapper<W> {
@@ -40,6 +71,7 @@ impl<W: Write> WriteWrapper<W> {
         Self {
             writer: w,
             written_bytes_count: 0,
+            hasher: None,
         }
     }
 }
@@ -55,6 +87,9 @@ impl<W: Write> Write for WriteWrapper<W> {
         match self.writer.write(buf) {
             Ok(nb_byte_written) => {
                 self.written_bytes_count += nb_byte_written as u64;
+                if let Some(hasher) = &mut self.hasher {
+                    hasher.update(&buf[..nb_byte_written]);
+                }
                 Ok(nb_byte_written)
             }
             Err(e) => Err(e),
@@ -117,4 +152,81 @@ impl<W: Write + Seek> CommonWrapper<W> for WriteSeekWrapper<W> {
     fn get_into(self: Box<Self>) -> W {
         self.writer
     }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        Some(&mut self.writer)
+    }
+}
+
+/// A streaming wrapper coalescing the writes in a buffer, written downstream once full, e.g. to send fewer and
+/// larger packets on a socket.
+///
+/// The archive's flushes between its entries are ignored: the buffer is written downstream once full, and
+/// flushed when the archive is finalized.
+#[derive(Debug)]
+pub struct BufferedSink<W: Write> {
+    wrapper: WriteWrapper<BufWriter<W>>,
+    finalizing: bool,
+}
+
+impl<W: Write> BufferedSink<W> {
+    pub fn new(w: W, capacity: usize) -> BufferedSink<W> {
+        Self {
+            wrapper: WriteWrapper::new(BufWriter::with_capacity(capacity, w)),
+            finalizing: false,
+        }
+    }
+}
+
+impl<W: Write> Write for BufferedSink<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.wrapper.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.finalizing {
+            self.wrapper.flush()
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<W: Write> Seek for BufferedSink<W> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.wrapper.seek(pos)
+    }
+}
+
+impl<W: Write> CommonWrapper<W> for BufferedSink<W> {
+    fn get_written_bytes_count(&mut self) -> Result<u64, Error> {
+        self.wrapper.get_written_bytes_count()
+    }
+
+    fn set_written_bytes_count(&mut self, count: u64) {
+        self.wrapper.set_written_bytes_count(count);
+    }
+
+    fn get_into(self: Box<Self>) -> W {
+        //the archive flushes the buffer before giving the writer back
+        debug_assert!(self.wrapper.writer.buffer().is_empty());
+        self.wrapper.writer.into_parts().0
+    }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        let writer = &mut self.wrapper.writer;
+        writer.buffer().is_empty().then(|| writer.get_mut())
+    }
+
+    fn start_hashing(&mut self) -> bool {
+        self.wrapper.start_hashing()
+    }
+
+    fn written_crc32(&self) -> Option<u32> {
+        self.wrapper.written_crc32()
+    }
+
+    fn finalizing(&mut self) {
+        self.finalizing = true;
+    }
 }
@@ -0,0 +1,280 @@
+//! A writer forwarding the bytes of an archive to two writers at once.
+//!
+//! Building an archive against a [`TeeWriter`] writes it, e.g., to a local file and to an upload stream in a
+//! single pass. As the archive wrapper counts the bytes written to the tee, they are counted once.
+//!
+//!```rust
+//! use archflow::{
+//!     compress::std::archive::ZipArchive, compress::tee::TeeWriter, compress::FileOptions,
+//!     error::ArchiveError,
+//! };
+//!
+//! # fn main() -> Result<(), ArchiveError> {
+//! let mut archive = ZipArchive::new_streamable(TeeWriter::new(Vec::new(), Vec::new()));
+//! archive.append_bytes("file1.txt", &FileOptions::default(), b"Some string data")?;
+//! let (_, tee) = archive.finalize()?;
+//!
+//! let (local, uploaded) = tee.into_inner();
+//! # assert_eq!(local, uploaded);
+//! # Ok(())
+//! # }
+//!```
+
+use std::io::{Error, ErrorKind, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+/// An [`AsyncWrite`] operation without argument, e.g. a flush.
+type PollFn<T> = fn(Pin<&mut T>, &mut Context<'_>) -> Poll<Result<(), Error>>;
+
+/// What a [`TeeWriter`] does when one of its writers fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TeeFailure {
+    /// The error is returned, the archive fails.
+    #[default]
+    FailFast,
+    /// The failed writer is dropped from the tee, the other one keeps on being written. The error is returned
+    /// only once both writers have failed.
+    Continue,
+}
+
+/// A writer forwarding every write to two writers.
+///
+/// It isn't seekable, it is meant for a streamable archive.
+///
+/// _Note:_ as an [`AsyncWrite`], a write is complete once both writers have written the whole buffer. If one of
+/// them is pending, the write must be polled again with the same buffer, as
+/// [`write_all`](tokio::io::AsyncWriteExt::write_all) does.
+#[derive(Debug)]
+pub struct TeeWriter<A, B> {
+    first: A,
+    second: B,
+    on_failure: TeeFailure,
+    errors: [Option<Error>; 2],
+    /// The bytes of the current buffer already written by each writer
+    written: [usize; 2],
+}
+
+impl<A, B> TeeWriter<A, B> {
+    pub fn new(first: A, second: B) -> TeeWriter<A, B> {
+        TeeWriter {
+            first,
+            second,
+            on_failure: TeeFailure::default(),
+            errors: [None, None],
+            written: [0, 0],
+        }
+    }
+
+    /// Set what the tee does when one of its writers fails.
+    ///
+    /// The default is `TeeFailure::FailFast`.
+    pub fn on_failure(mut self, on_failure: TeeFailure) -> TeeWriter<A, B> {
+        self.on_failure = on_failure;
+        self
+    }
+
+    /// The error of the first writer, if it failed.
+    pub fn first_error(&self) -> Option<&Error> {
+        self.errors[0].as_ref()
+    }
+
+    /// The error of the second writer, if it failed.
+    pub fn second_error(&self) -> Option<&Error> {
+        self.errors[1].as_ref()
+    }
+
+    /// Get back the two writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+
+    fn is_active(&self, writer: usize) -> bool {
+        self.errors[writer].is_none()
+    }
+
+    /// Record the failure of a writer, returns the error the tee fails with, if any.
+    fn fail(&mut self, writer: usize, error: Error) -> Result<(), Error> {
+        match self.on_failure {
+            TeeFailure::FailFast => {
+                self.written = [0, 0];
+                Err(error)
+            }
+            TeeFailure::Continue => {
+                self.errors[writer] = Some(error);
+                if self.is_active(0) || self.is_active(1) {
+                    Ok(())
+                } else {
+                    self.written = [0, 0];
+                    Err(Error::other("both writers of the tee failed"))
+                }
+            }
+        }
+    }
+
+    /// Apply the outcome of an operation done by a writer.
+    fn apply(&mut self, writer: usize, result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(error) => self.fail(writer, error),
+        }
+    }
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.is_active(0) {
+            let result = self.first.write_all(buf);
+            self.apply(0, result)?;
+        }
+        if self.is_active(1) {
+            let result = self.second.write_all(buf);
+            self.apply(1, result)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.is_active(0) {
+            let result = self.first.flush();
+            self.apply(0, result)?;
+        }
+        if self.is_active(1) {
+            let result = self.second.flush();
+            self.apply(1, result)?;
+        }
+        Ok(())
+    }
+}
+
+impl<A: AsyncWrite + Unpin, B: AsyncWrite + Unpin> TeeWriter<A, B> {
+    /// Poll a write of the rest of the buffer by the writer, returns `true` once it is complete.
+    fn poll_write_rest(
+        &mut self,
+        writer: usize,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<bool, Error>> {
+        if !self.is_active(writer) || self.written[writer] == buf.len() {
+            return Poll::Ready(Ok(true));
+        }
+
+        let rest = &buf[self.written[writer]..];
+        let polled = match writer {
+            0 => Pin::new(&mut self.first).poll_write(cx, rest),
+            _ => Pin::new(&mut self.second).poll_write(cx, rest),
+        };
+
+        match polled {
+            Poll::Ready(Ok(0)) => {
+                let error = Error::new(ErrorKind::WriteZero, "failed to write the whole buffer");
+                Poll::Ready(self.fail(writer, error).map(|_| true))
+            }
+            Poll::Ready(Ok(written)) => {
+                self.written[writer] += written;
+                Poll::Ready(Ok(self.written[writer] == buf.len()))
+            }
+            Poll::Ready(Err(error)) => Poll::Ready(self.fail(writer, error).map(|_| true)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Poll an operation for both writers, complete once both are.
+    fn poll_both(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll_first: PollFn<A>,
+        poll_second: PollFn<B>,
+    ) -> Poll<Result<(), Error>> {
+        let mut pending = false;
+
+        if self.is_active(0) {
+            match poll_first(Pin::new(&mut self.first), cx) {
+                Poll::Ready(result) => self.apply(0, result)?,
+                Poll::Pending => pending = true,
+            }
+        }
+        if self.is_active(1) {
+            match poll_second(Pin::new(&mut self.second), cx) {
+                Poll::Ready(result) => self.apply(1, result)?,
+                Poll::Pending => pending = true,
+            }
+        }
+
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl<A: AsyncWrite + Unpin, B: AsyncWrite + Unpin> AsyncWrite for TeeWriter<A, B> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        let tee = self.get_mut();
+
+        loop {
+            let first = tee.poll_write_rest(0, cx, buf)?;
+            let second = tee.poll_write_rest(1, cx, buf)?;
+
+            match (first, second) {
+                (Poll::Ready(true), Poll::Ready(true)) => {
+                    tee.written = [0, 0];
+                    return Poll::Ready(Ok(buf.len()));
+                }
+                (Poll::Pending, _) | (_, Poll::Pending) => return Poll::Pending,
+                //a partial write, keep on writing
+                _ => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut()
+            .poll_both(cx, AsyncWrite::poll_flush, AsyncWrite::poll_flush)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.get_mut()
+            .poll_both(cx, AsyncWrite::poll_shutdown, AsyncWrite::poll_shutdown)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TeeFailure, TeeWriter};
+    use std::io::{Error, ErrorKind, Write};
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(Error::new(ErrorKind::BrokenPipe, "broken"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tee_on_failure() {
+        let mut tee = TeeWriter::new(FailingWriter, Vec::new());
+        assert!(tee.write_all(&[0; 10]).is_err());
+
+        let mut tee = TeeWriter::new(FailingWriter, Vec::new()).on_failure(TeeFailure::Continue);
+        tee.write_all(&[0; 10]).unwrap();
+        tee.write_all(&[0; 5]).unwrap();
+        assert_eq!(tee.first_error().unwrap().kind(), ErrorKind::BrokenPipe);
+        assert!(tee.second_error().is_none());
+        assert_eq!(tee.into_inner().1.len(), 15);
+
+        let mut tee = TeeWriter::new(FailingWriter, FailingWriter).on_failure(TeeFailure::Continue);
+        assert!(tee.write_all(&[0; 10]).is_err());
+    }
+}
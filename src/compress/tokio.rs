@@ -1,3 +1,3 @@
 pub mod archive;
-mod async_wrapper;
-mod compressor;
+pub(crate) mod async_wrapper;
+pub(crate) mod compressor;
@@ -1,18 +1,37 @@
-use super::async_wrapper::{AsyncWriteSeekWrapper, AsyncWriteWrapper, CommonWrapper};
+use super::async_wrapper::{AsyncWriteSeekWrapper, AsyncWriteWrapper, BufferedSink, CommonWrapper};
 use super::compressor::compress;
+#[cfg(feature = "deflate")]
+use async_compression::tokio::bufread::GzipDecoder;
 
 use crate::archive_common::{ArchiveDescriptor, ExtraField, ExtraFieldZIP64ExtendedInformation};
 use crate::compress::common::{
-    build_central_directory_end, build_central_directory_file_header, build_data_descriptor,
-    build_file_header, build_file_sizes_update, is_streaming, SubZipArchiveData,
+    build_archive_digest_suffix, build_central_directory_end, build_central_directory_file_header,
+    build_complete_file_header, build_data_descriptor, build_file_header, build_file_sizes_update,
+    head_size, is_incompressible, is_streaming, might_need_zip64, rollback_overrun, BudgetReader,
+    DeclaredLength, EntryHasher, SubZipArchiveData, BUFFER_SIZE, IN_MEMORY_MAX_SIZE,
 };
-use crate::compress::FileOptions;
+use crate::compress::{AppendOutcome, FileOptions, FinalizeReport};
 use crate::compression::{CompressionMethod, Level};
-use crate::constants::{EXTENDED_LOCAL_HEADER_FLAG, FILE_HEADER_BASE_SIZE, FILE_HEADER_CRC_OFFSET};
+use crate::constants::{
+    ARCHIVE_DIGEST_SUFFIX_SIZE, EXTENDED_LOCAL_HEADER_FLAG, FILE_HEADER_BASE_SIZE,
+    FILE_HEADER_CRC_OFFSET,
+};
 use crate::error::ArchiveError;
-use crc32fast::Hasher;
-use std::io::SeekFrom;
-use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::io::{Cursor, SeekFrom};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::fs::File;
+#[cfg(feature = "deflate")]
+use tokio::io::BufReader;
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, ReadBuf,
+};
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
 
 /// A zip archive.
 ///
@@ -33,6 +52,8 @@ pub struct ZipArchive<'a, W: AsyncWrite + Unpin + 'a> {
 
 impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
     /// Create a new __streamable__ zip archive, using the underlying [`AsyncWrite`] to write files' header and payload.
+    ///
+    /// The sink is never seeked, so a write only sink (e.g. a socket) can be used.
     pub fn new_streamable(sink_: W) -> Self {
         let mut data = SubZipArchiveData::default();
         data.base_flags = EXTENDED_LOCAL_HEADER_FLAG;
@@ -42,10 +63,25 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
         }
     }
 
+    /// Create a new __streamable__ zip archive, like [`Self::new_streamable`], coalescing the writes to the
+    /// underlying [`AsyncWrite`] in a buffer of `capacity` bytes.
+    ///
+    /// The archive writes many small buffers, e.g. the headers and descriptors of tiny entries: they are written
+    /// downstream once the buffer is full, which reduces the syscalls and the small packets of a socket. The
+    /// buffer is flushed by [`Self::finalize`] or [`Self::retrieve_writer`].
+    pub fn new_streamable_buffered(sink: W, capacity: usize) -> Self {
+        let mut data = SubZipArchiveData::default();
+        data.base_flags = EXTENDED_LOCAL_HEADER_FLAG;
+        Self {
+            sink: Box::new(BufferedSink::new(sink, capacity)),
+            data,
+        }
+    }
+
     /// Create a new zip archive (non streamable), using the underlying [`AsyncWrite`] + [`AsyncSeek`] to
     /// write files' header and payload.
     ///
-    /// _Note:_ a non streamable archive save few bytes per files.
+    /// _Note:_ a non streamable archive save few bytes per files (the 16 bytes data descriptor).
     pub fn new<S: AsyncWrite + AsyncSeek + Unpin + 'a>(sink: S) -> ZipArchive<'a, W>
     where
         AsyncWriteSeekWrapper<S>: CommonWrapper<W>,
@@ -59,20 +95,24 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
         }
     }
 
+    /// The offset where the next entry's local header will be written, e.g. to build an external index of the
+    /// entries while appending them.
+    ///
+    /// It is the size of the archive so far, the entries' headers and payloads.
+    pub fn current_offset(&self) -> u64 {
+        self.data.archive_size
+    }
+
     /// Get archive current total bytes written.
-    pub fn get_archive_size(&mut self) -> u64 {
-        match self.sink.get_written_bytes_count() {
-            Ok(val) => val,
-            Err(e) => {
-                eprintln!("Error: {:?}", e);
-                0
-            }
-        }
+    pub fn get_archive_size(&mut self) -> Result<u64, ArchiveError> {
+        Ok(self.sink.get_written_bytes_count()?)
     }
 
-    /// Get back archive writer.
-    pub fn retrieve_writer(self) -> W {
-        self.sink.get_into()
+    /// Get back archive writer, the bytes still buffered by [`Self::new_streamable_buffered`] are flushed to it.
+    pub async fn retrieve_writer(mut self) -> Result<W, ArchiveError> {
+        self.sink.finalizing();
+        self.sink.flush().await?;
+        Ok(self.sink.get_into())
     }
 
     /// Append a new entity to the archive using the provided name, options and payload as [`AsyncRead`] object to
@@ -88,14 +128,84 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
         file_name: &str,
         options: &FileOptions<'a>,
         payload: &mut R,
-    ) -> Result<(), ArchiveError>
+    ) -> Result<AppendOutcome, ArchiveError>
     where
         W: AsyncWrite + Unpin,
         R: AsyncRead + Unpin,
     {
         let file_header_offset = self.data.archive_size;
-        let mut hasher = Hasher::new();
-        let compressor = options.compression_method;
+        let deadline = options.time_budget.map(|budget| Instant::now() + budget);
+        let compressor = self.data.compressor(file_name, options);
+
+        //peek at the payload, so the compression method can be adapted before the header is written
+        let head_size = head_size(compressor, options);
+        let mut head = Vec::with_capacity(head_size);
+        (&mut *payload)
+            .take(head_size as u64)
+            .read_to_end(&mut head)
+            .await?;
+        let compressor = adapt_compressor(
+            compressor,
+            options,
+            self.data.compression_level(options),
+            &head,
+        )
+        .await?;
+
+        //a streamed entry can't be rolled back, its budget is only checked before the compression starts
+        let streaming = is_streaming(self.data.base_flags);
+        let compressor = match deadline {
+            Some(deadline) if streaming && Instant::now() >= deadline => CompressionMethod::Store(),
+            _ => compressor,
+        };
+
+        let mut payload = BudgetReader::new(
+            head.as_slice().chain(payload),
+            deadline.filter(|_| !streaming),
+        );
+        match self
+            .append_payload(file_name, options, compressor, &mut payload)
+            .await
+        {
+            Err(error) if payload.timed_out(&error) => {
+                //the entry is rolled back and appended again, stored
+                let written_end = self.data.archive_size;
+                self.seek_sink(file_header_offset).await?;
+                self.data.archive_size = file_header_offset;
+
+                let (consumed, rest) = payload.into_parts();
+                let mut replay = Cursor::new(consumed).chain(rest);
+                let outcome = self
+                    .append_payload(file_name, options, CompressionMethod::Store(), &mut replay)
+                    .await?;
+
+                if self.data.archive_size < written_end {
+                    //the compressed bytes past the stored entry can't be truncated
+                    self.data.pop_archive_file_entry();
+                    self.seek_sink(written_end).await?;
+                    self.data.archive_size = written_end;
+                    return Err(ArchiveError::InconsistentArchive(rollback_overrun()));
+                }
+                Ok(outcome)
+            }
+            outcome => outcome,
+        }
+    }
+
+    /// Write the entry, its header and its payload compressed with the method.
+    async fn append_payload<P>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compressor: CompressionMethod,
+        payload: &mut P,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        P: AsyncRead + Unpin,
+    {
+        let file_header_offset = self.data.archive_size;
+        let mut hasher = EntryHasher::new(options);
 
         let (file_header, mut archive_file_entry) = build_file_header(
             file_name,
@@ -104,45 +214,57 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
             file_header_offset,
             &self.data,
             false,
-        );
+        )?;
 
         self.sink.write_all(file_header.buffer()).await?;
 
-        let file_begin = self.sink.stream_position().await?;
+        let file_begin = self.sink.get_written_bytes_count()?;
 
-        let (uncompressed_size, is_text) = compress(
+        let (uncompressed_size, is_text) = match compress(
             compressor,
             &mut self.sink,
             payload,
             &mut hasher,
-            Level::Default,
+            self.data.compression_level(options),
         )
-        .await?;
+        .await
+        {
+            Ok(sizes) => sizes,
+            Err(error) => {
+                //the partial entry is left out of the central directory, the next one is written after it
+                self.data.archive_size = self.sink.get_written_bytes_count()?;
+                return Err(error);
+            }
+        };
 
-        let archive_size = self.sink.stream_position().await?;
+        let archive_size = self.sink.get_written_bytes_count()?;
         let compressed_size = archive_size - file_begin;
 
-        let crc32 = hasher.finalize();
+        let (crc32, digest) = match hasher.finalize() {
+            Ok(hashes) => hashes,
+            Err(error) => {
+                //the entry is left out of the central directory, the next one is written after it
+                self.data.archive_size = archive_size;
+                return Err(error);
+            }
+        };
         archive_file_entry.crc32 = crc32;
         archive_file_entry.compressed_size = compressed_size;
         archive_file_entry.uncompressed_size = uncompressed_size;
         archive_file_entry.apparently_text_file(is_text);
 
         if is_streaming(archive_file_entry.general_purpose_flags) {
-            let data_descriptor = build_data_descriptor(&archive_file_entry);
+            let data_descriptor = build_data_descriptor(&archive_file_entry, options);
             self.sink.write_all(data_descriptor.buffer()).await?;
         } else {
             let sizes_update = build_file_sizes_update(&archive_file_entry);
 
-            //position in the the file header
-            self.sink
-                .seek(SeekFrom::Start(file_header_offset + FILE_HEADER_CRC_OFFSET))
-                .await?;
-
-            self.sink.write_all(sizes_update.buffer()).await?;
-
-            //position back at the end
-            self.sink.seek(SeekFrom::Start(archive_size)).await?;
+            self.patch_sink(
+                file_header_offset + FILE_HEADER_CRC_OFFSET,
+                sizes_update.buffer(),
+                archive_size,
+            )
+            .await?;
 
             /*             if let Some(zip64_extra_field_arc) = extrafield_zip64_arc {
             let mut file_descriptor = ArchiveDescriptor::new(30);
@@ -151,7 +273,7 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
             zip64_extra_field
                 .local_header_write_data(&mut file_descriptor, &archive_file_entry); */
 
-            if archive_file_entry.is_zip64() {
+            if archive_file_entry.needs_zip64_local() {
                 if archive_file_entry.has_zip64_extra_field() {
                     let mut file_descriptor = ArchiveDescriptor::new(30);
 
@@ -160,16 +282,18 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
                     zip64_extra_field
                         .local_header_write_data(&mut file_descriptor, &archive_file_entry);
 
-                    self.sink
-                        .seek(SeekFrom::Start(file_header_offset + FILE_HEADER_BASE_SIZE))
-                        .await?;
-
-                    self.sink.write_all(file_descriptor.buffer()).await?;
-                    //position back at the end
-                    self.sink.seek(SeekFrom::Start(archive_size)).await?;
+                    //the zip64 extra field is the first one, right after the file name
+                    self.patch_sink(
+                        file_header_offset
+                            + FILE_HEADER_BASE_SIZE
+                            + archive_file_entry.file_name_len as u64,
+                        file_descriptor.buffer(),
+                        archive_size,
+                    )
+                    .await?;
                 } else {
                     //it wasn't identified as zip64 from option, but it can be as stream
-                    let data_descriptor = build_data_descriptor(&archive_file_entry);
+                    let data_descriptor = build_data_descriptor(&archive_file_entry, options);
                     self.sink.write_all(data_descriptor.buffer()).await?;
                 }
             }
@@ -177,103 +301,1226 @@ impl<'a, W: AsyncWrite + Unpin + Send + 'a> ZipArchive<'a, W> {
 
         archive_file_entry.need_to_add_zip64_extra_field();
 
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            digest,
+            index: self.data.entry_count(),
+        };
+
         self.data.add_archive_file_entry(archive_file_entry);
 
         self.data.archive_size = self.sink.get_written_bytes_count()?;
 
-        Ok(())
+        Ok(outcome)
     }
 
-    /// Append a directory entry to the archive.
+    /// Append a new entity to the archive like [`Self::append`], unless the token is cancelled.
     ///
+    /// The token is checked each time a chunk of the payload is read. Once it is cancelled, the append stops with
+    /// [`ArchiveError::Cancelled`]: the partially written entry is left out of the central directory, so the
+    /// archive can still be finalized with the previous entries, or simply dropped.
     ///
-    pub async fn append_directory(
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The [`AsyncRead`] entity to be archived
+    /// * `token` - The token cancelling the append
+    ///
+    /// # Features
+    ///
+    /// Requires `cancellation` feature
+    #[cfg(feature = "cancellation")]
+    pub async fn append_cancellable<R>(
         &mut self,
         file_name: &str,
         options: &FileOptions<'a>,
-    ) -> Result<(), ArchiveError>
+        payload: &mut R,
+        token: CancellationToken,
+    ) -> Result<AppendOutcome, ArchiveError>
     where
         W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
     {
-        let file_header_offset = self.data.archive_size;
-        let compressor = CompressionMethod::Store();
-
-        //ensure that the name end with a slash ('/')
-        let new_file_name = match file_name.chars().last() {
-            Some('/') | Some('\\') => file_name.to_owned(),
-            _ => {
-                let mut s = file_name.to_owned();
-                s.push('/');
-                s
-            }
+        let mut payload = CancellableReader {
+            reader: payload,
+            token: &token,
         };
 
-        let (file_header, mut archive_file_entry) = build_file_header(
-            &new_file_name,
-            options,
-            compressor,
-            file_header_offset,
-            &self.data,
-            true,
-        );
-
-        //Since no payload, there is no need for data descriptor
-        archive_file_entry.general_purpose_flags &= !EXTENDED_LOCAL_HEADER_FLAG;
+        match self.append(file_name, options, &mut payload).await {
+            Err(_) if token.is_cancelled() => Err(ArchiveError::Cancelled),
+            result => result,
+        }
+    }
 
-        self.sink.write_all(file_header.buffer()).await?;
+    /// Append several entities to the archive, sharing the same options.
+    ///
+    /// Each entry is appended with [`Self::append`], in the iterator order. It stops at the first error.
+    ///
+    /// # Arguments
+    /// * `entries` - The entries' name and payload as an [`AsyncRead`]
+    /// * `options` - The options applied to every entry
+    ///
+    pub async fn append_all<'r, I>(
+        &mut self,
+        entries: I,
+        options: &FileOptions<'a>,
+    ) -> Result<Vec<AppendOutcome>, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        I: IntoIterator<Item = (String, Box<dyn AsyncRead + Unpin + Send + 'r>)>,
+    {
+        let mut outcomes = Vec::new();
+        for (file_name, mut payload) in entries {
+            outcomes.push(self.append(&file_name, options, &mut payload).await?);
+        }
+        Ok(outcomes)
+    }
 
-        self.data.add_archive_file_entry(archive_file_entry);
+    /// Append a new entity to the archive, taking the payload by value.
+    ///
+    /// It is [`Self::append`] for a payload built inline, e.g. a `Box<dyn AsyncRead>` or a reader produced by an
+    /// iterator, which then doesn't need to be bound to a mutable variable first.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The [`AsyncRead`] entity to be archived, dropped once appended
+    ///
+    pub async fn append_reader_owned<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        mut payload: R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+    {
+        self.append(file_name, options, &mut payload).await
+    }
 
-        self.data.archive_size = self.sink.get_written_bytes_count()?;
+    /// Append a new entity to the archive, from a payload whose length is known through [`AsyncSeek`].
+    ///
+    /// The payload's remaining length (from its current position) is read upfront. A small payload is read in
+    /// memory and appended with [`Self::append_bytes`], so the local header is complete: no patch nor data
+    /// descriptor is needed. A larger one is appended with [`Self::append`], with the `large_file` option set if
+    /// its sizes might not fit on 32 bits.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The entity's payload, it is left at its end
+    ///
+    pub async fn append_seekable<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        payload: &mut R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        let start = payload.stream_position().await?;
+        let end = payload.seek(SeekFrom::End(0)).await?;
+        payload.seek(SeekFrom::Start(start)).await?;
+        let size = end.saturating_sub(start);
 
-        Ok(())
+        if size <= IN_MEMORY_MAX_SIZE {
+            let mut bytes = Vec::with_capacity(size as usize);
+            payload.read_to_end(&mut bytes).await?;
+            self.append_bytes(file_name, options, &bytes).await
+        } else {
+            let options = options
+                .clone()
+                .large_file(options.large_file || might_need_zip64(size));
+            self.append(file_name, &options, payload).await
+        }
     }
 
-    /// Finalize the archive by writing the necessary metadata to the end of the archive.
+    /// Append a new entity to the archive, from a payload whose length is declared upfront, e.g. an upload's
+    /// content length.
     ///
-    /// Returns the archive size (bytes) and the [AsyncWrite] object passed at creation.
-    pub async fn finalize(mut self) -> Result<(u64, W), ArchiveError>
+    /// The payload must hold exactly `declared_len` bytes: it is read up to this length then checked for a
+    /// trailing byte. A shorter or longer payload fails the append with [`ArchiveError::LengthMismatch`], telling
+    /// the actual length and the CRC-32 of the bytes read. A longer payload is read to its end to tell its length.
+    /// As for any failed append, the partially written entry is left out of the central directory.
+    ///
+    /// The `large_file` option is set if the sizes might not fit on 32 bits.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `declared_len` - The payload's declared length (bytes)
+    /// * `payload` - The entity's payload as a [`AsyncRead`]
+    ///
+    pub async fn append_sized<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        declared_len: u64,
+        payload: &mut R,
+    ) -> Result<AppendOutcome, ArchiveError>
     where
         W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
     {
-        let central_directory_offset = self.sink.get_written_bytes_count()?;
-/*         println!(
-            "central_directory_offset  {:?}  {:0X}",
-            central_directory_offset, central_directory_offset
-        ); */
-        let mut central_directory_header = ArchiveDescriptor::new(500);
-
-        for file_info in self.data.iter() {
-            build_central_directory_file_header(&mut central_directory_header, file_info);
+        let options = options
+            .clone()
+            .large_file(options.large_file || might_need_zip64(declared_len));
+        let mut sized = SizedReader {
+            reader: payload,
+            length: DeclaredLength::new(declared_len),
+        };
+        let result = if self.data.inlines_store_size(&options, declared_len) {
+            let mut bytes = Vec::with_capacity(declared_len as usize);
+            match sized.read_to_end(&mut bytes).await {
+                Ok(_) => self.append_bytes(file_name, &options, &bytes).await,
+                Err(error) => Err(error.into()),
+            }
+        } else {
+            self.append(file_name, &options, &mut sized).await
+        };
 
-            self.sink
-                .write_all(central_directory_header.buffer())
-                .await?;
-            central_directory_header.clear();
+        let extra = if sized.length.is_overrun() {
+            tokio::io::copy(sized.reader, &mut tokio::io::sink()).await?
+        } else {
+            0
+        };
+        match sized.length.mismatch(extra) {
+            Some(mismatch) => Err(mismatch),
+            None => result,
         }
+    }
 
-        let current_archive_size = self.sink.get_written_bytes_count()?;
-        let central_directory_size = current_archive_size - central_directory_offset;
+    /// Append a new entity to the archive, reading the payload from the file at the given path.
+    ///
+    /// With [`CompressionMethod::Auto`], the compression method is selected from the entry name's extension,
+    /// so already compressed files are stored. The entry takes the file's modification time, like
+    /// [`Self::append_file`].
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `path` - The path of the file to be archived
+    ///
+    pub async fn append_path<P>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        path: P,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path).await?;
+        self.append_file(file_name, options, &mut file).await
+    }
 
-        let end_of_central_directory = build_central_directory_end(
-            &mut self.data,
-            central_directory_offset,
-            central_directory_size,
-        );
+    /// Append a new entity to the archive, reading the payload from an opened file.
+    ///
+    /// Unless the options set another modification time than the default one, the entry takes the file's
+    /// modification time.
+    /// On Unix, unless the options set the permissions, the entry keeps the file's execute bits, e.g. a `0o755`
+    /// script stays runnable once extracted.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `file` - The file to be archived
+    ///
+    pub async fn append_file(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        file: &mut File,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let options = options.with_file_metadata(file.metadata().await);
+        self.append(file_name, &options, file).await
+    }
 
-        self.sink
-            .write_all(end_of_central_directory.buffer())
-            .await?;
+    /// Append a new entity to the archive, decompressing the provided gzip stream on the fly.
+    ///
+    /// The entry holds the decompressed content. A stream of concatenated gzip members (e.g. appended log
+    /// files) is decompressed as a whole.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `gz_reader` - The gzip compressed payload
+    ///
+    /// # Features
+    ///
+    /// Requires `deflate` feature
+    #[cfg(feature = "deflate")]
+    pub async fn append_gz_decompressed<R>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        gz_reader: R,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+    {
+        let mut decoder = GzipDecoder::new(BufReader::new(gz_reader));
+        decoder.multiple_members(true);
+        self.append(file_name, options, &mut decoder).await
+    }
 
-        self.sink.flush().await?;
+    /// Append a new entity to the archive like [`Self::append`], writing a copy of its uncompressed payload to
+    /// `capture` at the same time.
+    ///
+    /// The payload is read once: each chunk is written to `capture` before being compressed. If the append fails,
+    /// `capture` holds the payload read so far.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `payload` - The entity's payload as a [`AsyncRead`]
+    /// * `capture` - The [`AsyncWrite`] receiving the uncompressed payload
+    ///
+    pub async fn append_and_capture<R, C>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        payload: &mut R,
+        capture: &mut C,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        R: AsyncRead + Unpin,
+        C: AsyncWrite + Unpin,
+    {
+        let mut payload = CaptureReader {
+            reader: payload,
+            capture,
+            pending: Vec::new(),
+        };
+        let outcome = self.append(file_name, options, &mut payload).await?;
+        payload.capture.flush().await?;
+        Ok(outcome)
+    }
 
-        self.data.archive_size = self.sink.get_written_bytes_count()?;
+    /// Append a new entity to the archive using the provided name, options and in-memory payload.
+    ///
+    /// The payload is compressed before anything is written, so the local header is written with the final
+    /// CRC-32 and sizes: the archive doesn't need to seek back and no data descriptor is needed, even for a
+    /// streamable archive.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options
+    /// * `bytes` - The entity's payload
+    ///
+    pub async fn append_bytes(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        bytes: &[u8],
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut buffer = Vec::new();
+        let (compressor, outcome, is_text, compressed) = self
+            .compress_bytes(file_name, options, bytes, &mut buffer)
+            .await?;
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compressor,
+            &outcome,
+            is_text,
+            [compressed],
+        )
+        .await?;
 
-        Ok((self.data.archive_size, self.sink.get_into()))
+        Ok(outcome)
     }
 
-    ///Set the archive comment
-    pub fn set_archive_comment(&mut self, comment: &str) {
-        self.data.set_archive_comment(comment);
+    /// Append the same in-memory payload under several names, compressing it once.
+    ///
+    /// The payload is compressed as by [`Self::append_bytes`], the compression method being chosen for the first
+    /// name, then its compressed bytes are written for each name, as by [`Self::append_raw`]. ZIP has no aliasing:
+    /// each name gets its own entry, with a distinct local header and its own copy of the compressed bytes, and
+    /// extractors create a file per name, e.g. `index.html` and `default.html` with identical content.
+    ///
+    /// It returns the outcome of each entry, in the order of the names. Without any name, nothing is written.
+    ///
+    /// # Arguments
+    /// * `names` - The names of the archive entries
+    /// * `options` - Entries' archive options
+    /// * `bytes` - The entities' payload
+    ///
+    pub async fn append_bytes_multi(
+        &mut self,
+        names: &[&str],
+        options: &FileOptions<'a>,
+        bytes: &[u8],
+    ) -> Result<Vec<AppendOutcome>, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let Some(first_name) = names.first() else {
+            return Ok(Vec::new());
+        };
+
+        let mut buffer = Vec::new();
+        let (compressor, mut outcome, is_text, compressed) = self
+            .compress_bytes(first_name, options, bytes, &mut buffer)
+            .await?;
+
+        let mut outcomes = Vec::with_capacity(names.len());
+        for file_name in names {
+            outcome.index = self.data.entry_count();
+            self.write_complete_entry(
+                file_name,
+                options,
+                compressor,
+                &outcome,
+                is_text,
+                [compressed],
+            )
+            .await?;
+            outcomes.push(outcome.clone());
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Compress an in-memory payload, returns the compression method, the entry's outcome, if the payload is
+    /// text, and the compressed bytes: the payload itself if it is stored, the buffer otherwise.
+    async fn compress_bytes<'b>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        bytes: &'b [u8],
+        buffer: &'b mut Vec<u8>,
+    ) -> Result<(CompressionMethod, AppendOutcome, bool, &'b [u8]), ArchiveError> {
+        let mut hasher = EntryHasher::new(options);
+        hasher.check_entry_size(bytes.len() as u64)?;
+        let compressor = adapt_compressor(
+            self.data.compressor(file_name, options),
+            options,
+            self.data.compression_level(options),
+            bytes,
+        )
+        .await?;
+
+        let (uncompressed_size, is_text, compressed) = if compressor == CompressionMethod::Store() {
+            //no encoder nor copy needed for a stored payload
+            hasher.update(bytes);
+            let sample = &bytes[..bytes.len().min(BUFFER_SIZE)];
+            (bytes.len() as u64, hasher.is_text(sample), bytes)
+        } else {
+            let (uncompressed_size, is_text) = compress(
+                compressor,
+                buffer,
+                &mut { bytes },
+                &mut hasher,
+                self.data.compression_level(options),
+            )
+            .await?;
+            (uncompressed_size, is_text, buffer.as_slice())
+        };
+
+        let (crc32, digest) = hasher.finalize()?;
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size: compressed.len() as u64,
+            uncompressed_size,
+            digest,
+            index: self.data.entry_count(),
+        };
+
+        Ok((compressor, outcome, is_text, compressed))
+    }
+
+    /// Append a new entity to the archive from its payload already compressed, written verbatim.
+    ///
+    /// The payload must have been compressed with `compression_method`, `crc32` and `uncompressed_size` describing
+    /// the uncompressed payload: they are recorded as is, without any check. As the CRC-32 and sizes are known, the
+    /// local header is complete and no data descriptor is needed, even for a streamable archive.
+    ///
+    /// It allows to reuse the compressed bytes of identical entries, e.g. those reported by
+    /// [`Self::report_duplicates`]. ZIP has no aliasing: each entry holds its own copy of the bytes, and
+    /// extractors create a file per entry.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options, its compression method is ignored
+    /// * `compression_method` - The method the payload was compressed with
+    /// * `crc32` - The CRC-32 of the uncompressed payload
+    /// * `uncompressed_size` - The size of the uncompressed payload
+    /// * `compressed` - The compressed payload
+    ///
+    pub async fn append_raw(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compression_method: CompressionMethod,
+        crc32: u32,
+        uncompressed_size: u64,
+        compressed: &[u8],
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size: compressed.len() as u64,
+            uncompressed_size,
+            digest: None,
+            index: self.data.entry_count(),
+        };
+
+        //only a stored payload can be checked for text
+        let sample = if compression_method == CompressionMethod::Store() {
+            &compressed[..compressed.len().min(BUFFER_SIZE)]
+        } else {
+            &[]
+        };
+        let is_text = EntryHasher::new(options).is_text(sample);
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compression_method,
+            &outcome,
+            is_text,
+            [compressed],
+        )
+        .await?;
+
+        Ok(outcome)
+    }
+
+    /// Append a new entity to the archive from its payload already compressed, supplied as frames written verbatim
+    /// one after the other, e.g. the zstd or xz frames of a separate compressor.
+    ///
+    /// It generalizes [`Self::append_raw`] to a compressed payload arriving piecemeal. As for it, the CRC-32 and
+    /// sizes describe the whole payload and are recorded as is, the local header is complete and no data
+    /// descriptor is needed. If the frames' total length isn't `compressed_size`, it returns
+    /// [`ArchiveError::LengthMismatch`] with the frames' length, and the entry is left out of the central
+    /// directory.
+    ///
+    /// # Arguments
+    /// * `file_name` - A for the name of the archive entry
+    /// * `options` - Entry's archive options, its compression method is ignored
+    /// * `compression_method` - The method the frames were compressed with
+    /// * `crc32` - The CRC-32 of the uncompressed payload
+    /// * `uncompressed_size` - The size of the uncompressed payload
+    /// * `compressed_size` - The total length of the frames
+    /// * `frames` - The compressed payload's frames
+    ///
+    #[allow(clippy::too_many_arguments)]
+    pub async fn append_precompressed_frames<'f, I>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compression_method: CompressionMethod,
+        crc32: u32,
+        uncompressed_size: u64,
+        compressed_size: u64,
+        frames: I,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        I: IntoIterator<Item = &'f [u8]>,
+    {
+        let outcome = AppendOutcome {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            digest: None,
+            index: self.data.entry_count(),
+        };
+
+        //only a stored payload can be checked for text, from its first frame
+        let mut frames = frames.into_iter().peekable();
+        let sample = match frames.peek() {
+            Some(frame) if compression_method == CompressionMethod::Store() => {
+                &frame[..frame.len().min(BUFFER_SIZE)]
+            }
+            _ => &[],
+        };
+        let is_text = EntryHasher::new(options).is_text(sample);
+
+        self.write_complete_entry(
+            file_name,
+            options,
+            compression_method,
+            &outcome,
+            is_text,
+            frames,
+        )
+        .await?;
+
+        Ok(outcome)
+    }
+
+    /// Write an entry whose payload is already compressed, with its complete local header.
+    ///
+    /// The frames are written in order, their total length must be the outcome's compressed size: otherwise, the
+    /// local header is wrong and the entry is left out of the central directory.
+    async fn write_complete_entry<'f, I>(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+        compressor: CompressionMethod,
+        outcome: &AppendOutcome,
+        is_text: bool,
+        frames: I,
+    ) -> Result<(), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        I: IntoIterator<Item = &'f [u8]>,
+    {
+        let (file_header, archive_file_entry) = build_complete_file_header(
+            file_name,
+            options,
+            compressor,
+            self.data.archive_size,
+            &self.data,
+            outcome,
+            is_text,
+        )?;
+
+        self.sink.write_all(file_header.buffer()).await?;
+
+        let mut written = 0;
+        for frame in frames {
+            self.sink.write_all(frame).await?;
+            written += frame.len() as u64;
+        }
+
+        if written != outcome.compressed_size {
+            self.data.archive_size = self.sink.get_written_bytes_count()?;
+            return Err(ArchiveError::LengthMismatch {
+                expected: outcome.compressed_size,
+                actual: written,
+                crc32: outcome.crc32,
+            });
+        }
+
+        self.data.add_archive_file_entry(archive_file_entry);
+        self.data.archive_size = self.sink.get_written_bytes_count()?;
+
+        Ok(())
+    }
+
+    /// Append a text listing of all the entries appended so far.
+    ///
+    /// Each line holds the entry name, its uncompressed size and its CRC-32 (in hexadecimal), separated by tabs.
+    /// It is meant to be called after all the other appends and before the archive is finalized.
+    ///
+    /// # Arguments
+    /// * `file_name` - The name of the listing entry, e.g. `MANIFEST`
+    /// * `options` - Entry's archive options
+    ///
+    pub async fn append_manifest(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+    ) -> Result<AppendOutcome, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let manifest = self.data.build_manifest();
+        self.append_bytes(file_name, options, manifest.as_bytes())
+            .await
+    }
+
+    /// Append an in-memory tree where the keys are the entries' paths and the values their payload.
+    ///
+    /// The entries are appended in the map's (sorted) order, so the archive is reproducible. A directory entry
+    /// is appended for each path prefix before its first entry. A key ending with a slash (`/`) is appended as
+    /// a directory, its value is ignored.
+    pub async fn append_virtual_tree(
+        &mut self,
+        tree: &BTreeMap<String, Vec<u8>>,
+        options: &FileOptions<'a>,
+    ) -> Result<(), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut directories = BTreeSet::new();
+
+        for (path, bytes) in tree {
+            //the prefixes, including the path itself if it is a directory
+            for (index, _) in path.match_indices('/') {
+                let directory = &path[..=index];
+                if directories.insert(directory.to_owned()) {
+                    self.append_directory(directory, options).await?;
+                }
+            }
+
+            if !path.ends_with('/') {
+                self.append_bytes(path, options, bytes).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Append a directory entry to the archive.
+    ///
+    /// The directory honors the entries' metadata options as a file does: its modification time, extended
+    /// timestamp, comment, permissions and owner. It has no payload, so neither a compression method nor a data
+    /// descriptor.
+    pub async fn append_directory(
+        &mut self,
+        file_name: &str,
+        options: &FileOptions<'a>,
+    ) -> Result<(), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let file_header_offset = self.data.archive_size;
+        let compressor = CompressionMethod::Store();
+
+        //ensure that the name end with a slash ('/')
+        let new_file_name = match file_name.chars().last() {
+            Some('/') | Some('\\') => file_name.to_owned(),
+            _ => {
+                let mut s = file_name.to_owned();
+                s.push('/');
+                s
+            }
+        };
+
+        let (file_header, mut archive_file_entry) = build_file_header(
+            &new_file_name,
+            options,
+            compressor,
+            file_header_offset,
+            &self.data,
+            true,
+        )?;
+
+        //Since no payload, there is no need for data descriptor
+        archive_file_entry.general_purpose_flags &= !EXTENDED_LOCAL_HEADER_FLAG;
+
+        self.sink.write_all(file_header.buffer()).await?;
+
+        self.data.add_archive_file_entry(archive_file_entry);
+
+        self.data.archive_size = self.sink.get_written_bytes_count()?;
+
+        Ok(())
+    }
+
+    /// Finalize the archive by writing the necessary metadata to the end of the archive.
+    ///
+    /// The central directory is written to the sink entry by entry, through a single reused buffer. The memory
+    /// used doesn't depend on the central directory size, only the appended entries' metadata is held until
+    /// then: a couple hundred bytes per entry, plus its name, comment and extra fields.
+    ///
+    /// Returns the archive size (bytes) and the [AsyncWrite] object passed at creation.
+    pub async fn finalize(self) -> Result<(u64, W), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.finalize_keep_writer().await
+    }
+
+    /// Finalize the archive like [`finalize`](Self::finalize()), for formats layered on ZIP.
+    ///
+    /// The returned [AsyncWrite] object is flushed, not shut down, and positioned right after the
+    /// end of central directory record, so the caller may append arbitrary trailing data
+    /// (e.g. a signature block).
+    ///
+    /// Returns the archive size (bytes) and the [AsyncWrite] object passed at creation.
+    pub async fn finalize_keep_writer(self) -> Result<(u64, W), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let report = self.finalize_report().await?;
+        Ok((report.size, report.writer))
+    }
+
+    /// Finalize the archive like [`finalize_keep_writer`](Self::finalize_keep_writer()).
+    ///
+    /// Returns a [`FinalizeReport`] with the archive size (bytes), the [AsyncWrite] object passed at creation, the
+    /// number of entries and whether the zip64 format extensions were needed.
+    pub async fn finalize_report(self) -> Result<FinalizeReport<W>, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.finalize_report_with_progress(|_, _| {}).await
+    }
+
+    /// Finalize the archive like [`finalize`](Self::finalize()), reporting the progress of the central directory
+    /// write.
+    ///
+    /// With a huge number of entries, writing the central directory takes noticeable time. The callback is
+    /// invoked once each entry's central directory header is written, with the entry index and the number of
+    /// central directory bytes written so far.
+    ///
+    /// Returns the archive size (bytes) and the [AsyncWrite] object passed at creation.
+    pub async fn finalize_with_progress<F>(self, progress: F) -> Result<(u64, W), ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(usize, u64),
+    {
+        let report = self.finalize_report_with_progress(progress).await?;
+        Ok((report.size, report.writer))
+    }
+
+    async fn finalize_report_with_progress<F>(
+        mut self,
+        mut progress: F,
+    ) -> Result<FinalizeReport<W>, ArchiveError>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(usize, u64),
+    {
+        let central_directory_offset = self.sink.get_written_bytes_count()?;
+        /*         println!(
+            "central_directory_offset  {:?}  {:0X}",
+            central_directory_offset, central_directory_offset
+        ); */
+        let mut central_directory_header = ArchiveDescriptor::new(500);
+
+        if self.data.sort_central_directory {
+            self.data.sort_by_name();
+        }
+
+        let mut central_directory_written = 0;
+        for (index, file_info) in self.data.iter().enumerate() {
+            build_central_directory_file_header(&mut central_directory_header, file_info);
+
+            self.sink
+                .write_all(central_directory_header.buffer())
+                .await?;
+            central_directory_written += central_directory_header.len() as u64;
+            progress(index, central_directory_written);
+            central_directory_header.clear();
+        }
+
+        let current_archive_size = self.sink.get_written_bytes_count()?;
+        let central_directory_size = current_archive_size
+            .checked_sub(central_directory_offset)
+            .ok_or_else(|| {
+                ArchiveError::BadArchiveStructure(format!(
+                    "Central directory offset {} is past the end of the archive {}",
+                    central_directory_offset, current_archive_size
+                ))
+            })?;
+
+        if self.data.archive_digest {
+            self.data.reserve_archive_digest()?;
+        }
+
+        let end_of_central_directory = build_central_directory_end(
+            &mut self.data,
+            central_directory_offset,
+            central_directory_size,
+        )?;
+
+        if self.data.archive_digest {
+            //the reserved suffix is written once the preceding bytes are hashed
+            let buffer = end_of_central_directory.buffer();
+            let digested = &buffer[..buffer.len() - ARCHIVE_DIGEST_SUFFIX_SIZE];
+            self.sink.write_all(digested).await?;
+
+            let crc32 = self.sink.written_crc32().ok_or_else(|| {
+                ArchiveError::Unsupported(
+                    "The archive digest requires a streamable archive".to_owned(),
+                )
+            })?;
+            self.sink
+                .write_all(build_archive_digest_suffix(crc32).as_bytes())
+                .await?;
+        } else {
+            self.sink
+                .write_all(end_of_central_directory.buffer())
+                .await?;
+        }
+
+        self.data.archive_size = self.sink.get_written_bytes_count()?;
+
+        //make sure the writer is left right after the end of central directory
+        if let Some(sink) = self.sink.seekable() {
+            sink.seek(SeekFrom::Start(self.data.archive_size)).await?;
+        }
+        self.sink.finalizing();
+        self.sink.flush().await?;
+
+        Ok(FinalizeReport {
+            size: self.data.archive_size,
+            entry_count: self.data.entry_count(),
+            used_zip64: self.data.used_zip64(),
+            writer: self.sink.get_into(),
+        })
+    }
+
+    /// Seek the sink at the given position, to patch an entry's local header.
+    ///
+    /// Only the non streamable archive's sink seeks: a streamable archive never patches what it wrote.
+    async fn seek_sink(&mut self, position: u64) -> Result<(), ArchiveError> {
+        match self.sink.seekable() {
+            Some(sink) => {
+                sink.seek(SeekFrom::Start(position)).await?;
+                Ok(())
+            }
+            None => Err(ArchiveError::Unsupported(
+                "A streamable archive can't seek".to_owned(),
+            )),
+        }
+    }
+
+    /// Overwrite the bytes at the position, to patch an entry's local header, then seek back at the end.
+    ///
+    /// If a seek or the write fails, the sink is sought back at the end on a best effort basis, so the next
+    /// entries aren't written over this one, and [`ArchiveError::InconsistentArchive`] is returned.
+    async fn patch_sink(
+        &mut self,
+        position: u64,
+        bytes: &[u8],
+        end: u64,
+    ) -> Result<(), ArchiveError> {
+        let mut patched = self.seek_sink(position).await;
+        if patched.is_ok() {
+            patched = self.sink.write_all(bytes).await.map_err(ArchiveError::from);
+        }
+        if patched.is_ok() {
+            patched = self.seek_sink(end).await;
+        }
+
+        match patched {
+            Err(ArchiveError::IoError(error)) => {
+                let _ = self.seek_sink(end).await;
+                self.data.archive_size = end;
+                Err(ArchiveError::InconsistentArchive(error))
+            }
+            patched => patched,
+        }
+    }
+
+    /// Set whether the CRC-32 of the whole archive is embedded at the end of the archive comment.
+    ///
+    /// It is meant to check the archive integrity after transport. When the archive is finalized, the archive
+    /// comment is suffixed with `archflow-crc32:` followed by the CRC-32, as 8 lowercase hexadecimal digits. The
+    /// CRC-32 is computed over all the archive bytes before this 23 bytes suffix, which ends the archive. A
+    /// verifier checks that the archive ends with the prefix and a CRC-32 matching the preceding bytes.
+    ///
+    /// It requires a __streamable__ archive and must be set before the first entry is appended, otherwise an
+    /// [`ArchiveError::Unsupported`] is returned.
+    ///
+    /// The default value is `false`.
+    pub fn with_archive_digest(&mut self, digest: bool) -> Result<(), ArchiveError> {
+        if digest {
+            if self.data.archive_size != 0 {
+                return Err(ArchiveError::Unsupported(
+                    "The archive digest must be enabled before the first entry".to_owned(),
+                ));
+            }
+            if !self.sink.start_hashing() {
+                return Err(ArchiveError::Unsupported(
+                    "The archive digest requires a streamable archive".to_owned(),
+                ));
+            }
+        }
+        self.data.archive_digest = digest;
+        Ok(())
+    }
+
+    /// Set whether the appended entries with the same content as a previous one are reported.
+    ///
+    /// An entry is reported, in [`Self::duplicates`], if a previous entry has the same CRC-32, uncompressed size
+    /// and name stem (the base name without extension). Its compressed bytes could have been reused with
+    /// [`Self::append_raw`]. Empty entries are never reported. With the `log` feature, the duplicates are logged
+    /// at info level too.
+    ///
+    /// The default value is `false`.
+    pub fn report_duplicates(&mut self, report: bool) {
+        self.data.contents = if report { Some(HashMap::new()) } else { None };
+        self.data.duplicates.clear();
+    }
+
+    /// The duplicates reported since [`Self::report_duplicates`] was set, in the append order: each entry's name
+    /// with the name of the first entry with the same content.
+    pub fn duplicates(&self) -> &[(String, String)] {
+        &self.data.duplicates
+    }
+
+    /// Set whether the central directory lists the entries sorted by name, instead of the append order.
+    ///
+    /// The entries are sorted by their name's bytes when the archive is finalized. Only the central directory
+    /// is affected: the local headers and payloads stay in the append order, each central directory entry
+    /// pointing to its local header.
+    ///
+    /// The default value is `false`.
+    pub fn sort_central_directory_by_name(&mut self, sort: bool) {
+        self.data.sort_central_directory = sort;
+    }
+
+    /// Set the compression level of the entries appended afterwards whose [`FileOptions`] leave it at
+    /// `Level::Inherit`, their default.
+    ///
+    /// The default value is `Level::Default`.
+    pub fn default_compression_level(&mut self, level: Level) {
+        self.data.default_compression_level = level;
+    }
+
+    /// Set whether the zip64 format extensions are used even if the archive doesn't need them.
+    ///
+    /// If set to `true`, the zip64 end of central directory record and locator are always written, and every entry
+    /// appended afterwards stores its sizes in a zip64 extra field. This is meant for toolchains that mandate zip64.
+    ///
+    /// The default value is `false`.
+    pub fn force_zip64(&mut self, force: bool) {
+        self.data.force_zip64 = force;
+    }
+
+    /// Roll the last appended entry back, e.g. after a speculative append to try another compression method.
+    ///
+    /// The sink is truncated at the entry's local header, where the next entry is written: the bytes of a failed
+    /// append after it are dropped too. Only a non streamable archive can roll an entry back, its sink must
+    /// support the truncation, see [`SetLen`].
+    ///
+    /// Returns `false` if there is no entry to roll back.
+    pub async fn rollback_last_entry(&mut self) -> Result<bool, ArchiveError>
+    where
+        W: SetLen,
+    {
+        if is_streaming(self.data.base_flags) {
+            return Err(ArchiveError::Unsupported(
+                "A streamable archive can't roll an entry back".to_owned(),
+            ));
+        }
+
+        let offset = match self.data.pop_archive_file_entry() {
+            Some(archive_file_entry) => archive_file_entry.offset,
+            None => return Ok(false),
+        };
+
+        self.sink
+            .writer_mut()
+            .ok_or_else(|| {
+                ArchiveError::Unsupported("The buffered sink can't be truncated".to_owned())
+            })?
+            .set_len(offset)
+            .await?;
+        self.seek_sink(offset).await?;
+        self.data.archive_size = offset;
+
+        Ok(true)
+    }
+
+    /// Set the comment of an appended entry, identified by the [`AppendOutcome::index`] its append returned.
+    ///
+    /// The comment is written in the central directory, so it can be set until the archive is finalized. Returns
+    /// [`ArchiveError::EntryNotFound`] if no entry has this index, or [`ArchiveError::CommentTooLong`] if the
+    /// comment is longer than 0xFFFF bytes.
+    pub fn set_entry_comment(&mut self, index: usize, comment: &str) -> Result<(), ArchiveError> {
+        self.data.set_entry_comment(index, comment)
+    }
+
+    ///Set the archive comment
+    ///
+    /// The comment is silently truncated to 0xFFFF bytes, on a character boundary, use
+    /// [`Self::try_set_archive_comment`] to be notified instead.
+    ///
+    /// The archive comment is written as UTF-8 but, unlike the entries' names and comments, it can't be flagged
+    /// as such: the ZIP specification has no encoding flag for it. UTF-8 is the conventional encoding readers
+    /// assume.
+    pub fn set_archive_comment(&mut self, comment: &str) {
+        self.data.set_archive_comment(comment);
+    }
+
+    ///Set the archive comment
+    ///
+    /// Returns [`ArchiveError::CommentTooLong`] with the comment length in bytes if the comment is
+    /// longer than 0xFFFF bytes, in this case the archive comment is left unchanged.
+    pub fn try_set_archive_comment(&mut self, comment: &str) -> Result<(), ArchiveError> {
+        self.data.try_set_archive_comment(comment)
+    }
+
+    /// Set the archive comment to JSON metadata, e.g. tooling information, to be read back with
+    /// `ArchiveReader::metadata_json`.
+    ///
+    /// The JSON is written after the `archflow-json:` prefix, replacing the archive comment. Returns
+    /// [`ArchiveError::CommentTooLong`] if the prefixed JSON is longer than 0xFFFF bytes, in this case the archive
+    /// comment is left unchanged. The metadata is compatible with the archive digest, which is appended after it.
+    ///
+    /// # Features
+    ///
+    /// Requires `serde` feature
+    #[cfg(feature = "serde")]
+    pub fn set_metadata_json(&mut self, metadata: &serde_json::Value) -> Result<(), ArchiveError> {
+        self.data.set_metadata_json(metadata)
+    }
+}
+
+/// A seekable sink whose length can be set, to truncate what the archive wrote.
+///
+/// See [`ZipArchive::rollback_last_entry`].
+pub trait SetLen {
+    /// Truncate or extend the sink to `len` bytes.
+    fn set_len(&mut self, len: u64) -> impl Future<Output = std::io::Result<()>> + Send;
+}
+
+impl SetLen for File {
+    fn set_len(&mut self, len: u64) -> impl Future<Output = std::io::Result<()>> + Send {
+        File::set_len(self, len)
+    }
+}
+
+impl SetLen for std::io::Cursor<Vec<u8>> {
+    async fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.get_mut().resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+/// Selects the entry's compression method from the payload's head, peeked before the header is written: an
+/// empty payload is stored with the `store_empty` option, an incompressible sample with the `adaptive` one. The
+/// sample is compressed at the entry's level.
+async fn adapt_compressor(
+    compressor: CompressionMethod,
+    options: &FileOptions<'_>,
+    compression_level: Level,
+    head: &[u8],
+) -> Result<CompressionMethod, ArchiveError> {
+    if options.store_empty && head.is_empty() {
+        return Ok(CompressionMethod::Store());
+    }
+
+    if options.adaptive && compressor != CompressionMethod::Store() && !head.is_empty() {
+        let sample = &head[..head.len().min(options.adaptive_sample_size)];
+        let mut compressed = Vec::new();
+        let mut hasher = EntryHasher::new(&FileOptions::default());
+        compress(
+            compressor,
+            &mut compressed,
+            &mut { sample },
+            &mut hasher,
+            compression_level,
+        )
+        .await?;
+        if is_incompressible(options, sample.len(), compressed.len()) {
+            return Ok(CompressionMethod::Store());
+        }
+    }
+
+    Ok(compressor)
+}
+
+/// A reader checking its payload holds exactly the declared length.
+struct SizedReader<'r, R: AsyncRead + Unpin> {
+    reader: &'r mut R,
+    length: DeclaredLength,
+}
+
+impl<'r, R: AsyncRead + Unpin> AsyncRead for SizedReader<'r, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let sized = self.get_mut();
+        let remaining = sized.length.remaining();
+
+        if remaining == 0 {
+            //probe for a byte past the declared length
+            let mut probe = [0; 1];
+            let mut probe = ReadBuf::new(&mut probe);
+            return match Pin::new(&mut *sized.reader).poll_read(cx, &mut probe) {
+                Poll::Ready(Ok(())) if probe.filled().is_empty() => Poll::Ready(Ok(())),
+                Poll::Ready(Ok(())) => Poll::Ready(Err(sized.length.end(true))),
+                polled => polled,
+            };
+        }
+
+        let max = usize::try_from(remaining)
+            .map_or(buf.remaining(), |remaining| remaining.min(buf.remaining()));
+        let mut limited = ReadBuf::new(buf.initialize_unfilled_to(max));
+        match Pin::new(&mut *sized.reader).poll_read(cx, &mut limited) {
+            Poll::Ready(Ok(())) => {
+                let read = limited.filled().len();
+                if read == 0 && max > 0 {
+                    return Poll::Ready(Err(sized.length.end(false)));
+                }
+                sized.length.update(limited.filled());
+                buf.advance(read);
+                Poll::Ready(Ok(()))
+            }
+            polled => polled,
+        }
+    }
+}
+
+/// A reader writing what it reads to its capture.
+///
+/// The bytes of a read are written to the capture on the next one, they are all written before the end of the
+/// payload is reported.
+struct CaptureReader<'r, R: AsyncRead + Unpin, C: AsyncWrite + Unpin> {
+    reader: &'r mut R,
+    capture: &'r mut C,
+    /// The bytes read, not written to the capture yet
+    pending: Vec<u8>,
+}
+
+impl<'r, R: AsyncRead + Unpin, C: AsyncWrite + Unpin> AsyncRead for CaptureReader<'r, R, C> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let capturing = self.get_mut();
+
+        while !capturing.pending.is_empty() {
+            match Pin::new(&mut *capturing.capture).poll_write(cx, &capturing.pending) {
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+                }
+                Poll::Ready(Ok(written)) => {
+                    capturing.pending.drain(..written);
+                }
+                Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let filled = buf.filled().len();
+        match Pin::new(&mut *capturing.reader).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                capturing.pending.extend_from_slice(&buf.filled()[filled..]);
+                Poll::Ready(Ok(()))
+            }
+            polled => polled,
+        }
+    }
+}
+
+/// A reader failing once its token is cancelled.
+#[cfg(feature = "cancellation")]
+struct CancellableReader<'r, R: AsyncRead + Unpin> {
+    reader: &'r mut R,
+    token: &'r CancellationToken,
+}
+
+#[cfg(feature = "cancellation")]
+impl<'r, R: AsyncRead + Unpin> AsyncRead for CancellableReader<'r, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let cancellable = self.get_mut();
+        if cancellable.token.is_cancelled() {
+            return Poll::Ready(Err(std::io::Error::other("cancelled")));
+        }
+        Pin::new(&mut *cancellable.reader).poll_read(cx, buf)
     }
 }
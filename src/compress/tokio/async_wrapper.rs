@@ -1,9 +1,11 @@
+use crc32fast::Hasher;
 use std::pin::Pin;
 use std::{io::Error, task::Poll};
-use tokio::io::{AsyncSeek, AsyncWrite};
+use tokio::io::{AsyncSeek, AsyncWrite, BufWriter};
 pub struct AsyncWriteWrapper<W: AsyncWrite + Unpin> {
     writer: W,
     written_bytes_count: u64,
+    hasher: Option<Hasher>,
 }
 
 pub struct AsyncWriteSeekWrapper<WS: AsyncWrite + AsyncSeek + Unpin> {
@@ -12,11 +14,32 @@ pub struct AsyncWriteSeekWrapper<WS: AsyncWrite + AsyncSeek + Unpin> {
 }
 
 pub trait CommonWrapper<W: AsyncWrite + Unpin + ?Sized>:
-    AsyncWrite + AsyncSeek + Unpin + std::marker::Send
+    AsyncWrite + Unpin + std::marker::Send
 {
     fn get_written_bytes_count(&mut self) -> Result<u64, Error>;
     fn set_written_bytes_count(&mut self, count: u64);
     fn get_into(self: Box<Self>) -> W;
+
+    /// The wrapped writer, `None` while bytes written to the wrapper are buffered before it.
+    fn writer_mut(&mut self) -> Option<&mut W>;
+
+    /// The wrapper as an [`AsyncSeek`], `None` if the wrapped writer doesn't seek.
+    fn seekable(&mut self) -> Option<&mut (dyn AsyncSeek + Unpin + Send)> {
+        None
+    }
+
+    /// Start computing the CRC-32 of the written bytes, returns `false` if the wrapper can't.
+    fn start_hashing(&mut self) -> bool {
+        false
+    }
+
+    /// The CRC-32 of the bytes written since the hashing started.
+    fn written_crc32(&self) -> Option<u32> {
+        None
+    }
+
+    /// Tell the wrapper the archive is finalized, so its next flush is the last one.
+    fn finalizing(&mut self) {}
 }
 
 impl<W: AsyncWrite + Unpin + Send> CommonWrapper<W> for AsyncWriteWrapper<W> {
@@ -31,6 +54,19 @@ impl<W: AsyncWrite + Unpin + Send> CommonWrapper<W> for AsyncWriteWrapper<W> {
     fn get_into(self: Box<Self>) -> W {
         self.writer
     }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        Some(&mut self.writer)
+    }
+
+    fn start_hashing(&mut self) -> bool {
+        self.hasher.get_or_insert_with(Hasher::new);
+        true
+    }
+
+    fn written_crc32(&self) -> Option<u32> {
+        self.hasher.as_ref().map(|hasher| hasher.clone().finalize())
+    }
 }
 
 impl<W: AsyncWrite + AsyncSeek + Unpin + Send> CommonWrapper<W> for AsyncWriteSeekWrapper<W> {
@@ -45,6 +81,14 @@ impl<W: AsyncWrite + AsyncSeek + Unpin + Send> CommonWrapper<W> for AsyncWriteSe
     fn get_into(self: Box<Self>) -> W {
         self.writer
     }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        Some(&mut self.writer)
+    }
+
+    fn seekable(&mut self) -> Option<&mut (dyn AsyncSeek + Unpin + Send)> {
+        Some(self)
+    }
 }
 
 impl<W: AsyncWrite + Unpin> AsyncWriteWrapper<W> {
@@ -52,23 +96,11 @@ impl<W: AsyncWrite + Unpin> AsyncWriteWrapper<W> {
         Self {
             writer: w,
             written_bytes_count: 0,
+            hasher: None,
         }
     }
 }
 
-impl<W: AsyncWrite + Unpin> AsyncSeek for AsyncWriteWrapper<W> {
-    fn start_seek(self: Pin<&mut Self>, _position: std::io::SeekFrom) -> std::io::Result<()> {
-        Ok(())
-    }
-
-    fn poll_complete(
-        self: Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<std::io::Result<u64>> {
-        std::task::Poll::Ready(Ok(self.written_bytes_count))
-    }
-}
-
 impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncWriteWrapper<W> {
     fn poll_write(
         self: Pin<&mut Self>,
@@ -82,6 +114,9 @@ impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncWriteWrapper<W> {
         results.map(|pool_result| match pool_result {
             Ok(nb_byte_written) => {
                 wrapper.written_bytes_count += nb_byte_written as u64;
+                if let Some(hasher) = &mut wrapper.hasher {
+                    hasher.update(&buf[..nb_byte_written]);
+                }
                 Ok(nb_byte_written)
             }
             Err(e) => Err(e),
@@ -169,3 +204,84 @@ impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncSeek for AsyncWriteSeekWrapper<W> {
         })
     }
 }
+
+/// A streaming wrapper coalescing the writes in a buffer, written downstream once full, e.g. to send fewer and
+/// larger packets on a socket.
+///
+/// The archive's flushes between its entries are ignored: the buffer is written downstream once full, and
+/// flushed when the archive is finalized.
+pub struct BufferedSink<W: AsyncWrite + Unpin> {
+    wrapper: AsyncWriteWrapper<BufWriter<W>>,
+    finalizing: bool,
+}
+
+impl<W: AsyncWrite + Unpin> BufferedSink<W> {
+    pub fn new(w: W, capacity: usize) -> BufferedSink<W> {
+        Self {
+            wrapper: AsyncWriteWrapper::new(BufWriter::with_capacity(capacity, w)),
+            finalizing: false,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> CommonWrapper<W> for BufferedSink<W> {
+    fn get_written_bytes_count(&mut self) -> Result<u64, Error> {
+        self.wrapper.get_written_bytes_count()
+    }
+
+    fn set_written_bytes_count(&mut self, count: u64) {
+        self.wrapper.set_written_bytes_count(count);
+    }
+
+    fn get_into(self: Box<Self>) -> W {
+        //the archive flushes the buffer before giving the writer back
+        debug_assert!(self.wrapper.writer.buffer().is_empty());
+        self.wrapper.writer.into_inner()
+    }
+
+    fn writer_mut(&mut self) -> Option<&mut W> {
+        let writer = &mut self.wrapper.writer;
+        writer.buffer().is_empty().then(|| writer.get_mut())
+    }
+
+    fn start_hashing(&mut self) -> bool {
+        self.wrapper.start_hashing()
+    }
+
+    fn written_crc32(&self) -> Option<u32> {
+        self.wrapper.written_crc32()
+    }
+
+    fn finalizing(&mut self) {
+        self.finalizing = true;
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufferedSink<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        Pin::new(&mut self.get_mut().wrapper).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        let sink = self.get_mut();
+        if sink.finalizing {
+            Pin::new(&mut sink.wrapper).poll_flush(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().wrapper).poll_shutdown(cx)
+    }
+}
@@ -1,19 +1,47 @@
-use async_compression::tokio::write::{BzEncoder, DeflateEncoder, XzEncoder, ZstdEncoder};
-use crc32fast::Hasher;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+#[cfg(feature = "bzip2")]
+use async_compression::tokio::write::BzEncoder;
+#[cfg(feature = "deflate")]
+use async_compression::tokio::write::DeflateEncoder;
+#[cfg(feature = "xz")]
+use async_compression::tokio::write::XzEncoder;
+#[cfg(feature = "zstd")]
+use async_compression::tokio::write::ZstdEncoder;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 
+#[cfg(feature = "zstd")]
+use crate::compress::common::zstd_level;
 use crate::{
-    compress::common::{compress_common, compress_common_async, is_text_buf, write_async},
+    compress::common::{
+        compress_common, compress_common_async, write_async, BudgetReader, EntryHasher,
+    },
     compression::{CompressionMethod, Level},
     error::ArchiveError,
 };
 
+impl<R: AsyncRead + Unpin> AsyncRead for BudgetReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let reader = self.get_mut();
+        reader.check_deadline()?;
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut reader.inner).poll_read(cx, buf))?;
+        reader.record(&buf.filled()[filled..])?;
+        Poll::Ready(Ok(()))
+    }
+}
+
 impl From<Level> for async_compression::Level {
     fn from(level: Level) -> Self {
         match level {
             Level::Fastest => async_compression::Level::Fastest,
             Level::Best => async_compression::Level::Best,
-            Level::Default => async_compression::Level::Default,
+            Level::Default | Level::Inherit => async_compression::Level::Default,
             Level::Precise(val) => async_compression::Level::Precise(val),
             Level::None => async_compression::Level::Precise(0),
         }
@@ -24,7 +52,7 @@ pub async fn compress<'a, R, W>(
     compressor: CompressionMethod,
     writer: &'a mut W,
     reader: &'a mut R,
-    hasher: &'a mut Hasher,
+    hasher: &'a mut EntryHasher,
     compression_level: Level,
 ) -> Result<(u64, bool), ArchiveError>
 where
@@ -37,11 +65,27 @@ where
         compressor
     };
 
+    //the default level is defined by the compression method
+    #[cfg_attr(
+        not(any(
+            feature = "deflate",
+            feature = "bzip2",
+            feature = "zstd",
+            feature = "xz"
+        )),
+        allow(unused_variables)
+    )]
+    let compression_level = match compression_level {
+        Level::Default | Level::Inherit => Level::Precise(compression_method.default_level()),
+        level => level,
+    };
+
     match compression_method {
         CompressionMethod::Store() => {
             let total_read = write_async!(writer, hasher, reader);
             Ok(total_read)
         }
+        #[cfg(feature = "deflate")]
         CompressionMethod::Deflate() => {
             let mut zencoder = DeflateEncoder::with_quality(writer, compression_level.into());
 
@@ -50,6 +94,7 @@ where
             Ok(total_read)
         }
 
+        #[cfg(feature = "bzip2")]
         CompressionMethod::BZip2() => {
             let mut encoder = BzEncoder::with_quality(writer, compression_level.into());
 
@@ -58,13 +103,17 @@ where
             Ok(total_read)
         }
 
+        #[cfg(feature = "zstd")]
         CompressionMethod::Zstd() => {
+            //the encoder clamps the level, it is validated as with the standard compressor
+            zstd_level(compression_level)?;
             let mut encoder = ZstdEncoder::with_quality(writer, compression_level.into());
 
             let total_read = compress_common_async!(encoder, hasher, reader);
 
             Ok(total_read)
         }
+        #[cfg(feature = "xz")]
         CompressionMethod::Xz() => {
             //let bw = BufWriter::new(writer);
             let mut encoder = XzEncoder::with_quality(writer, compression_level.into());
@@ -73,16 +122,27 @@ where
 
             Ok(total_read)
         }
+        #[allow(unreachable_patterns)]
+        CompressionMethod::Deflate()
+        | CompressionMethod::BZip2()
+        | CompressionMethod::Zstd()
+        | CompressionMethod::Xz() => Err(ArchiveError::CompressionMethodNotCompiled(
+            compression_method,
+        )),
         CompressionMethod::Unknown(compression_method_code) => Err(
             ArchiveError::UnsuportedCompressionMethodCode(compression_method_code),
         ),
+        CompressionMethod::Auto() => Err(ArchiveError::UnsuportedCompressionMethod(
+            compression_method,
+        )),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "deflate"))]
 mod test {
     use crate::compress::tokio::async_wrapper::AsyncWriteWrapper;
     use crate::compress::tokio::async_wrapper::CommonWrapper;
+    use crate::compress::FileOptions;
     use crate::compression::Level;
 
     use super::*;
@@ -144,7 +204,7 @@ mod test {
         let x = b"example";
 
         let compressor = CompressionMethod::Deflate();
-        let mut hasher = Hasher::new();
+        let mut hasher = EntryHasher::new(&FileOptions::default());
 
         //let a: AsyncRead = &x;
         let mut writer: Box<dyn CommonWrapper<Vec<u8>>> =
@@ -164,6 +224,42 @@ mod test {
         println!("compress len {:?}", temp.len());
         println!("{:X?}", temp);
     }
+
+    #[tokio::test]
+    async fn test_deflate_is_raw() {
+        use flate2::read::DeflateDecoder;
+        use std::io::Read;
+
+        let x = b"Some string data, some string data, some more string data".repeat(20);
+
+        for level in [Level::Fastest, Level::Default, Level::Best] {
+            let mut hasher = EntryHasher::new(&FileOptions::default());
+            let mut writer: Box<dyn CommonWrapper<Vec<u8>>> =
+                Box::new(AsyncWriteWrapper::new(Vec::new()));
+
+            compress(
+                CompressionMethod::Deflate(),
+                &mut writer,
+                &mut x.as_slice(),
+                &mut hasher,
+                level,
+            )
+            .await
+            .unwrap();
+            let compressed = writer.get_into();
+
+            //ZIP entries hold raw deflate data: no zlib header (deflate method 8, checksum multiple of 31)
+            let zlib_header = compressed[0] & 0x0F == 8
+                && (u16::from(compressed[0]) << 8 | u16::from(compressed[1])) % 31 == 0;
+            assert!(!zlib_header, "{:02X?}", &compressed[..2]);
+
+            let mut decompressed = Vec::new();
+            DeflateDecoder::new(compressed.as_slice())
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, x);
+        }
+    }
 }
 
 //74 78 9C 4A AD 48 CC 2D C8 49 05 00 00 00 FF FF 03 00 0B C0 02 ED
@@ -10,7 +10,7 @@ pub const ZSTD: u16 = 93;
 pub const XZ: u16 = 95;
 
 /// Provides the comtession methods supported for a ZipArchive
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum CompressionMethod {
     Store(),
     Deflate(),
@@ -18,6 +18,9 @@ pub enum CompressionMethod {
     Zstd(),
     Xz(),
     Unknown(u16),
+    /// Selects the method from the entry name's extension when the entry is appended, see
+    /// [`CompressionMethod::for_extension`].
+    Auto(),
 }
 
 impl CompressionMethod {
@@ -29,6 +32,8 @@ impl CompressionMethod {
             CompressionMethod::Zstd() => ZSTD,
             CompressionMethod::Xz() => XZ,
             CompressionMethod::Unknown(comp_method_code) => *comp_method_code,
+            // resolved before being written
+            CompressionMethod::Auto() => DEFALTE,
         }
     }
 
@@ -42,6 +47,116 @@ impl CompressionMethod {
         }
     }
 
+    /// The precise level used when [`Level::Default`] is requested for this method.
+    pub fn default_level(&self) -> i32 {
+        match self {
+            CompressionMethod::Deflate() => 6,
+            CompressionMethod::BZip2() => 6,
+            CompressionMethod::Zstd() => 3,
+            CompressionMethod::Xz() => 6,
+            CompressionMethod::Store()
+            | CompressionMethod::Unknown(_)
+            | CompressionMethod::Auto() => 0,
+        }
+    }
+
+    /// Tells whether a backend for this method is compiled in.
+    ///
+    /// Each compression backend comes with the method's feature (`deflate`, `bzip2`, `zstd` and `xz`) and the
+    /// `std` or `tokio` feature; [`CompressionMethod::Store`] is always available. Selecting a method that isn't
+    /// compiled in fails with [`ArchiveError::CompressionMethodNotCompiled`].
+    pub fn is_available(&self) -> bool {
+        let engine = cfg!(any(feature = "std", feature = "tokio"));
+        match self {
+            CompressionMethod::Store() | CompressionMethod::Auto() => true,
+            CompressionMethod::Deflate() => engine && cfg!(feature = "deflate"),
+            CompressionMethod::BZip2() => engine && cfg!(feature = "bzip2"),
+            CompressionMethod::Zstd() => engine && cfg!(feature = "zstd"),
+            CompressionMethod::Xz() => engine && cfg!(feature = "xz"),
+            CompressionMethod::Unknown(_) => false,
+        }
+    }
+
+    /// Every compression method, in the order of their ZIP codes.
+    ///
+    /// [`CompressionMethod::Unknown`] and [`CompressionMethod::Auto`] aren't methods of their own, they aren't
+    /// listed.
+    pub fn all() -> &'static [CompressionMethod] {
+        &[
+            CompressionMethod::Store(),
+            CompressionMethod::Deflate(),
+            CompressionMethod::BZip2(),
+            CompressionMethod::Zstd(),
+            CompressionMethod::Xz(),
+        ]
+    }
+
+    /// The compression methods compiled in, those of [`CompressionMethod::all`] that are
+    /// [available](CompressionMethod::is_available), e.g. to offer only the methods that can be selected.
+    pub fn supported() -> &'static [CompressionMethod] {
+        &[
+            CompressionMethod::Store(),
+            #[cfg(all(feature = "deflate", any(feature = "std", feature = "tokio")))]
+            CompressionMethod::Deflate(),
+            #[cfg(all(feature = "bzip2", any(feature = "std", feature = "tokio")))]
+            CompressionMethod::BZip2(),
+            #[cfg(all(feature = "zstd", any(feature = "std", feature = "tokio")))]
+            CompressionMethod::Zstd(),
+            #[cfg(all(feature = "xz", any(feature = "std", feature = "tokio")))]
+            CompressionMethod::Xz(),
+        ]
+    }
+
+    /// The highest-ratio method compiled in.
+    ///
+    /// The methods are tried in the order xz, zstd, bzip2 and deflate, falling back to
+    /// [`CompressionMethod::Store`] if none is available.
+    pub fn best_available() -> CompressionMethod {
+        [
+            CompressionMethod::Xz(),
+            CompressionMethod::Zstd(),
+            CompressionMethod::BZip2(),
+            CompressionMethod::Deflate(),
+        ]
+        .into_iter()
+        .find(CompressionMethod::is_available)
+        .unwrap_or(CompressionMethod::Store())
+    }
+
+    /// The method suited to a file with the given extension (without the dot, case insensitive).
+    ///
+    /// Already compressed formats (archives, images, audio and video) are stored, to save the CPU of
+    /// compressing them again for close to no gain. Others are deflated.
+    pub fn for_extension(extension: &str) -> CompressionMethod {
+        const INCOMPRESSIBLE: &[&str] = &[
+            "7z", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar", "jpeg", "jpg",
+            "lz4", "lzma", "m4a", "mkv", "mov", "mp3", "mp4", "odt", "ogg", "png", "pptx", "rar",
+            "tgz", "webm", "webp", "woff", "woff2", "xlsx", "xz", "zip", "zst",
+        ];
+
+        let extension = extension.to_ascii_lowercase();
+        if INCOMPRESSIBLE.contains(&extension.as_str()) {
+            CompressionMethod::Store()
+        } else {
+            CompressionMethod::Deflate()
+        }
+    }
+
+    /// The method to use for the given entry: [`CompressionMethod::Auto`] is resolved from the name's extension,
+    /// any other method is kept.
+    pub fn resolve(self, file_name: &str) -> CompressionMethod {
+        match self {
+            CompressionMethod::Auto() => {
+                let base_name = file_name.rsplit('/').next().unwrap_or(file_name);
+                match base_name.rsplit_once('.') {
+                    Some((_, extension)) => CompressionMethod::for_extension(extension),
+                    None => CompressionMethod::for_extension(""),
+                }
+            }
+            method => method,
+        }
+    }
+
     pub fn from_compression_method(
         compression_method: u16,
     ) -> Result<CompressionMethod, ArchiveError> {
@@ -67,6 +182,7 @@ impl CompressionMethod {
             CompressionMethod::Zstd() => "zstd",
             CompressionMethod::Xz() => "xz",
             CompressionMethod::Unknown(_) => "unknown",
+            CompressionMethod::Auto() => "auto",
         }
     }
 
@@ -77,7 +193,7 @@ impl CompressionMethod {
             CompressionMethod::Deflate() => match level {
                 Level::Fastest => flag | BIT2, //1      1    Super Fast (-es) compression option was used. //1      0    Fast (-ef) compression option was used.
                 Level::Best => flag | BIT1, //0      1    Maximum (-exx/-ex) compression option was used.
-                Level::Default => flag,     //0      0    Normal (-en) compression option was used.
+                Level::Default | Level::Inherit => flag, //0      0    Normal (-en) compression option was used.
                 Level::Precise(val) => match val {
                     1..=2 => self.update_general_purpose_bit_flag(flag, Level::Fastest),
                     6 => self.update_general_purpose_bit_flag(flag, Level::Default),
@@ -105,7 +221,7 @@ impl Default for CompressionMethod {
 }
 
 /// Compression method's level
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum Level {
     /// Fastest quality of compression, usually produces bigger size.
     Fastest,
@@ -114,8 +230,13 @@ pub enum Level {
     Best,
 
     /// Default quality of compression defined by the selected compression algorithm.
+    #[default]
     Default,
 
+    /// The archive's default level, set with its `default_compression_level` method. Elsewhere, it is the
+    /// same as `Level::Default`.
+    Inherit,
+
     /// No compression at all, the archiver will set the compression method as _store_
     None,
 
@@ -127,8 +248,114 @@ pub enum Level {
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
+
     use super::*;
 
+    #[test]
+    fn compression_method_equality() {
+        assert_eq!(CompressionMethod::Deflate(), CompressionMethod::Deflate());
+        assert_ne!(CompressionMethod::Deflate(), CompressionMethod::Store());
+        assert_eq!(
+            CompressionMethod::Unknown(42),
+            CompressionMethod::Unknown(42)
+        );
+        assert_ne!(
+            CompressionMethod::Unknown(42),
+            CompressionMethod::Unknown(43)
+        );
+
+        // an unknown code is not equal to the known method with the same code
+        assert_ne!(
+            CompressionMethod::Unknown(DEFALTE),
+            CompressionMethod::Deflate()
+        );
+
+        let mut extensions = HashMap::new();
+        extensions.insert(CompressionMethod::Zstd(), "zst");
+        extensions.insert(CompressionMethod::Unknown(42), "bin");
+
+        assert_eq!(extensions.get(&CompressionMethod::Zstd()), Some(&"zst"));
+        assert_eq!(
+            extensions.get(&CompressionMethod::Unknown(42)),
+            Some(&"bin")
+        );
+        assert_eq!(extensions.get(&CompressionMethod::Unknown(7)), None);
+    }
+
+    #[test]
+    fn default_level() {
+        assert_eq!(CompressionMethod::Deflate().default_level(), 6);
+        assert_eq!(CompressionMethod::BZip2().default_level(), 6);
+        assert_eq!(CompressionMethod::Zstd().default_level(), 3);
+        assert_eq!(CompressionMethod::Xz().default_level(), 6);
+        assert_eq!(CompressionMethod::Store().default_level(), 0);
+    }
+
+    #[test]
+    fn best_available() {
+        let best = CompressionMethod::best_available();
+        assert!(best.is_available());
+        assert!(CompressionMethod::Store().is_available());
+        assert!(!CompressionMethod::Unknown(42).is_available());
+
+        #[cfg(all(feature = "std", feature = "xz"))]
+        assert_eq!(best, CompressionMethod::Xz());
+    }
+
+    #[test]
+    fn supported() {
+        let supported = CompressionMethod::supported();
+        let expected: Vec<_> = CompressionMethod::all()
+            .iter()
+            .copied()
+            .filter(CompressionMethod::is_available)
+            .collect();
+        assert_eq!(supported, expected.as_slice());
+        assert!(supported.contains(&CompressionMethod::best_available()));
+        assert_eq!(CompressionMethod::all().len(), 5);
+    }
+
+    #[test]
+    fn for_extension() {
+        assert_eq!(
+            CompressionMethod::for_extension("png"),
+            CompressionMethod::Store()
+        );
+        assert_eq!(
+            CompressionMethod::for_extension("ZIP"),
+            CompressionMethod::Store()
+        );
+        assert_eq!(
+            CompressionMethod::for_extension("txt"),
+            CompressionMethod::Deflate()
+        );
+        assert_eq!(
+            CompressionMethod::for_extension(""),
+            CompressionMethod::Deflate()
+        );
+    }
+
+    #[test]
+    fn resolve() {
+        assert_eq!(
+            CompressionMethod::Auto().resolve("images/photo.JPG"),
+            CompressionMethod::Store()
+        );
+        assert_eq!(
+            CompressionMethod::Auto().resolve("archive.tar.gz"),
+            CompressionMethod::Store()
+        );
+        assert_eq!(
+            CompressionMethod::Auto().resolve("photos.d/README"),
+            CompressionMethod::Deflate()
+        );
+        assert_eq!(
+            CompressionMethod::Xz().resolve("photo.jpg"),
+            CompressionMethod::Xz()
+        );
+    }
+
     #[test]
     fn update_general_purpose_bit_flag() {
         assert_eq!(
@@ -2,12 +2,14 @@ use std::mem::size_of;
 
 pub const FILE_HEADER_BASE_SIZE: u64 = (7 * size_of::<u16>() + 4 * size_of::<u32>()) as u64;
 pub const ZIP64_DESCRIPTOR_SIZE: u64 = 28;
-#[cfg(any(feature = "experimental"))]
 pub const CENTRAL_DIRECTORY_ENTRY_BASE_SIZE: u64 =
     (11 * size_of::<u16>() + 6 * size_of::<u32>()) as u64;
 
-#[cfg(any(feature = "experimental"))]
 pub const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = (5 * size_of::<u16>() + 3 * size_of::<u32>()) as u64;
+pub const ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE: u64 =
+    (2 * size_of::<u16>() + 3 * size_of::<u32>() + 5 * size_of::<u64>()) as u64;
+pub const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE: u64 =
+    (3 * size_of::<u32>() + size_of::<u64>()) as u64;
 pub const FILE_HEADER_CRC_OFFSET: u64 = 14;
 
 pub const CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06054b50;
@@ -17,14 +19,29 @@ pub const CENTRAL_DIRECTORY_ENTRY_SIGNATURE: u32 = 0x02014b50;
 pub const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50; // Local file header signature.
 pub const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50; // Data descriptor signature.
 
+pub const ARCHIVE_DIGEST_PREFIX: &str = "archflow-crc32:";
+/// The prefix followed by the CRC-32 as 8 lowercase hexadecimal digits
+pub const ARCHIVE_DIGEST_SUFFIX_SIZE: usize = ARCHIVE_DIGEST_PREFIX.len() + 8;
+
+/// The prefix of an archive comment holding JSON metadata
+#[cfg(feature = "serde")]
+pub const METADATA_JSON_PREFIX: &str = "archflow-json:";
+
 pub const DEFAULT_VERSION: u8 = 46;
-pub const UNIX: u8 = 3;
-pub const VERSION_MADE_BY: u16 = (UNIX as u16) << 8 | DEFAULT_VERSION as u16;
 
+pub const ENCRYPTED_FLAG: u16 = 1 << 0;
 pub const EXTENDED_LOCAL_HEADER_FLAG: u16 = 1 << 3;
+#[cfg(feature = "experimental")]
+pub const COMPRESSED_PATCHED_DATA_FLAG: u16 = 1 << 5;
+pub const STRONG_ENCRYPTION_FLAG: u16 = 1 << 6;
 pub const UTF8_HEADER_FLAG: u16 = 1 << 11;
 pub const VERSION_USES_ZIP64_FORMAT_EXTENSIONS: u16 = 45;
 pub const X5455_EXTENDEDTIMESTAMP: u16 = 0x5455;
+#[cfg(feature = "experimental")]
+pub const X7075_INFOZIP_UNICODE_PATH: u16 = 0x7075;
+pub const X7855_INFOZIP_UNIX_NEW: u16 = 0x7855;
+pub const X7875_INFOZIP_UNIX_UID_GID: u16 = 0x7875;
+pub const XD935_ANDROID_ALIGNMENT: u16 = 0xD935;
 
 pub const S_IFREG: u32 = 0o100000;
 pub const S_IFDIR: u32 = 0o040000;
@@ -0,0 +1,46 @@
+//! Pluggable CRC-32 implementation.
+//!
+//! The archiver computes the entries' CRC-32 with [`crc32fast`] by default. Platforms with a faster
+//! implementation (e.g. through SIMD intrinsics) can provide it by implementing [`Crc32`] and selecting it with
+//! [`FileOptions::crc32_hasher`](crate::compress::FileOptions::crc32_hasher).
+
+/// A CRC-32 (IEEE) hasher.
+pub trait Crc32: Default {
+    /// Process the given bytes.
+    fn update(&mut self, buf: &[u8]);
+
+    /// Return the CRC-32 of the bytes processed so far.
+    fn finalize(self) -> u32;
+}
+
+impl Crc32 for crc32fast::Hasher {
+    fn update(&mut self, buf: &[u8]) {
+        crc32fast::Hasher::update(self, buf)
+    }
+
+    fn finalize(self) -> u32 {
+        crc32fast::Hasher::finalize(self)
+    }
+}
+
+/// Object safe counterpart of [`Crc32`], so the hasher can be selected at runtime.
+pub(crate) trait DynCrc32 {
+    fn update(&mut self, buf: &[u8]);
+    fn finalize(self: Box<Self>) -> u32;
+}
+
+impl<C: Crc32> DynCrc32 for C {
+    fn update(&mut self, buf: &[u8]) {
+        Crc32::update(self, buf)
+    }
+
+    fn finalize(self: Box<Self>) -> u32 {
+        Crc32::finalize(*self)
+    }
+}
+
+pub(crate) type Crc32Factory = fn() -> Box<dyn DynCrc32 + Send>;
+
+pub(crate) fn new_hasher<C: Crc32 + Send + 'static>() -> Box<dyn DynCrc32 + Send> {
+    Box::<C>::default()
+}
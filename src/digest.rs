@@ -0,0 +1,42 @@
+//! Additional digests computed over the entries' uncompressed payload.
+//!
+//! The ZIP format only records a CRC-32 for each entry. When a stronger digest
+//! is needed (e.g. for content-addressable storage), it can be computed in the
+//! same pass using [`FileOptions::with_digest`](crate::compress::FileOptions::with_digest).
+//!
+//! # Features
+//!
+//! Requires `digest` feature
+
+use sha2::{Digest, Sha256};
+
+/// The digest algorithm computed alongside the entry's CRC-32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestKind {
+    /// SHA-256, a 32 bytes digest.
+    Sha256,
+}
+
+pub(crate) enum DigestHasher {
+    Sha256(Sha256),
+}
+
+impl DigestHasher {
+    pub fn new(kind: DigestKind) -> Self {
+        match kind {
+            DigestKind::Sha256 => DigestHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        match self {
+            DigestHasher::Sha256(hasher) => hasher.update(buf),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            DigestHasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
@@ -4,11 +4,37 @@ use crate::compression::CompressionMethod;
 
 pub enum ArchiveError {
     IoError(std::io::Error),
-    UnsuportedCompressionLevel(CompressionMethod),
+    /// The level isn't valid for the method, the detail tells the valid ones.
+    UnsuportedCompressionLevel(CompressionMethod, String),
     UnsuportedCompressionMethodCode(u16),
     UnsuportedCompressionMethod(CompressionMethod),
+    CompressionMethodNotCompiled(CompressionMethod),
     BadArchiveStructure(String),
+    #[cfg(feature = "lzma")]
     LZMA(xz2::stream::Error),
+    CommentTooLong(usize),
+    /// The entry name is longer than 0xFFFF bytes, its length in bytes.
+    EntryNameTooLong(usize),
+    Unsupported(String),
+    EntrySizeLimitExceeded(u64),
+    /// The payload length differs from the declared one. The CRC-32 is the one of the bytes read, up to the
+    /// declared length.
+    LengthMismatch {
+        expected: u64,
+        actual: u64,
+        crc32: u32,
+    },
+    Cancelled,
+    /// The payload's CRC-32 differs from the one provided with `FileOptions::known_crc32`.
+    Crc32Mismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// No entry was appended at this index.
+    EntryNotFound(usize),
+    /// Patching an entry's local header failed, the entry may be partially patched, or its bytes couldn't be
+    /// rolled back. The entry is left out of the central directory.
+    InconsistentArchive(std::io::Error),
 }
 
 impl Display for ArchiveError {
@@ -17,8 +43,8 @@ impl Display for ArchiveError {
             ArchiveError::IoError(e) => {
                 write!(f, "Archive io error: {:?}", e)
             }
-            ArchiveError::UnsuportedCompressionLevel(method) => {
-                write!(f, "Archive level error for method {:}", method)
+            ArchiveError::UnsuportedCompressionLevel(method, detail) => {
+                write!(f, "Archive level error for method {:}: {}", method, detail)
             }
             ArchiveError::UnsuportedCompressionMethodCode(val) => {
                 write!(f, "The compression method code '{:}' is not supported", val)
@@ -33,7 +59,61 @@ impl Display for ArchiveError {
             ArchiveError::BadArchiveStructure(detail) => {
                 write!(f, "Bad archive structure : {}", detail)
             }
+            ArchiveError::CompressionMethodNotCompiled(compression_method) => {
+                write!(
+                    f,
+                    "The compression method '{:}' is not compiled in, enable the '{}' feature",
+                    compression_method,
+                    compression_method.label()
+                )
+            }
+            #[cfg(feature = "lzma")]
             ArchiveError::LZMA(e) => write!(f, "LZMA error : {}", e),
+            ArchiveError::CommentTooLong(len) => {
+                write!(
+                    f,
+                    "The comment is {} bytes long, the maximum is {}",
+                    len,
+                    u16::MAX
+                )
+            }
+            ArchiveError::EntryNameTooLong(len) => {
+                write!(
+                    f,
+                    "The entry name is {} bytes long, the maximum is {}",
+                    len,
+                    u16::MAX
+                )
+            }
+            ArchiveError::Unsupported(detail) => write!(f, "Unsupported : {}", detail),
+            ArchiveError::EntrySizeLimitExceeded(max) => {
+                write!(f, "The entry exceeds the maximum size of {} bytes", max)
+            }
+            ArchiveError::LengthMismatch {
+                expected,
+                actual,
+                crc32,
+            } => write!(
+                f,
+                "The payload is {} bytes long, {} declared (CRC-32 {:08x})",
+                actual, expected, crc32
+            ),
+            ArchiveError::Cancelled => write!(f, "The append was cancelled"),
+            ArchiveError::Crc32Mismatch { expected, actual } => write!(
+                f,
+                "The payload's CRC-32 is {:08x}, {:08x} provided",
+                actual, expected
+            ),
+            ArchiveError::EntryNotFound(index) => {
+                write!(f, "The archive has no entry at index {}", index)
+            }
+            ArchiveError::InconsistentArchive(e) => {
+                write!(
+                    f,
+                    "The entry couldn't be patched or rolled back, the archive is inconsistent: {}",
+                    e
+                )
+            }
         }
     }
 }
@@ -44,14 +124,15 @@ impl Debug for ArchiveError {
             ArchiveError::IoError(e) => {
                 write!(f, "Archive io error {:?}", e)
             }
-            ArchiveError::UnsuportedCompressionLevel(method) => {
-                write!(f, "Archive level error for method {:?}", method)
+            ArchiveError::UnsuportedCompressionLevel(method, detail) => {
+                write!(f, "Archive level error for method {:?}: {}", method, detail)
             }
             ArchiveError::UnsuportedCompressionMethodCode(val) => write!(
                 f,
                 "The compression method code '{:?}' is not supported",
                 val
             ),
+            #[cfg(feature = "lzma")]
             ArchiveError::LZMA(e) => write!(f, "LZMA error : {:?}", e),
             _ => (self as &dyn Display).fmt(f),
         }
@@ -64,6 +145,7 @@ impl From<std::io::Error> for ArchiveError {
     }
 }
 
+#[cfg(feature = "lzma")]
 impl From<xz2::stream::Error> for ArchiveError {
     fn from(value: xz2::stream::Error) -> Self {
         ArchiveError::LZMA(value)
@@ -0,0 +1,50 @@
+//! Single member gzip (.gz) streams.
+//!
+//! For a single payload, e.g. an HTTP response body, a gzip member carries less
+//! overhead than a zip archive. It uses the same deflate encoder and CRC-32 as
+//! the archive entries.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use archflow::gz::std::GzStream;
+//! use std::fs::File;
+//!
+//! let file = File::create("lorem_ipsum.txt.gz").unwrap();
+//! let mut payload = File::open("lorem_ipsum.txt").unwrap();
+//!
+//! let (gz_size, _file) = GzStream::new(file).compress(&mut payload).unwrap();
+//! ```
+
+use crate::archive_common::ArchiveDescriptor;
+
+#[cfg(feature = "std")]
+pub mod std;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+const GZIP_ID1: u8 = 0x1f;
+const GZIP_ID2: u8 = 0x8b;
+const GZIP_CM_DEFLATE: u8 = 8;
+const GZIP_OS_UNKNOWN: u8 = 255;
+
+/// The 10 bytes gzip member header, without optional fields and modification time.
+pub(crate) fn build_gz_header() -> ArchiveDescriptor {
+    let mut header = ArchiveDescriptor::new(10);
+    header.write_u8(GZIP_ID1);
+    header.write_u8(GZIP_ID2);
+    header.write_u8(GZIP_CM_DEFLATE);
+    header.write_u8(0); // flags
+    header.write_u32(0); // modification time, not available
+    header.write_u8(0); // extra flags
+    header.write_u8(GZIP_OS_UNKNOWN);
+    header
+}
+
+/// The gzip member trailer: the CRC-32 and the uncompressed size modulo 2^32.
+pub(crate) fn build_gz_trailer(crc32: u32, uncompressed_size: u64) -> ArchiveDescriptor {
+    let mut trailer = ArchiveDescriptor::new(8);
+    trailer.write_u32(crc32);
+    trailer.write_u32(uncompressed_size as u32);
+    trailer
+}
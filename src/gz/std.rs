@@ -0,0 +1,65 @@
+use std::io::{Read, Write};
+
+use crate::compress::common::EntryHasher;
+use crate::compress::std::compressor::compress;
+use crate::compress::std::write_wrapper::{CommonWrapper, WriteWrapper};
+use crate::compress::FileOptions;
+use crate::compression::{CompressionMethod, Level};
+use crate::error::ArchiveError;
+
+use super::{build_gz_header, build_gz_trailer};
+
+/// A gzip stream holding a single member.
+///
+/// # Features
+///
+/// Requires `std` feature
+pub struct GzStream<W: Write> {
+    sink: WriteWrapper<W>,
+    compression_level: Level,
+}
+
+impl<W: Write> GzStream<W> {
+    /// Create a new gzip stream, using the underlying [`Write`] to write the member.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink: WriteWrapper::new(sink),
+            compression_level: Level::Default,
+        }
+    }
+
+    /// Set the deflate compression level.
+    ///
+    /// The default is [`Level::Default`].
+    pub fn compression_level(mut self, level: Level) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Compress the payload as the gzip member.
+    ///
+    /// Returns the gzip stream size and the underlying [`Write`].
+    pub fn compress<R>(mut self, payload: &mut R) -> Result<(u64, W), ArchiveError>
+    where
+        R: Read,
+    {
+        self.sink.write_all(build_gz_header().buffer())?;
+
+        let mut hasher = EntryHasher::new(&FileOptions::default());
+        let (uncompressed_size, _) = compress(
+            CompressionMethod::Deflate(),
+            &mut self.sink,
+            payload,
+            &mut hasher,
+            self.compression_level,
+        )?;
+        let (crc32, _) = hasher.finalize()?;
+
+        self.sink
+            .write_all(build_gz_trailer(crc32, uncompressed_size).buffer())?;
+        self.sink.flush()?;
+
+        let gz_size = self.sink.get_written_bytes_count()?;
+        Ok((gz_size, Box::new(self.sink).get_into()))
+    }
+}
@@ -0,0 +1,67 @@
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::compress::common::EntryHasher;
+use crate::compress::tokio::async_wrapper::{AsyncWriteWrapper, CommonWrapper};
+use crate::compress::tokio::compressor::compress;
+use crate::compress::FileOptions;
+use crate::compression::{CompressionMethod, Level};
+use crate::error::ArchiveError;
+
+use super::{build_gz_header, build_gz_trailer};
+
+/// A gzip stream holding a single member.
+///
+/// # Features
+///
+/// Requires `tokio` feature
+pub struct GzStream<W: AsyncWrite + Unpin> {
+    sink: AsyncWriteWrapper<W>,
+    compression_level: Level,
+}
+
+impl<W: AsyncWrite + Unpin + Send> GzStream<W> {
+    /// Create a new gzip stream, using the underlying [`AsyncWrite`] to write the member.
+    pub fn new(sink: W) -> Self {
+        Self {
+            sink: AsyncWriteWrapper::new(sink),
+            compression_level: Level::Default,
+        }
+    }
+
+    /// Set the deflate compression level.
+    ///
+    /// The default is [`Level::Default`].
+    pub fn compression_level(mut self, level: Level) -> Self {
+        self.compression_level = level;
+        self
+    }
+
+    /// Compress the payload as the gzip member.
+    ///
+    /// Returns the gzip stream size and the underlying [`AsyncWrite`].
+    pub async fn compress<R>(mut self, payload: &mut R) -> Result<(u64, W), ArchiveError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.sink.write_all(build_gz_header().buffer()).await?;
+
+        let mut hasher = EntryHasher::new(&FileOptions::default());
+        let (uncompressed_size, _) = compress(
+            CompressionMethod::Deflate(),
+            &mut self.sink,
+            payload,
+            &mut hasher,
+            self.compression_level,
+        )
+        .await?;
+        let (crc32, _) = hasher.finalize()?;
+
+        self.sink
+            .write_all(build_gz_trailer(crc32, uncompressed_size).buffer())
+            .await?;
+        self.sink.flush().await?;
+
+        let gz_size = self.sink.get_written_bytes_count()?;
+        Ok((gz_size, Box::new(self.sink).get_into()))
+    }
+}
@@ -34,6 +34,15 @@
 //! ---------|------
 //! tokio    | To use tokio non blocking API, namely: [tokio::io::AsyncRead], [tokio::io::AsyncWrite] and [tokio::io::AsyncSeek]
 //! std      | To use standard API, namely:  [std::io::Read], [std::io::Write] and [std::io::Seek]
+//! digest   | To compute a SHA-256 digest of the entries alongside their CRC-32
+//! tar      | To repackage a tar archive as a zip archive (implies `std`)
+//! deflate  | The deflate compression method, and the gzip payloads
+//! bzip2    | The bzip2 compression method
+//! zstd     | The zstd compression method
+//! xz       | The xz compression method (implies `lzma`)
+//! lzma     | The liblzma library, backing the xz compression method
+//! serde    | To store JSON metadata in the archive comment
+//! cancellation | To cancel a tokio append with a [`CancellationToken`](https://docs.rs/tokio-util/latest/tokio_util/sync/struct.CancellationToken.html) (implies `tokio`)
 //!
 //!
 //! ## Examples
@@ -218,7 +227,23 @@ mod constants;
 mod archive_common;
 pub mod compress;
 pub mod compression;
+#[cfg(feature = "experimental")]
+mod cp437;
+pub mod crc32;
+#[cfg(feature = "digest")]
+pub mod digest;
 pub mod error;
+#[cfg(all(feature = "deflate", any(feature = "std", feature = "tokio")))]
+pub mod gz;
+pub mod overhead;
+pub mod prelude;
+#[cfg(feature = "tar")]
+pub mod repackage;
+#[cfg(feature = "std")]
+pub mod reproducible;
 pub mod types;
 #[cfg(feature = "experimental")]
 pub mod uncompress;
+
+#[cfg(feature = "std")]
+pub use reproducible::build_reproducible;
@@ -0,0 +1,47 @@
+//! Size of the ZIP structures surrounding the entries' payload.
+//!
+//! Useful to estimate an archive's size before writing it, e.g. to set a `Content-Length` header when the
+//! payloads are stored. The archive size is the sum of each entry's local header, payload and central
+//! directory header, plus the end of central directory.
+//!
+//! The extra field lengths depend on the [`FileOptions`](crate::compress::FileOptions) and on whether the entry
+//! needs the zip64 format extensions.
+
+use crate::constants::{
+    CENTRAL_DIRECTORY_ENTRY_BASE_SIZE, END_OF_CENTRAL_DIRECTORY_SIZE, FILE_HEADER_BASE_SIZE,
+    ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE, ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE,
+};
+
+/// Size of an entry's local file header.
+///
+/// # Arguments
+/// * `name_len` - The length in bytes of the entry's name
+/// * `extra_len` - The length in bytes of the local header's extra fields
+pub fn local_header_overhead(name_len: usize, extra_len: usize) -> u64 {
+    FILE_HEADER_BASE_SIZE + name_len as u64 + extra_len as u64
+}
+
+/// Size of an entry's central directory header.
+///
+/// # Arguments
+/// * `name_len` - The length in bytes of the entry's name
+/// * `extra_len` - The length in bytes of the central header's extra fields
+/// * `comment_len` - The length in bytes of the entry's comment
+pub fn central_header_overhead(name_len: usize, extra_len: usize, comment_len: usize) -> u64 {
+    CENTRAL_DIRECTORY_ENTRY_BASE_SIZE + name_len as u64 + extra_len as u64 + comment_len as u64
+}
+
+/// Size of the end of central directory, including the zip64 record and locator if `zip64` is set.
+///
+/// # Arguments
+/// * `comment_len` - The length in bytes of the archive's comment
+/// * `zip64` - Whether the archive uses the zip64 format extensions
+pub fn eocd_overhead(comment_len: usize, zip64: bool) -> u64 {
+    let zip64_overhead = if zip64 {
+        ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE + ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE
+    } else {
+        0
+    };
+
+    END_OF_CENTRAL_DIRECTORY_SIZE + comment_len as u64 + zip64_overhead
+}
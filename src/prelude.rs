@@ -0,0 +1,19 @@
+//! Re-exports of the types commonly used to create an archive.
+//!
+//! ```rust
+//! use archflow::prelude::*;
+//! ```
+//!
+//! The `ZipArchive` is the tokio one if the `tokio` feature is enabled, the standard one otherwise. When both
+//! features are enabled, the standard archive is available in [`crate::compress::std::archive`].
+
+#[cfg(all(feature = "std", not(feature = "tokio")))]
+pub use crate::compress::std::archive::ZipArchive;
+#[cfg(feature = "tokio")]
+pub use crate::compress::tokio::archive::ZipArchive;
+pub use crate::{
+    compress::FileOptions,
+    compression::{CompressionMethod, Level},
+    error::ArchiveError,
+    types::FileDateTime,
+};
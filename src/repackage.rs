@@ -0,0 +1,66 @@
+//! Repackage other archive formats as zip archives.
+//!
+//! # Features
+//!
+//! Requires `tar` feature
+
+use std::io::{Read, Write};
+
+use ::tar::EntryType;
+
+use crate::compress::std::archive::ZipArchive;
+use crate::compress::FileOptions;
+use crate::error::ArchiveError;
+use crate::types::FileDateTime;
+
+/// Append every entry of the tar archive to the zip archive, preserving their names, modification
+/// times and unix modes.
+///
+/// Regular files and directories are appended, other entry types (links, devices, ...) have no zip
+/// counterpart and are skipped. The other entry options are taken from `options`.
+///
+/// Returns the number of entries appended.
+pub fn repackage_tar<'a, R, W>(
+    tar: R,
+    archive: &mut ZipArchive<'a, W>,
+    options: &FileOptions<'a>,
+) -> Result<usize, ArchiveError>
+where
+    R: Read,
+    W: Write + 'a,
+{
+    let mut tar_archive = ::tar::Archive::new(tar);
+    let mut count = 0;
+
+    for entry in tar_archive.entries()? {
+        let mut entry = entry?;
+        let header = entry.header();
+
+        let entry_type = header.entry_type();
+        if !matches!(
+            entry_type,
+            EntryType::Regular | EntryType::Continuous | EntryType::Directory
+        ) {
+            continue;
+        }
+
+        let mut entry_options = options.clone();
+        if let Ok(mode) = header.mode() {
+            entry_options = entry_options.unix_permissions(mode);
+        }
+        if let Some(mtime) = header.mtime().ok().and_then(|t| i32::try_from(t).ok()) {
+            entry_options = entry_options.last_modified_time(FileDateTime::UnixCustom(mtime));
+        }
+
+        let file_name = entry.path()?.to_string_lossy().into_owned();
+
+        if entry_type == EntryType::Directory {
+            archive.append_directory(&file_name, &entry_options)?;
+        } else {
+            archive.append(&file_name, &entry_options, &mut entry)?;
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,85 @@
+//! Bit-for-bit reproducible archives.
+//!
+//! Building the same files twice, e.g. on two CI runs, gives the same bytes: the entries are sorted by name and
+//! nothing depends on the time or on the host, the dates are `FileDateTime::Zero`, no extended timestamp is
+//! written and the permissions are fixed.
+//!
+//!```rust
+//! use archflow::{build_reproducible, compression::CompressionMethod, error::ArchiveError};
+//!
+//! # fn main() -> Result<(), ArchiveError> {
+//! let files: [(&str, &[u8]); 2] = [("b.txt", b"world"), ("a.txt", b"hello")];
+//! let (archive_size, buffer) = build_reproducible(Vec::new(), &files, CompressionMethod::Deflate())?;
+//! # assert_eq!(buffer.len() as u64, archive_size);
+//! # Ok(())
+//! # }
+//!```
+
+use std::io::Write;
+
+use crate::{
+    compress::std::archive::ZipArchive,
+    compress::FileOptions,
+    compression::{CompressionMethod, Level},
+    error::ArchiveError,
+    types::FileDateTime,
+};
+
+/// The permissions of every entry: `rw-r--r--`.
+const REPRODUCIBLE_PERMISSIONS: u32 = 0o644;
+
+/// Write a reproducible, streamable, archive of the files, given as name and payload pairs.
+///
+/// The entries are appended in the names' order, whatever the order of `files`, and compressed with the
+/// method's default level. Returns the archive size and the writer.
+pub fn build_reproducible<W: Write>(
+    writer: W,
+    files: &[(&str, &[u8])],
+    method: CompressionMethod,
+) -> Result<(u64, W), ArchiveError> {
+    let options = FileOptions::default()
+        .compression_method(method)
+        .compression_level(Level::Default)
+        .last_modified_time(FileDateTime::Zero)
+        .extended_timestamp(false)
+        .unix_permissions(REPRODUCIBLE_PERMISSIONS);
+
+    let mut sorted: Vec<&(&str, &[u8])> = files.iter().collect();
+    sorted.sort_by_key(|(name, _)| *name);
+
+    let mut archive = ZipArchive::new_streamable(writer);
+    for (name, bytes) in sorted {
+        archive.append_bytes(name, &options, bytes)?;
+    }
+    archive.finalize()
+}
+
+#[cfg(test)]
+mod test {
+    use super::build_reproducible;
+    use crate::compression::CompressionMethod;
+
+    #[test]
+    fn build_reproducible_ignores_order() {
+        let files: [(&str, &[u8]); 3] = [
+            ("dir/b.txt", b"world"),
+            ("a.txt", b"hello hello hello"),
+            ("c.bin", &[0, 1, 2, 3]),
+        ];
+        let mut reversed = files;
+        reversed.reverse();
+
+        let (size, first) =
+            build_reproducible(Vec::new(), &files, CompressionMethod::Deflate()).unwrap();
+        let (_, second) =
+            build_reproducible(Vec::new(), &reversed, CompressionMethod::Deflate()).unwrap();
+
+        assert_eq!(first.len() as u64, size);
+        assert_eq!(first, second);
+
+        //the first entry is the smallest name, right after its 30 bytes local header
+        assert_eq!(&first[30..35], b"a.txt");
+        //the dos time is zero and the date 1980, January 1st
+        assert_eq!(&first[10..14], &[0, 0, 0x21, 0]);
+    }
+}
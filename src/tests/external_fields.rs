@@ -100,3 +100,102 @@ mod timestamp_tests {
         );
     }
 }
+
+mod unix_owner_tests {
+    use crate::archive_common::{
+        ArchiveDescriptor, ArchiveFileEntry, ExtraField, ExtraFieldInfoZipUnixNew,
+        ExtraFieldInfoZipUnixUidGid,
+    };
+
+    #[test]
+    fn test_write_unix_new() {
+        let extrafield = ExtraFieldInfoZipUnixNew::new(1000, 0x1234);
+        let archive_file_entry = ArchiveFileEntry::default();
+
+        let mut archive_descriptor = ArchiveDescriptor::new(100);
+        extrafield.local_header_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert_eq!(
+            archive_descriptor.bytes(),
+            &[0x55, 0x78, 0x04, 0x00, 0xe8, 0x03, 0x34, 0x12]
+        );
+        assert_eq!(
+            archive_descriptor.len(),
+            extrafield.local_header_extra_field_size(&archive_file_entry) as usize
+        );
+
+        archive_descriptor.clear();
+        extrafield.central_header_extra_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert_eq!(archive_descriptor.bytes(), &[0x55, 0x78, 0x00, 0x00]);
+        assert_eq!(
+            archive_descriptor.len(),
+            extrafield.central_header_extra_field_size(&archive_file_entry) as usize
+        );
+    }
+
+    #[test]
+    fn test_write_unix_uid_gid() {
+        let extrafield = ExtraFieldInfoZipUnixUidGid::new(1000, 0x12345678);
+        let archive_file_entry = ArchiveFileEntry::default();
+        let expected = [
+            0x75, 0x78, 0x0b, 0x00, 0x01, 0x04, 0xe8, 0x03, 0x00, 0x00, 0x04, 0x78, 0x56, 0x34,
+            0x12,
+        ];
+
+        let mut archive_descriptor = ArchiveDescriptor::new(100);
+        extrafield.local_header_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert_eq!(archive_descriptor.bytes(), &expected);
+        assert_eq!(
+            archive_descriptor.len(),
+            extrafield.local_header_extra_field_size(&archive_file_entry) as usize
+        );
+
+        archive_descriptor.clear();
+        extrafield.central_header_extra_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert_eq!(archive_descriptor.bytes(), &expected);
+        assert_eq!(
+            archive_descriptor.len(),
+            extrafield.central_header_extra_field_size(&archive_file_entry) as usize
+        );
+    }
+}
+
+mod alignment_tests {
+    use crate::archive_common::{
+        ArchiveDescriptor, ArchiveFileEntry, ExtraField, ExtraFieldAlignment,
+    };
+
+    #[test]
+    fn test_write_alignment() {
+        let extrafield = ExtraFieldAlignment::new(4);
+        //data at 30 + 5 + 6 = 41 without padding
+        let archive_file_entry = ArchiveFileEntry {
+            file_name_len: 5,
+            ..Default::default()
+        };
+
+        let mut archive_descriptor = ArchiveDescriptor::new(100);
+        extrafield.local_header_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert_eq!(
+            archive_descriptor.bytes(),
+            &[0x35, 0xd9, 0x05, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            archive_descriptor.len(),
+            extrafield.local_header_extra_field_size(&archive_file_entry) as usize
+        );
+
+        archive_descriptor.clear();
+        extrafield.central_header_extra_write_data(&mut archive_descriptor, &archive_file_entry);
+
+        assert!(archive_descriptor.is_empty());
+        assert_eq!(
+            extrafield.central_header_extra_field_size(&archive_file_entry),
+            0
+        );
+    }
+}
@@ -1,6 +1,10 @@
 extern crate chrono;
 use core::fmt;
-use std::{u16, u8};
+use std::borrow::Cow;
+
+use crate::constants::{
+    ENCRYPTED_FLAG, EXTENDED_LOCAL_HEADER_FLAG, STRONG_ENCRYPTION_FLAG, UTF8_HEADER_FLAG,
+};
 
 use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone, Timelike, Utc};
 
@@ -103,7 +107,19 @@ impl DateTimeCS {
         date.and_hms_opt(hour, minute, second).unwrap_or_default()
     }
 
+    /// The MS-DOS date and time, the time has a 2 seconds precision: an odd second is rounded down.
+    ///
+    /// With the `log` feature, the loss of precision is logged at debug level, as file managers show a time off by
+    /// a second.
     pub fn ms_dos(&self) -> (u16, u16) {
+        #[cfg(feature = "log")]
+        if self.second % 2 == 1 {
+            log::debug!(
+                "The MS-DOS time of {} is rounded down to an even second",
+                self
+            );
+        }
+
         let date = self.day | (self.month << 5) | self.year.saturating_sub(1980) << 9;
         let time = (self.second / 2) | (self.minute << 5) | self.hour << 11;
         (date, time)
@@ -145,7 +161,7 @@ pub enum FileDateTime {
     /// (year, month, day, hour, minute, second)
     Custom(DateTimeCS),
 
-    ///
+    /// Current local date and time
     Now,
 
     /// Current timestamp (seconds since UNIX epoch)
@@ -237,6 +253,14 @@ impl FileCompatibilitySystem {
         }
     }
 
+    /// Tells if the high 16 bits of the external file attributes hold a unix file mode.
+    pub fn has_unix_attributes(&self) -> bool {
+        matches!(
+            self,
+            FileCompatibilitySystem::Unix | FileCompatibilitySystem::OsX
+        )
+    }
+
     /// Add the system code to the version needed
     pub fn update_version_needed(&self, version_needed: u16) -> u16 {
         let val = self.value();
@@ -245,6 +269,65 @@ impl FileCompatibilitySystem {
     }
 }
 
+/// The separator of the path components written in the entries' names.
+///
+/// The ZIP specification mandates forward slashes, backslashes are only for legacy tools expecting them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PathSeparator {
+    /// The names are written as given, with forward slashes as per the specification.
+    #[default]
+    Forward,
+    /// The forward slashes of the names are replaced by backslashes.
+    Backward,
+}
+
+impl PathSeparator {
+    /// The name as written in the archive.
+    pub fn apply<'n>(&self, file_name: &'n str) -> Cow<'n, str> {
+        match self {
+            PathSeparator::Backward if file_name.contains('/') => {
+                Cow::Owned(file_name.replace('/', "\\"))
+            }
+            _ => Cow::Borrowed(file_name),
+        }
+    }
+}
+
+/// The Info-ZIP extra field storing the Unix owner of the entries.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UidGidFormat {
+    /// The "Info-ZIP Unix (new)" field (0x7855), 16 bits ids in the local header only, the central header has
+    /// an empty field.
+    Unix16,
+    /// The "Info-ZIP Unix (newer UID/GID)" field (0x7875), ids of any size in both headers.
+    #[default]
+    AnySize,
+}
+
+/// The general purpose bit flags of an entry, decoded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GeneralPurposeFlags {
+    /// The payload is encrypted (bit 0)
+    pub encrypted: bool,
+    /// The CRC-32 and sizes are in a data descriptor following the payload (bit 3)
+    pub has_data_descriptor: bool,
+    /// The payload is encrypted with the PKWARE strong encryption (bit 6)
+    pub strong_encryption: bool,
+    /// The name and the comment are encoded in UTF-8 (bit 11)
+    pub utf8: bool,
+}
+
+impl From<u16> for GeneralPurposeFlags {
+    fn from(flags: u16) -> Self {
+        GeneralPurposeFlags {
+            encrypted: flags & ENCRYPTED_FLAG != 0,
+            has_data_descriptor: flags & EXTENDED_LOCAL_HEADER_FLAG != 0,
+            strong_encryption: flags & STRONG_ENCRYPTION_FLAG != 0,
+            utf8: flags & UTF8_HEADER_FLAG != 0,
+        }
+    }
+}
+
 impl fmt::Display for FileCompatibilitySystem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let label = match self {
@@ -282,6 +365,18 @@ mod test {
         println!("Time zero {} {}", date, time)
     }
 
+    #[test]
+    fn test_ms_dos_odd_second() {
+        let date_time = DateTimeCS::from_msdos(0x5821, 0x6433);
+        assert_eq!(date_time.second, 38);
+
+        let odd = DateTimeCS {
+            second: 39,
+            ..date_time
+        };
+        assert_eq!(odd.ms_dos(), date_time.ms_dos());
+    }
+
     #[test]
     fn test_time_display_0_0() {
         let date_time = DateTimeCS::from_msdos(0, 0);
@@ -330,4 +425,13 @@ mod test {
             FileCompatibilitySystem::Unknown(55)
         );
     }
+
+    #[test]
+    fn test_file_compatibility_system_unix_attributes() {
+        assert!(FileCompatibilitySystem::Unix.has_unix_attributes());
+        assert!(FileCompatibilitySystem::OsX.has_unix_attributes());
+        assert!(!FileCompatibilitySystem::Dos.has_unix_attributes());
+        assert!(!FileCompatibilitySystem::WindowsNTFS.has_unix_attributes());
+        assert!(!FileCompatibilitySystem::Unknown(55).has_unix_attributes());
+    }
 }
@@ -1,11 +1,19 @@
 use crate::archive_common::{
     ArchiveDescriptorReader, ArchiveFileEntry, CentralDirectoryEnd, ExtraField,
-    ExtraFieldExtendedTimestamp, ExtraFieldUnknown, ExtraFieldZIP64ExtendedInformation,
+    ExtraFieldExtendedTimestamp, ExtraFieldUnicodePath, ExtraFieldUnknown,
+    ExtraFieldZIP64ExtendedInformation,
 };
 use crate::compression::CompressionMethod;
-use crate::constants::{CENTRAL_DIRECTORY_ENTRY_BASE_SIZE, CENTRAL_DIRECTORY_ENTRY_SIGNATURE};
+use crate::constants::{
+    CENTRAL_DIRECTORY_ENTRY_SIGNATURE, DATA_DESCRIPTOR_SIGNATURE, FILE_HEADER_BASE_SIZE,
+    LOCAL_FILE_HEADER_SIGNATURE,
+};
 use crate::{
-    constants::{CENTRAL_DIRECTORY_END_SIGNATURE, END_OF_CENTRAL_DIRECTORY_SIZE},
+    constants::{
+        CENTRAL_DIRECTORY_END_SIGNATURE, END_OF_CENTRAL_DIRECTORY_SIZE,
+        ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE, ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE,
+        ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE, ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIGNATURE,
+    },
     error::ArchiveError,
 };
 use byteorder::{LittleEndian, ReadBytesExt};
@@ -16,28 +24,203 @@ pub struct ArchiveReader<R>
 where
     R: Read + Seek,
 {
-    #[allow(dead_code)]
     reader: R,
     pub file_entries: Vec<ArchiveFileEntry>,
     pub central_directory_end: CentralDirectoryEnd,
+    /// What the read recovered from, e.g. a skipped entry in a lenient read
+    pub warnings: Vec<String>,
 }
 
 impl<R: Read + Seek> ArchiveReader<R> {
     pub fn new(mut reader: R) -> Result<ArchiveReader<R>, ArchiveError> {
-        let (central_directory_end, file_entries) = Self::parse(&mut reader)?;
+        let mut warnings = Vec::new();
+        let (central_directory_end, file_entries) = Self::parse(&mut reader, false, &mut warnings)?;
 
         let ar = ArchiveReader {
             reader,
             file_entries,
             central_directory_end,
+            warnings,
         };
         Ok(ar)
     }
 
-    fn parse(reader: &mut R) -> Result<(CentralDirectoryEnd, Vec<ArchiveFileEntry>), ArchiveError> {
-        //find central dir end
+    /// Read a partially corrupted archive, collecting the central directory entries it can.
+    ///
+    /// This is a recovery tool: instead of failing on the first bad central directory entry, the entry is
+    /// skipped and the next entry signature is searched for. The skipped entries are described in the reader's
+    /// `warnings`. The end of central directory must still be valid.
+    pub fn new_lenient(mut reader: R) -> Result<ArchiveReader<R>, ArchiveError> {
+        let mut warnings = Vec::new();
+        let (central_directory_end, file_entries) = Self::parse(&mut reader, true, &mut warnings)?;
+
+        let ar = ArchiveReader {
+            reader,
+            file_entries,
+            central_directory_end,
+            warnings,
+        };
+        Ok(ar)
+    }
+
+    /// Read the raw compressed bytes of the entry at the index, in the central directory order, without decoding
+    /// them.
+    ///
+    /// The payload starts after the entry's local header, whose name and extra field lengths may differ from the
+    /// central directory ones, it is bounded by the entry's compressed size. Along with `append_raw`, it allows to
+    /// repackage an entry without recompressing it.
+    pub fn read_entry_raw(&mut self, index: usize) -> Result<impl Read + '_, ArchiveError> {
+        let payload_start = self.payload_start(index)?;
+        let compressed_size = self.file_entries[index].compressed_size;
+
+        self.reader.seek(SeekFrom::Start(payload_start))?;
+        Ok((&mut self.reader).take(compressed_size))
+    }
+
+    /// Check the data descriptors against the central directory, the mismatches are added to the `warnings`.
+    ///
+    /// The local header of an entry written with a data descriptor has a zero CRC-32 and sizes, the reader relies
+    /// on the central directory. A streaming reader relies on the data descriptor instead: this check catches the
+    /// writers disagreeing between the two. The sizes of a descriptor are on 32 bits, or on 64 bits if only the
+    /// latter are followed by a header signature.
+    pub fn check_data_descriptors(&mut self) -> Result<(), ArchiveError> {
+        for index in 0..self.file_entries.len() {
+            let entry = &self.file_entries[index];
+            if !entry.flags().has_data_descriptor {
+                continue;
+            }
+            let expected = (entry.crc32, entry.compressed_size, entry.uncompressed_size);
+            let file_name = entry.get_file_name();
+
+            let payload_start = self.payload_start(index)?;
+            self.reader
+                .seek(SeekFrom::Start(payload_start + expected.1))?;
+
+            //the optional signature, the CRC-32, the 64 bits sizes and the next header signature
+            let mut descriptor = Vec::new();
+            (&mut self.reader)
+                .take(4 + 4 + 16 + 4)
+                .read_to_end(&mut descriptor)?;
+
+            match read_data_descriptor(&descriptor) {
+                Some(found) if found == expected => {}
+                Some((crc32, compressed_size, uncompressed_size)) => {
+                    self.warnings.push(format!(
+                        "The data descriptor of the entry {:?} disagrees with the central directory: CRC-32 {:08x}, {} and {} bytes instead of {:08x}, {} and {} bytes",
+                        file_name, crc32, compressed_size, uncompressed_size, expected.0, expected.1, expected.2
+                    ));
+                }
+                None => {
+                    self.warnings.push(format!(
+                        "No data descriptor found after the payload of the entry {:?}",
+                        file_name
+                    ));
+                }
+            }
+        }
 
+        Ok(())
+    }
+
+    /// The offset of the payload of the entry at the index, right after its local header.
+    fn payload_start(&mut self, index: usize) -> Result<u64, ArchiveError> {
+        let entry = self
+            .file_entries
+            .get(index)
+            .ok_or(ArchiveError::EntryNotFound(index))?;
+
+        let mut local_header = vec![0; FILE_HEADER_BASE_SIZE as usize];
+        self.reader.seek(SeekFrom::Start(entry.offset))?;
+        self.reader.read_exact(&mut local_header)?;
+
+        let mut indexer = ArchiveDescriptorReader::new();
+        if indexer.read_u32(&local_header)? != LOCAL_FILE_HEADER_SIGNATURE {
+            return Err(ArchiveError::BadArchiveStructure(format!(
+                "No local file header at the offset {} of the entry {:?}",
+                entry.offset,
+                entry.get_file_name()
+            )));
+        }
+
+        //the file name and extra field lengths end the fixed part of the local header
+        indexer.set_index(FILE_HEADER_BASE_SIZE as usize - 4);
+        let file_name_len = indexer.read_u16(&local_header)?;
+        let extra_field_length = indexer.read_u16(&local_header)?;
+
+        Ok(entry.offset + FILE_HEADER_BASE_SIZE + file_name_len as u64 + extra_field_length as u64)
+    }
+
+    /// The JSON metadata set with `ZipArchive::set_metadata_json`.
+    ///
+    /// Returns `None` if the archive comment doesn't hold metadata, e.g. a plain comment, or
+    /// [`ArchiveError::BadArchiveStructure`] if the metadata isn't valid JSON.
+    ///
+    /// # Features
+    ///
+    /// Requires `serde` feature
+    #[cfg(feature = "serde")]
+    pub fn metadata_json(&self) -> Result<Option<serde_json::Value>, ArchiveError> {
+        self.central_directory_end.metadata_json()
+    }
+
+    /// Read every archive of a file holding several archives concatenated together, e.g. by
+    /// `cat a.zip b.zip > combined.zip`.
+    ///
+    /// This is a recovery tool: such a file isn't a valid archive, other readers only see its last archive. The
+    /// archives are found from the end of the file, each one ending where the next one starts. Their entries'
+    /// offsets are relative to the start of their archive, as written by a zip writer unaware of the
+    /// concatenation. Leading bytes not ending with an archive (e.g. a self-extractor stub) are ignored.
+    ///
+    /// Returns the archives in the file order.
+    pub fn read_all_members(mut reader: R) -> Result<Vec<ArchiveMember>, ArchiveError> {
+        let mut members = Vec::new();
+        let mut end = reader.seek(SeekFrom::End(0))?;
+
+        while end > 0 {
+            let parsed = Self::parse_ending_at(&mut reader, end, true, false, &mut Vec::new());
+            let (central_directory_end, file_entries, start) = match parsed {
+                Ok(parsed) => parsed,
+                Err(_) if !members.is_empty() => break,
+                Err(error) => return Err(error),
+            };
+
+            members.push(ArchiveMember {
+                start,
+                end,
+                file_entries,
+                central_directory_end,
+            });
+            end = start;
+        }
+
+        members.reverse();
+        Ok(members)
+    }
+
+    fn parse(
+        reader: &mut R,
+        lenient: bool,
+        warnings: &mut Vec<String>,
+    ) -> Result<(CentralDirectoryEnd, Vec<ArchiveFileEntry>), ArchiveError> {
         let file_length = reader.seek(SeekFrom::End(0))?;
+        let (central_directory_end, file_entries, _) =
+            Self::parse_ending_at(reader, file_length, false, lenient, warnings)?;
+        Ok((central_directory_end, file_entries))
+    }
+
+    /// Parse the archive ending at `file_length`, returns its central directory end, its entries and its start.
+    ///
+    /// With `relative_offsets`, the archive starts before its central directory, offsets are relative to this
+    /// start. Otherwise the offsets are from the start of the file. With `lenient`, the bad central directory
+    /// entries are skipped.
+    fn parse_ending_at(
+        reader: &mut R,
+        file_length: u64,
+        relative_offsets: bool,
+        lenient: bool,
+        warnings: &mut Vec<String>,
+    ) -> Result<(CentralDirectoryEnd, Vec<ArchiveFileEntry>, u64), ArchiveError> {
+        //find central dir end
 
         let mut position: u64 = match file_length.checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE) {
             Some(p) => p,
@@ -68,9 +251,9 @@ impl<R: Read + Seek> ArchiveReader<R> {
 
             //println!("val {:0X} ", val);
             if val == CENTRAL_DIRECTORY_END_SIGNATURE {
-              /*
+                /*
                 let signature = stringify!(CENTRAL_DIRECTORY_END_SIGNATURE);
-                 println!("{signature} found at {}", position); 
+                 println!("{signature} found at {}", position);
                  */
                 break;
             }
@@ -95,33 +278,72 @@ impl<R: Read + Seek> ArchiveReader<R> {
         let central_end_size: usize = (file_length - position - 4) as usize;
         let mut central_end_buffer: Vec<u8> = vec![0; central_end_size];
 
-/*         println!(
+        /*         println!(
             "central_end_size {} file_length {} location {}",
             central_end_size, file_length, position
         ); */
-    /*     println!("vec len  {} ", central_end_buffer.len()); */
+        /*     println!("vec len  {} ", central_end_buffer.len()); */
         //reader.seek(SeekFrom::Start(pos))?;
 
         reader.read_exact(&mut central_end_buffer)?;
 
-        let central_directory_end = Self::read_cental_directory_end(&central_end_buffer)?;
+        let mut central_directory_end =
+            Self::read_cental_directory_end(&central_end_buffer, warnings)?;
+
+        //a zip64 archive has its end of central directory record before the classic one
+        let zip64_central_directory_end_position = Self::read_zip64_central_directory_end(
+            reader,
+            position,
+            relative_offsets,
+            &mut central_directory_end,
+        )?;
+        let central_directory_end_position =
+            zip64_central_directory_end_position.unwrap_or(position);
+
+        /*         println!("central_directory_end {:#?}", central_directory_end); */
 
-/*         println!("central_directory_end {:#?}", central_directory_end); */
+        let start = if relative_offsets {
+            central_directory_end
+                .central_directory_size
+                .checked_add(central_directory_end.offset_of_start_of_central_directory)
+                .and_then(|size| central_directory_end_position.checked_sub(size))
+                .ok_or_else(|| {
+                    ArchiveError::BadArchiveStructure(format!(
+                        "The archive ending at {} starts before the file",
+                        file_length
+                    ))
+                })?
+        } else {
+            0
+        };
 
-        let archive_file_entry = Self::read_central_directory(&central_directory_end, reader)?;
+        let archive_file_entry = Self::read_central_directory(
+            &central_directory_end,
+            reader,
+            start,
+            file_length - start,
+            zip64_central_directory_end_position.is_some(),
+            lenient,
+            warnings,
+        )?;
 
         //println!("archive_file_entry {:#?}", archive_file_entry);
         //println!("archive_file_entry file: {}", archive_file_entry);
 
-        Ok((central_directory_end, archive_file_entry))
+        Ok((central_directory_end, archive_file_entry, start))
     }
 
     fn read_central_directory(
         central_directory_end: &CentralDirectoryEnd,
         reader: &mut R,
+        start: u64,
+        file_length: u64,
+        zip64_record: bool,
+        lenient: bool,
+        warnings: &mut Vec<String>,
     ) -> Result<Vec<ArchiveFileEntry>, ArchiveError> {
         reader.seek(SeekFrom::Start(
-            central_directory_end.offset_of_start_of_central_directory,
+            start + central_directory_end.offset_of_start_of_central_directory,
         ))?;
 
         let mut central_directory_buffer: Vec<u8> =
@@ -129,117 +351,292 @@ impl<R: Read + Seek> ArchiveReader<R> {
 
         reader.read_exact(&mut central_directory_buffer)?;
 
+        // The declared count bounds the entries read. Past it, or once the classic count is saturated without
+        // a ZIP64 record to give the actual one, the entries are scanned by their signatures.
+        let declared_entries =
+            central_directory_end.total_number_of_entries_in_the_central_directory;
+        let declared_count_known = zip64_record || declared_entries < u16::MAX as u64;
+
         let mut indexer = ArchiveDescriptorReader::new();
-        let mut i = 1u32;
         let mut entries: Vec<ArchiveFileEntry> = Vec::new();
         loop {
-            let signature = indexer.read_u32(&central_directory_buffer);
-
-            if signature != CENTRAL_DIRECTORY_ENTRY_SIGNATURE {
-   /*              println!(
-                    "I got {:0X}, I expect {:0X}",
-                    signature, CENTRAL_DIRECTORY_ENTRY_SIGNATURE
-                );
+            let index = indexer.get_index();
+            let next_signature = central_directory_buffer
+                .get(index..index + 4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+            let missing_entries = declared_count_known && (entries.len() as u64) < declared_entries;
 
-                println!("{:X?}", central_directory_buffer); */
-                return Err(ArchiveError::BadArchiveStructure(
-                    "Central directory signature not found!".to_owned(),
-                ));
+            if !missing_entries && next_signature != Some(CENTRAL_DIRECTORY_ENTRY_SIGNATURE) {
+                // Whatever follows the entries is padding
+                break;
             }
 
-            let version_made_by = indexer.read_u16(&central_directory_buffer); // Version made by.
-            let version_needed = indexer.read_u16(&central_directory_buffer); // Version needed to extract.
-            let general_purpose_flags = indexer.read_u16(&central_directory_buffer); // General purpose flag (temporary crc and sizes + UTF-8 filename).
-            let compression_method = indexer.read_u16(&central_directory_buffer); // Compression method .
-            let last_mod_file_time = indexer.read_u16(&central_directory_buffer); // Modification time.
-            let last_mod_file_date = indexer.read_u16(&central_directory_buffer); // Modification date.
-            let crc32 = indexer.read_u32(&central_directory_buffer); // CRC32.
-            let compressed_size = indexer.read_u32(&central_directory_buffer) as u64; // Compressed size.
-            let uncompressed_size = indexer.read_u32(&central_directory_buffer) as u64; // Uncompressed size.
-            let file_name_len = indexer.read_u16(&central_directory_buffer); // Filename length.
-            let extra_field_length = indexer.read_u16(&central_directory_buffer); // Extra field length.
-            let file_comment_length = indexer.read_u16(&central_directory_buffer); // File comment length.
-            let file_disk_number = indexer.read_u16(&central_directory_buffer); // File's Disk number.
-            let internal_file_attributes = indexer.read_u16(&central_directory_buffer); // Internal file attributes.
-            let external_file_attributes = indexer.read_u32(&central_directory_buffer); // External file attributes (regular file / rw-r--r--).
-            let file_info_offset = indexer.read_u32(&central_directory_buffer) as u64;
-            let file_name_as_bytes =
-                indexer.read_bytes(&central_directory_buffer, file_name_len as usize);
-
-            let compressor = CompressionMethod::from_compression_method(compression_method)?;
-
-            let mut archive_file_entry = ArchiveFileEntry {
-                version_made_by,
-                minimum_version_needed_to_extract: version_needed,
-                general_purpose_flags,
-                compression_method,
-                last_mod_file_time,
-                last_mod_file_date,
-                crc32,
-                compressed_size,
-                uncompressed_size,
-                file_name_len,
-                extra_field_length,
-                file_name_as_bytes,
-                offset: file_info_offset,
-                compressor,
-                internal_file_attributes,
-                external_file_attributes,
-                file_disk_number: file_disk_number as u32,
-                extra_fields: Vec::new(),
-                file_comment: None,
-            };
+            if next_signature != Some(CENTRAL_DIRECTORY_ENTRY_SIGNATURE) {
+                // Leniently, the next entry is searched for
+                if lenient {
+                    if let Some(next_index) =
+                        find_central_directory_entry(&central_directory_buffer, index + 1)
+                    {
+                        warnings.push(format!(
+                            "No central directory entry signature at index {}, resynchronized at index {}",
+                            index, next_index
+                        ));
+                        indexer.set_index(next_index);
+                        continue;
+                    }
 
-            if extra_field_length != 0 {
-                //TODO avoid copy
-                let extra_field_as_bytes =
-                    indexer.read_bytes(&central_directory_buffer, extra_field_length as usize);
+                    warnings.push(format!(
+                        "The central directory declares {} entries, only {} found",
+                        declared_entries,
+                        entries.len()
+                    ));
+                    break;
+                }
 
-                parse_extra_fields(extra_field_as_bytes, &mut archive_file_entry);
+                return Err(ArchiveError::BadArchiveStructure(format!(
+                    "The central directory declares {} entries, only {} found",
+                    declared_entries,
+                    entries.len()
+                )));
             }
 
-            if file_comment_length != 0 {
-                let file_comment_as_bytes =
-                    indexer.read_bytes(&central_directory_buffer, file_comment_length as usize);
-
-                archive_file_entry.file_comment = Some(file_comment_as_bytes)
+            let parsed = Self::read_central_directory_entry(
+                &mut indexer,
+                &central_directory_buffer,
+                file_length,
+            );
+            match parsed {
+                Ok(archive_file_entry) => entries.push(archive_file_entry),
+                Err(error) if lenient => {
+                    warnings.push(format!(
+                        "The entry at index {} is skipped: {}",
+                        index, error
+                    ));
+                    //resynchronize past its signature
+                    indexer.set_index(index + 4);
+                    continue;
+                }
+                Err(error) => return Err(error),
             }
+        }
 
-            println!("File entry info: {:#?}", archive_file_entry);
-            entries.push(archive_file_entry);
+        if declared_count_known && entries.len() as u64 > declared_entries {
+            warn(
+                warnings,
+                format!(
+                    "The central directory declares {} entries, {} found",
+                    declared_entries,
+                    entries.len()
+                ),
+            );
+        }
 
-            println!("Parsed entry: {}", i);
+        Ok(entries)
+    }
 
-            println!("-------------------------------------------");
-            println!("index {}", indexer.get_index());
+    /// Read the central directory entry at the indexer position.
+    fn read_central_directory_entry(
+        indexer: &mut ArchiveDescriptorReader,
+        central_directory_buffer: &[u8],
+        file_length: u64,
+    ) -> Result<ArchiveFileEntry, ArchiveError> {
+        let signature = indexer.read_u32(central_directory_buffer)?;
 
-            i += 1;
-            if indexer.get_index() + CENTRAL_DIRECTORY_ENTRY_BASE_SIZE as usize
-                >= central_directory_end.central_directory_size as usize
-            {
-                break;
-            }
+        if signature != CENTRAL_DIRECTORY_ENTRY_SIGNATURE {
+            /*              println!(
+                "I got {:0X}, I expect {:0X}",
+                signature, CENTRAL_DIRECTORY_ENTRY_SIGNATURE
+            );
+
+            println!("{:X?}", central_directory_buffer); */
+            return Err(ArchiveError::BadArchiveStructure(
+                "Central directory signature not found!".to_owned(),
+            ));
         }
-        Ok(entries)
+
+        let version_made_by = indexer.read_u16(central_directory_buffer)?; // Version made by.
+        let version_needed = indexer.read_u16(central_directory_buffer)?; // Version needed to extract.
+        let general_purpose_flags = indexer.read_u16(central_directory_buffer)?; // General purpose flag (temporary crc and sizes + UTF-8 filename).
+        let compression_method = indexer.read_u16(central_directory_buffer)?; // Compression method .
+        let last_mod_file_time = indexer.read_u16(central_directory_buffer)?; // Modification time.
+        let last_mod_file_date = indexer.read_u16(central_directory_buffer)?; // Modification date.
+        let crc32 = indexer.read_u32(central_directory_buffer)?; // CRC32.
+        let compressed_size = indexer.read_u32(central_directory_buffer)? as u64; // Compressed size.
+        let uncompressed_size = indexer.read_u32(central_directory_buffer)? as u64; // Uncompressed size.
+        let file_name_len = indexer.read_u16(central_directory_buffer)?; // Filename length.
+        let extra_field_length = indexer.read_u16(central_directory_buffer)?; // Extra field length.
+        let file_comment_length = indexer.read_u16(central_directory_buffer)?; // File comment length.
+        let file_disk_number = indexer.read_u16(central_directory_buffer)?; // File's Disk number.
+        let internal_file_attributes = indexer.read_u16(central_directory_buffer)?; // Internal file attributes.
+        let external_file_attributes = indexer.read_u32(central_directory_buffer)?; // External file attributes (regular file / rw-r--r--).
+        let file_info_offset = indexer.read_u32(central_directory_buffer)? as u64;
+        let file_name_as_bytes =
+            indexer.read_bytes(central_directory_buffer, file_name_len as usize)?;
+
+        // An unsupported method must not prevent reading the other entries
+        let compressor = CompressionMethod::from_compression_method(compression_method)
+            .unwrap_or(CompressionMethod::Unknown(compression_method));
+
+        let mut archive_file_entry = ArchiveFileEntry {
+            version_made_by,
+            minimum_version_needed_to_extract: version_needed,
+            general_purpose_flags,
+            compression_method,
+            last_mod_file_time,
+            last_mod_file_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name_len,
+            extra_field_length,
+            file_name_as_bytes,
+            offset: file_info_offset,
+            compressor,
+            internal_file_attributes,
+            external_file_attributes,
+            file_disk_number: file_disk_number as u32,
+            extra_fields: Vec::new(),
+            file_comment: None,
+            raw_extra_field: Vec::new(),
+            force_zip64: false,
+        };
+
+        if extra_field_length != 0 {
+            //TODO avoid copy
+            let extra_field_as_bytes =
+                indexer.read_bytes(central_directory_buffer, extra_field_length as usize)?;
+            archive_file_entry.raw_extra_field = extra_field_as_bytes.clone();
+
+            parse_extra_fields(extra_field_as_bytes, &mut archive_file_entry)?;
+        }
+
+        if file_comment_length != 0 {
+            let file_comment_as_bytes =
+                indexer.read_bytes(central_directory_buffer, file_comment_length as usize)?;
+
+            archive_file_entry.file_comment = Some(file_comment_as_bytes)
+        }
+
+        // A truncated or forged archive can't hold the entry's payload
+        let payload_end = archive_file_entry
+            .offset
+            .checked_add(archive_file_entry.compressed_size);
+        if payload_end.is_none_or(|end| end > file_length) {
+            return Err(ArchiveError::BadArchiveStructure(format!(
+                "The entry {:?} ends after the end of the archive ({} bytes)",
+                String::from_utf8_lossy(&archive_file_entry.file_name_as_bytes),
+                file_length
+            )));
+        }
+
+        #[cfg(feature = "log")]
+        log::debug!("Central directory entry: {:?}", archive_file_entry);
+        Ok(archive_file_entry)
+    }
+
+    /// Read the zip64 end of central directory record, if the archive has one: its locator is right before the
+    /// end of central directory record at `position`. Returns the record position.
+    ///
+    /// With `relative_offsets`, the record is expected right before its locator, whose offset is relative to the
+    /// archive's unknown start.
+    fn read_zip64_central_directory_end(
+        reader: &mut R,
+        position: u64,
+        relative_offsets: bool,
+        central_directory_end: &mut CentralDirectoryEnd,
+    ) -> Result<Option<u64>, ArchiveError> {
+        let locator_position =
+            match position.checked_sub(ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE) {
+                Some(locator_position) => locator_position,
+                None => return Ok(None),
+            };
+
+        let mut locator = vec![0; ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR_SIZE as usize];
+        reader.seek(SeekFrom::Start(locator_position))?;
+        reader.read_exact(&mut locator)?;
+
+        let mut indexer = ArchiveDescriptorReader::new();
+        if indexer.read_u32(&locator)? != ZIP64_END_OF_CENTRAL_DIR_LOCATOR_SIGNATURE {
+            return Ok(None);
+        }
+        let disk_with_record = indexer.read_u32(&locator)?;
+        let record_offset = indexer.read_u64(&locator)?;
+        let total_number_of_disks = indexer.read_u32(&locator)?;
+
+        let record_position = if relative_offsets {
+            locator_position.checked_sub(ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE)
+        } else {
+            Some(record_offset)
+        };
+        let record_position = record_position.ok_or_else(|| {
+            ArchiveError::BadArchiveStructure(
+                "The zip64 end of central directory record starts before the file".to_owned(),
+            )
+        })?;
+
+        let mut record = vec![0; ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE as usize];
+        reader.seek(SeekFrom::Start(record_position))?;
+        reader.read_exact(&mut record)?;
+
+        let mut indexer = ArchiveDescriptorReader::new();
+        if indexer.read_u32(&record)? != ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE {
+            return Err(ArchiveError::BadArchiveStructure(
+                "Zip64 end of central directory signature not found!".to_owned(),
+            ));
+        }
+        let _record_size = indexer.read_u64(&record)?;
+        let _version_made_by = indexer.read_u16(&record)?;
+        let _version_needed = indexer.read_u16(&record)?;
+        let _disk_number = indexer.read_u32(&record)?;
+        let _disk_with_central_directory = indexer.read_u32(&record)?;
+        central_directory_end.total_number_of_entries_on_this_disk = indexer.read_u64(&record)?;
+        central_directory_end.total_number_of_entries_in_the_central_directory =
+            indexer.read_u64(&record)?;
+        central_directory_end.central_directory_size = indexer.read_u64(&record)?;
+        central_directory_end.offset_of_start_of_central_directory = indexer.read_u64(&record)?;
+
+        central_directory_end
+            .z64ecdl_number_of_the_disk_with_the_start_of_the_zip64_end_of_central_directory =
+            disk_with_record;
+        central_directory_end
+            .z64ecdl_relative_offset_of_the_zip64_end_of_central_directory_record = record_offset;
+        central_directory_end.z64ecdl_total_number_of_disks = total_number_of_disks;
+
+        Ok(Some(record_position))
     }
 
-    fn read_cental_directory_end(stream: &[u8]) -> Result<CentralDirectoryEnd, ArchiveError> {
+    fn read_cental_directory_end(
+        stream: &[u8],
+        warnings: &mut Vec<String>,
+    ) -> Result<CentralDirectoryEnd, ArchiveError> {
         let mut indexer = ArchiveDescriptorReader::new();
 
-        //let _signature = indexer.read_u32(stream);
-        let disk_number = indexer.read_u16(stream) as u32;
-        let disk_with_central_directory = indexer.read_u16(stream) as u32;
-        let total_number_of_entries_on_this_disk = indexer.read_u16(stream) as u64;
-        let total_number_of_entries_in_the_central_directory = indexer.read_u16(stream);
-        let central_directory_size = indexer.read_u32(stream);
-        let offset_of_start_of_central_directory = indexer.read_u32(stream);
-        let zip_file_comment_length = indexer.read_u16(stream);
+        //let _signature = indexer.read_u32(stream)?;
+        let disk_number = indexer.read_u16(stream)? as u32;
+        let disk_with_central_directory = indexer.read_u16(stream)? as u32;
+        let total_number_of_entries_on_this_disk = indexer.read_u16(stream)? as u64;
+        let total_number_of_entries_in_the_central_directory = indexer.read_u16(stream)?;
+        let central_directory_size = indexer.read_u32(stream)?;
+        let offset_of_start_of_central_directory = indexer.read_u32(stream)?;
+        let zip_file_comment_length = indexer.read_u16(stream)?;
+
+        let archive_comment = indexer.read_bytes(stream, zip_file_comment_length as usize)?;
 
-        let archive_comment = indexer.read_bytes(stream, zip_file_comment_length as usize);
+        // Split archives aren't supported: the offsets are read from the start of the file.
+        // Some writers declare other disks for single file archives, read them as disk 0.
+        if disk_number != 0 || disk_with_central_directory != 0 {
+            warn(
+                warnings,
+                format!(
+                    "The archive declares disk {} with the central directory on disk {}, read as a single disk archive",
+                    disk_number, disk_with_central_directory
+                ),
+            );
+        }
 
         let central_directory_end = CentralDirectoryEnd {
-            number_of_this_disk: disk_number,
-            number_of_the_disk_with_central_directory: disk_with_central_directory,
+            number_of_this_disk: 0,
+            number_of_the_disk_with_central_directory: 0,
             total_number_of_entries_on_this_disk,
             total_number_of_entries_in_the_central_directory:
                 total_number_of_entries_in_the_central_directory as u64,
@@ -255,16 +652,76 @@ impl<R: Read + Seek> ArchiveReader<R> {
     }
 }
 
+/// Read a data descriptor followed by a header, returns its CRC-32, compressed and uncompressed sizes.
+fn read_data_descriptor(descriptor: &[u8]) -> Option<(u32, u64, u64)> {
+    let read_u32 = |index: usize| {
+        descriptor
+            .get(index..index + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    let read_u64 =
+        |index: usize| Some(read_u32(index)? as u64 | (read_u32(index + 4)? as u64) << 32);
+    //the descriptor of the last entry is followed by the central directory
+    let is_header = |index: usize| {
+        matches!(
+            read_u32(index),
+            Some(LOCAL_FILE_HEADER_SIGNATURE) | Some(CENTRAL_DIRECTORY_ENTRY_SIGNATURE)
+        )
+    };
+
+    let start = if read_u32(0)? == DATA_DESCRIPTOR_SIGNATURE {
+        4
+    } else {
+        0
+    };
+    let crc32 = read_u32(start)?;
+
+    if is_header(start + 12) {
+        Some((
+            crc32,
+            read_u32(start + 4)? as u64,
+            read_u32(start + 8)? as u64,
+        ))
+    } else if is_header(start + 20) {
+        Some((crc32, read_u64(start + 4)?, read_u64(start + 12)?))
+    } else {
+        None
+    }
+}
+
+/// The index of the next central directory entry signature in the buffer, from `from`.
+fn find_central_directory_entry(central_directory_buffer: &[u8], from: usize) -> Option<usize> {
+    let signature = CENTRAL_DIRECTORY_ENTRY_SIGNATURE.to_le_bytes();
+    central_directory_buffer
+        .get(from..)?
+        .windows(signature.len())
+        .position(|window| window == signature)
+        .map(|position| from + position)
+}
+
 fn parse_extra_fields(
     extra_field_as_bytes: Vec<u8>,
     archive_file_entry: &mut ArchiveFileEntry,
-) -> Vec<Box<dyn ExtraField>> {
+) -> Result<Vec<Box<dyn ExtraField>>, ArchiveError> {
     let mut indexer = ArchiveDescriptorReader::new();
     let extra_fields = Vec::with_capacity(10);
 
     while indexer.get_index() + 4 <= extra_field_as_bytes.len() {
-        let extra_field_header_id = indexer.read_u16(&extra_field_as_bytes);
-        let extra_field_data_size = indexer.read_u16(&extra_field_as_bytes);
+        let extra_field_header_id = indexer.read_u16(&extra_field_as_bytes)?;
+        let extra_field_data_size = indexer.read_u16(&extra_field_as_bytes)?;
+
+        //a field overrunning the extra field bytes is kept truncated, as an unknown one, and ends the parsing
+        let remaining = extra_field_as_bytes.len() - indexer.get_index();
+        if extra_field_data_size as usize > remaining {
+            let ef = ExtraFieldUnknown::parse_extra_field(
+                &mut indexer,
+                &extra_field_as_bytes,
+                remaining as u16,
+                extra_field_header_id,
+            )?;
+            archive_file_entry.extra_fields.push(Box::new(ef));
+            break;
+        }
 
         let extra_field: Box<dyn ExtraField> = match extra_field_header_id {
             ExtraFieldZIP64ExtendedInformation::HEADER_ID => {
@@ -273,7 +730,7 @@ fn parse_extra_fields(
                     &extra_field_as_bytes,
                     extra_field_data_size,
                     archive_file_entry,
-                );
+                )?;
                 Box::new(ef)
             }
             ExtraFieldExtendedTimestamp::HEADER_ID => {
@@ -281,17 +738,25 @@ fn parse_extra_fields(
                     &mut indexer,
                     &extra_field_as_bytes,
                     extra_field_data_size,
-                );
+                )?;
 
                 Box::new(ef)
             }
+            ExtraFieldUnicodePath::HEADER_ID => {
+                let ef = ExtraFieldUnicodePath::parse_extra_field(
+                    &mut indexer,
+                    &extra_field_as_bytes,
+                    extra_field_data_size,
+                )?;
+                Box::new(ef)
+            }
             _ => {
                 let ef = ExtraFieldUnknown::parse_extra_field(
                     &mut indexer,
                     &extra_field_as_bytes,
                     extra_field_data_size,
                     extra_field_header_id,
-                );
+                )?;
                 Box::new(ef)
             }
         };
@@ -299,7 +764,26 @@ fn parse_extra_fields(
         archive_file_entry.extra_fields.push(extra_field);
     }
 
-    extra_fields
+    Ok(extra_fields)
+}
+
+/// Adds the warning to the read's ones, and logs it with the `log` feature.
+fn warn(warnings: &mut Vec<String>, warning: String) {
+    #[cfg(feature = "log")]
+    log::warn!("{}", warning);
+    warnings.push(warning);
+}
+
+/// An archive found by [`ArchiveReader::read_all_members`] in a file holding several concatenated archives.
+#[derive(Debug)]
+pub struct ArchiveMember {
+    /// The position of the archive's first byte in the file
+    pub start: u64,
+    /// The position following the archive's last byte in the file
+    pub end: u64,
+    /// The entries, their offsets are relative to `start`
+    pub file_entries: Vec<ArchiveFileEntry>,
+    pub central_directory_end: CentralDirectoryEnd,
 }
 
 impl<R: Read + Seek> Debug for ArchiveReader<R> {
@@ -339,14 +823,11 @@ impl<R: Read + Seek> Display for ArchiveReader<R> {
 
         writeln!(f, "{:?}", self.central_directory_end)?;
 
-        let mut i = 1u32;
-        for entry in &self.file_entries {
-            writeln!(f, "Central directory entry #{}", i)?;
+        for (i, entry) in self.file_entries.iter().enumerate() {
+            writeln!(f, "Central directory entry #{}", i + 1)?;
             writeln!(f, "---------------------------")?;
             writeln!(f)?;
             writeln!(f, "{}", entry)?;
-
-            i += 1;
         }
 
         writeln!(f, "-- END --")?;
@@ -359,30 +840,219 @@ impl<R: Read + Seek> Display for ArchiveReader<R> {
 mod test {
     use std::io::Cursor;
 
+    use crate::archive_common::{
+        ArchiveDescriptor, ArchiveFileEntry, ExtraField, ExtraFieldZIP64ExtendedInformation,
+    };
+    use crate::compression::CompressionMethod;
     use crate::error::ArchiveError;
 
-    use super::ArchiveReader;
+    use super::{parse_extra_fields, ArchiveReader};
+
+    /// An archive of a single LZMA compressed entry, "short_text_file.txt", written by the rust zip library.
+    const SHORT_TEXT_FILE_ARCHIVE: &[u8] = &[
+        0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00, 0x00,
+        0x82, 0xea, 0xc6, 0x24, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00,
+        0x73, 0x68, 0x6f, 0x72, 0x74, 0x5f, 0x74, 0x65, 0x78, 0x74, 0x5f, 0x66, 0x69, 0x6c, 0x65,
+        0x2e, 0x74, 0x78, 0x74, 0xed, 0xcd, 0xb9, 0x11, 0x00, 0x30, 0x08, 0x03, 0xb0, 0x3e, 0xd3,
+        0xc4, 0xfc, 0xec, 0xbf, 0x18, 0x53, 0x70, 0x47, 0xe1, 0x4e, 0x9d, 0x20, 0x6a, 0x1e, 0x59,
+        0xfd, 0xb1, 0xa6, 0x07, 0x26, 0x4c, 0x98, 0x5c, 0x4c, 0x06, 0x50, 0x4b, 0x01, 0x02, 0x2e,
+        0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00, 0x00, 0x82, 0xea, 0xc6,
+        0x24, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xa4, 0x81, 0x00, 0x00, 0x00, 0x00, 0x73, 0x68, 0x6f, 0x72,
+        0x74, 0x5f, 0x74, 0x65, 0x78, 0x74, 0x5f, 0x66, 0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74,
+        0x50, 0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x41, 0x00, 0x00,
+        0x00, 0x55, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
 
     #[test]
     fn test_mem_dump_rust_zip_lib_lzma() -> Result<(), ArchiveError> {
-        let vec: Vec<u8> = vec![
-            0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00,
-            0x00, 0x82, 0xea, 0xc6, 0x24, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x13, 0x00,
-            0x00, 0x00, 0x73, 0x68, 0x6f, 0x72, 0x74, 0x5f, 0x74, 0x65, 0x78, 0x74, 0x5f, 0x66,
-            0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74, 0xed, 0xcd, 0xb9, 0x11, 0x00, 0x30, 0x08,
-            0x03, 0xb0, 0x3e, 0xd3, 0xc4, 0xfc, 0xec, 0xbf, 0x18, 0x53, 0x70, 0x47, 0xe1, 0x4e,
-            0x9d, 0x20, 0x6a, 0x1e, 0x59, 0xfd, 0xb1, 0xa6, 0x07, 0x26, 0x4c, 0x98, 0x5c, 0x4c,
-            0x06, 0x50, 0x4b, 0x01, 0x02, 0x2e, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00,
-            0x00, 0x21, 0x00, 0x00, 0x82, 0xea, 0xc6, 0x24, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00,
-            0x00, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xa4,
-            0x81, 0x00, 0x00, 0x00, 0x00, 0x73, 0x68, 0x6f, 0x72, 0x74, 0x5f, 0x74, 0x65, 0x78,
-            0x74, 0x5f, 0x66, 0x69, 0x6c, 0x65, 0x2e, 0x74, 0x78, 0x74, 0x50, 0x4b, 0x05, 0x06,
-            0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x41, 0x00, 0x00, 0x00, 0x55, 0x00,
-            0x00, 0x00, 0x00, 0x00,
-        ];
-
-        let buff = Cursor::new(vec);
-        ArchiveReader::new(buff)?;
+        ArchiveReader::new(Cursor::new(SHORT_TEXT_FILE_ARCHIVE))?;
         Ok(())
     }
+
+    #[test]
+    fn test_unknown_compression_method() -> Result<(), ArchiveError> {
+        let mut vec = SHORT_TEXT_FILE_ARCHIVE.to_vec();
+
+        // the central directory starts at 0x55, the compression method is 10 bytes further
+        let compression_method_index = 0x55 + 10;
+        vec[compression_method_index] = 42;
+        vec[compression_method_index + 1] = 0;
+
+        let archive_reader = ArchiveReader::new(Cursor::new(vec))?;
+
+        assert_eq!(archive_reader.file_entries.len(), 1);
+        let entry = &archive_reader.file_entries[0];
+        assert_eq!(entry.get_file_name(), "short_text_file.txt");
+        assert_eq!(entry.compression_method, 42);
+        assert_eq!(entry.compressor, CompressionMethod::Unknown(42));
+        assert_eq!(entry.compressor.to_string(), "unknown");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_zero_disk_number() -> Result<(), ArchiveError> {
+        let mut vec = SHORT_TEXT_FILE_ARCHIVE.to_vec();
+
+        // the end of central directory starts at 0x55 + 0x41, the disk numbers right after the signature
+        let disk_number_index = 0x55 + 0x41 + 4;
+        vec[disk_number_index] = 1;
+        vec[disk_number_index + 2] = 1;
+
+        let archive_reader = ArchiveReader::new(Cursor::new(vec))?;
+
+        assert_eq!(archive_reader.warnings.len(), 1);
+        assert_eq!(archive_reader.central_directory_end.number_of_this_disk, 0);
+        assert_eq!(
+            archive_reader
+                .central_directory_end
+                .number_of_the_disk_with_central_directory,
+            0
+        );
+        assert_eq!(archive_reader.file_entries.len(), 1);
+        assert_eq!(
+            archive_reader.file_entries[0].get_file_name(),
+            "short_text_file.txt"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_central_directory() {
+        let mut vec = SHORT_TEXT_FILE_ARCHIVE.to_vec();
+
+        // declare a central directory shorter than its single entry
+        let central_directory_size_index = 0x55 + 0x41 + 12;
+        vec[central_directory_size_index] = 0x20;
+
+        let result = ArchiveReader::new(Cursor::new(vec));
+
+        assert!(matches!(result, Err(ArchiveError::BadArchiveStructure(_))));
+    }
+
+    #[test]
+    fn test_wrong_number_of_entries() {
+        let mut vec = SHORT_TEXT_FILE_ARCHIVE.to_vec();
+
+        let number_of_entries_index = 0x55 + 0x41 + 10;
+
+        // declare more entries than the central directory holds
+        let mut more = vec.clone();
+        more[number_of_entries_index] = 0x02;
+        let result = ArchiveReader::new(Cursor::new(more));
+        assert!(matches!(result, Err(ArchiveError::BadArchiveStructure(_))));
+
+        // declare fewer entries, the remaining ones are still read
+        vec[number_of_entries_index] = 0x00;
+        let archive_read = ArchiveReader::new(Cursor::new(vec)).unwrap();
+        assert_eq!(archive_read.file_entries.len(), 1);
+        assert_eq!(archive_read.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_compressed_size_past_the_end() {
+        let mut vec = SHORT_TEXT_FILE_ARCHIVE.to_vec();
+
+        // declare a compressed size of 1 MiB in the central directory
+        let compressed_size_index = 0x55 + 20;
+        vec[compressed_size_index + 2] = 0x10;
+
+        let result = ArchiveReader::new(Cursor::new(vec));
+
+        assert!(matches!(result, Err(ArchiveError::BadArchiveStructure(_))));
+    }
+
+    #[test]
+    fn test_zip64_extra_field_large_offset_small_sizes() {
+        const OFFSET: u64 = 5 * 1024 * 1024 * 1024;
+
+        let written_entry = ArchiveFileEntry {
+            uncompressed_size: 100,
+            compressed_size: 50,
+            offset: OFFSET,
+            ..Default::default()
+        };
+
+        let mut extra_field_buffer = ArchiveDescriptor::new(30);
+        ExtraFieldZIP64ExtendedInformation::default()
+            .central_header_extra_write_data(&mut extra_field_buffer, &written_entry);
+
+        //header id, data size and only the offset
+        assert_eq!(extra_field_buffer.len(), 4 + 8);
+
+        //what the central directory classic fields hold
+        let mut read_entry = ArchiveFileEntry {
+            uncompressed_size: written_entry.zip64_uncompressed_size() as u64,
+            compressed_size: written_entry.zip64_compressed_size() as u64,
+            offset: written_entry.zip64_offset() as u64,
+            ..Default::default()
+        };
+
+        parse_extra_fields(extra_field_buffer.bytes().to_vec(), &mut read_entry).unwrap();
+
+        assert_eq!(read_entry.uncompressed_size, 100);
+        assert_eq!(read_entry.compressed_size, 50);
+        assert_eq!(read_entry.offset, OFFSET);
+        assert!(read_entry.has_zip64_extra_field());
+    }
+
+    #[test]
+    fn test_unicode_path_extra_field() {
+        //"réadme.txt" encoded with CP437
+        let file_name = b"r\x82adme.txt";
+        let unicode_name = "réadme.txt".as_bytes();
+
+        let unicode_path_field = |name_crc32: u32| {
+            let mut extra_field_buffer = ArchiveDescriptor::new(30);
+            extra_field_buffer.write_u16(0x7075);
+            extra_field_buffer.write_u16(5 + unicode_name.len() as u16);
+            extra_field_buffer.write_u8(1);
+            extra_field_buffer.write_u32(name_crc32);
+            extra_field_buffer.write_bytes(unicode_name);
+            extra_field_buffer.bytes().to_vec()
+        };
+
+        let mut entry = ArchiveFileEntry {
+            file_name_as_bytes: file_name.to_vec(),
+            ..Default::default()
+        };
+        parse_extra_fields(unicode_path_field(crc32fast::hash(file_name)), &mut entry).unwrap();
+        assert_eq!(entry.get_file_name(), "réadme.txt");
+
+        //a field not matching the header's name, e.g. after a rename, is ignored
+        let mut entry = ArchiveFileEntry {
+            file_name_as_bytes: b"r\x8aadme.txt".to_vec(),
+            ..Default::default()
+        };
+        parse_extra_fields(unicode_path_field(crc32fast::hash(file_name)), &mut entry).unwrap();
+        assert_eq!(entry.get_file_name(), "rèadme.txt");
+    }
+
+    #[test]
+    fn test_truncated_extra_field() {
+        let mut extra_field_buffer = ArchiveDescriptor::new(30);
+        //an extended timestamp, then a field declaring 20 data bytes out of the 3 remaining
+        extra_field_buffer.write_u16(0x5455);
+        extra_field_buffer.write_u16(5);
+        extra_field_buffer.write_u8(1);
+        extra_field_buffer.write_u32(0);
+        extra_field_buffer.write_u16(0x5455);
+        extra_field_buffer.write_u16(20);
+        extra_field_buffer.write_bytes(&[1, 2, 3]);
+
+        let mut entry = ArchiveFileEntry::default();
+        parse_extra_fields(extra_field_buffer.bytes().to_vec(), &mut entry).unwrap();
+
+        assert_eq!(entry.extra_fields.len(), 2);
+        assert!(entry.get_extra_field_time_stamp().is_some());
+
+        //the truncated field is kept as an unknown one, with its remaining data
+        let truncated = &entry.extra_fields[1];
+        assert_eq!(truncated.central_header_extra_field_size(&entry), 3);
+        let mut written = ArchiveDescriptor::new(7);
+        truncated.central_header_extra_write_data(&mut written, &entry);
+        assert_eq!(written.bytes(), &[0x55, 0x54, 3, 0, 1, 2, 3]);
+    }
 }
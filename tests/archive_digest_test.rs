@@ -0,0 +1,60 @@
+use archflow::{
+    compress::FileOptions, compression::CompressionMethod, digest::DigestKind, error::ArchiveError,
+};
+
+mod common;
+
+const PAYLOAD: &[u8] = b"Some string data";
+const PAYLOAD_SHA256: &str = "df9601b0b26a232f6579d31f0342de827288b4a89b27de7e7d778f2ade18d8f8";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn archive_digest_std() -> Result<(), ArchiveError> {
+    use archflow::compress::std::archive::ZipArchive;
+    use common::std::create_new_clean_file;
+
+    let out_file = create_new_clean_file("test_digest.zip");
+    let mut archive = ZipArchive::new_streamable(out_file);
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflate())
+        .with_digest(DigestKind::Sha256);
+    let outcome = archive.append("file1.txt", &options, &mut { PAYLOAD })?;
+
+    assert_eq!(outcome.uncompressed_size, PAYLOAD.len() as u64);
+    assert_eq!(outcome.crc32, crc32fast::hash(PAYLOAD));
+    assert_eq!(to_hex(&outcome.digest.unwrap()), PAYLOAD_SHA256);
+
+    let options = FileOptions::default();
+    let outcome = archive.append("file2.txt", &options, &mut { PAYLOAD })?;
+    assert!(outcome.digest.is_none());
+
+    archive.finalize()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_digest_tokio() -> Result<(), ArchiveError> {
+    use archflow::compress::tokio::archive::ZipArchive;
+    use common::tokio::create_new_clean_file;
+
+    let out_file = create_new_clean_file("test_digest.zip").await;
+    let mut archive = ZipArchive::new(out_file);
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .with_digest(DigestKind::Sha256);
+    let outcome = archive
+        .append("file1.txt", &options, &mut { PAYLOAD })
+        .await?;
+
+    assert_eq!(outcome.compressed_size, PAYLOAD.len() as u64);
+    assert_eq!(outcome.crc32, crc32fast::hash(PAYLOAD));
+    assert_eq!(to_hex(&outcome.digest.unwrap()), PAYLOAD_SHA256);
+
+    archive.finalize().await?;
+    Ok(())
+}
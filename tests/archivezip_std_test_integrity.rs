@@ -1,12 +1,21 @@
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    io::{Cursor, Read, Write},
+    path::Path,
+    time::Duration,
+};
 
 use archflow::{
-    compress::std::archive::ZipArchive, compress::FileOptions, compression::CompressionMethod,
-    error::ArchiveError, uncompress::ArchiveReader,
+    compress::std::archive::ZipArchive,
+    compress::FileOptions,
+    compression::CompressionMethod,
+    error::ArchiveError,
+    types::{FileCompatibilitySystem, FileDateTime},
+    uncompress::ArchiveReader,
 };
 mod common;
 use common::std::create_new_clean_file;
-use common::std::MockReader;
+use common::std::{MockReader, SeekFailingWriter, SlowReader, WriteCounter};
 
 #[test]
 fn archive_multiple() -> Result<(), ArchiveError> {
@@ -247,3 +256,2231 @@ fn archive_multiple_mock_z64_read() -> Result<(), ArchiveError> {
 
     Ok(())
 }
+
+#[test]
+fn archive_finalize_keep_writer() -> Result<(), ArchiveError> {
+    const TRAILER: &[u8] = b"SIGNATURE BLOCK";
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    archive.append("file1.txt", &options, &mut b"Some string data".as_ref())?;
+
+    let (archive_size, mut cursor) = archive.finalize_keep_writer()?;
+
+    assert_eq!(cursor.position(), archive_size);
+    assert_eq!(cursor.get_ref().len() as u64, archive_size);
+
+    cursor.write_all(TRAILER)?;
+
+    let buffer = cursor.into_inner();
+    assert_eq!(buffer.len() as u64, archive_size + TRAILER.len() as u64);
+    assert!(buffer.ends_with(TRAILER));
+
+    //the archive is still readable once the trailer is removed
+    let archive_read = ArchiveReader::new(Cursor::new(&buffer[..archive_size as usize]))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    assert_eq!("file1.txt", archive_read.file_entries[0].get_file_name());
+
+    Ok(())
+}
+
+#[test]
+fn archive_host_os_attributes() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+
+    let options = FileOptions::default().system(FileCompatibilitySystem::Dos);
+    archive.append("dos.txt", &options, &mut b"Some string data".as_ref())?;
+    archive.append_directory("dos_dir", &options)?;
+
+    let options = FileOptions::default().unix_permissions(0o600);
+    archive.append("unix.txt", &options, &mut b"Some string data".as_ref())?;
+
+    let (_, buffer) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let mut iter = archive_read.file_entries.iter();
+
+    let dos_file = iter.next().unwrap();
+    assert_eq!(dos_file.system(), FileCompatibilitySystem::Dos);
+    assert_eq!(dos_file.unix_mode(), None);
+    assert_eq!(dos_file.external_file_attributes, 0);
+    assert!(!dos_file.to_string().contains("Unix file attributes"));
+
+    let dos_dir = iter.next().unwrap();
+    assert_eq!(dos_dir.unix_mode(), None);
+    assert_eq!(dos_dir.external_file_attributes, 0x10);
+
+    let unix_file = iter.next().unwrap();
+    assert_eq!(unix_file.system(), FileCompatibilitySystem::Unix);
+    assert_eq!(unix_file.unix_mode(), Some(0o100600));
+
+    Ok(())
+}
+
+#[test]
+fn archive_made_by_version() -> Result<(), ArchiveError> {
+    //central directory file header signature
+    const CENTRAL_DIRECTORY_SIGNATURE: &[u8] = &[0x50, 0x4b, 0x01, 0x02];
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().made_by_version(63);
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    archive.append_bytes("file2.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+    let buffer = cursor.into_inner();
+
+    //the version made by follows the signature, its high byte is the host OS
+    let central_directory = buffer
+        .windows(4)
+        .position(|window| window == CENTRAL_DIRECTORY_SIGNATURE)
+        .unwrap();
+    assert_eq!(
+        &buffer[central_directory + 4..central_directory + 6],
+        &[63, 3]
+    );
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries[0].version_made_by, 0x033F);
+    assert_eq!(archive_read.file_entries[1].version_made_by, 0x032E);
+
+    Ok(())
+}
+
+#[test]
+fn archive_unix_mode_full() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+
+    let options = FileOptions::default().unix_mode_full(0o4755);
+    archive.append("setuid", &options, &mut b"Some string data".as_ref())?;
+    let options = FileOptions::default().unix_mode_full(0o1777);
+    archive.append_directory("sticky", &options)?;
+    let options = FileOptions::default().unix_permissions(0o4755);
+    archive.append("masked", &options, &mut b"Some string data".as_ref())?;
+
+    let (_, buffer) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+
+    assert_eq!(entries[0].unix_mode(), Some(0o104755));
+    let display = entries[0].to_string();
+    assert!(display.contains("(104755 octal)"), "{}", display);
+    assert!(display.contains("-rwsr-xr-x"), "{}", display);
+
+    assert_eq!(entries[1].unix_mode(), Some(0o41777));
+    assert!(entries[1].to_string().contains("drwxrwxrwt"));
+
+    assert_eq!(entries[2].unix_mode(), Some(0o100755));
+
+    Ok(())
+}
+
+#[test]
+fn archive_known_crc32() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+    let crc32 = crc32fast::hash(PAYLOAD);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .known_crc32(crc32);
+    let outcome = archive.append("known.txt", &options, &mut { PAYLOAD })?;
+
+    assert_eq!(outcome.crc32, crc32);
+    assert_eq!(outcome.uncompressed_size, PAYLOAD.len() as u64);
+    assert_eq!(outcome.compressed_size, PAYLOAD.len() as u64);
+
+    //the provided value is used as is
+    let options = options.known_crc32(0xDEADBEEF);
+    let outcome = archive.append("wrong.txt", &options, &mut { PAYLOAD })?;
+    assert_eq!(outcome.crc32, 0xDEADBEEF);
+
+    //unless verified, the entry is then left out
+    let verified = options.verify_known_crc32(true);
+    let result = archive.append("verified.txt", &verified, &mut { PAYLOAD });
+    assert!(matches!(
+        result,
+        Err(ArchiveError::Crc32Mismatch {
+            expected: 0xDEADBEEF,
+            actual
+        }) if actual == crc32
+    ));
+    let result = archive.append_bytes("verified.txt", &verified, PAYLOAD);
+    assert!(matches!(result, Err(ArchiveError::Crc32Mismatch { .. })));
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries[0].crc32, crc32);
+    assert_eq!(
+        archive_read.file_entries[0].uncompressed_size,
+        PAYLOAD.len() as u64
+    );
+    assert_eq!(archive_read.file_entries[1].crc32, 0xDEADBEEF);
+    assert_eq!(archive_read.file_entries.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn archive_try_set_archive_comment() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    archive.try_set_archive_comment("A fine comment")?;
+    archive.append("file.txt", &FileOptions::default(), &mut { &b"data"[..] })?;
+
+    let too_long = "a".repeat(u16::MAX as usize + 1);
+    match archive.try_set_archive_comment(&too_long) {
+        Err(ArchiveError::CommentTooLong(len)) => assert_eq!(len, u16::MAX as usize + 1),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    let (_, cursor) = archive.finalize()?;
+
+    //the previous comment is kept
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(
+        archive_read.central_directory_end.archive_comment,
+        Some(b"A fine comment".to_vec())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_set_archive_comment_truncated() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    //the 0xFFFF bytes limit falls in the middle of the last "é"
+    let comment = format!("{}é", "a".repeat(u16::MAX as usize - 1));
+    archive.set_archive_comment(&comment);
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let archive_comment = archive_read.central_directory_end.archive_comment.unwrap();
+    assert_eq!(archive_comment.len(), u16::MAX as usize - 1);
+    assert!(std::str::from_utf8(&archive_comment).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn archive_metadata_json() -> Result<(), ArchiveError> {
+    let metadata = serde_json::json!({"tool": "archflow", "build": 42, "tags": ["a", "b"]});
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.set_metadata_json(&metadata)?;
+    archive.with_archive_digest(true)?;
+    archive.append("file.txt", &FileOptions::default(), &mut { &b"data"[..] })?;
+    let (_, cursor) = archive.finalize()?;
+
+    //the digest appended after the metadata is ignored
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.metadata_json()?, Some(metadata));
+
+    //a plain comment holds no metadata
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.set_archive_comment("{\"tool\": \"archflow\"}");
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.metadata_json()?, None);
+
+    Ok(())
+}
+
+#[test]
+fn archive_streamable_overhead() -> Result<(), ArchiveError> {
+    //signature, crc32, compressed and uncompressed sizes
+    const DATA_DESCRIPTOR_SIZE: u64 = 16;
+    const FILE_COUNT: u64 = 5;
+    let payload = b"Some string data".repeat(100);
+
+    for method in [CompressionMethod::Store(), CompressionMethod::Deflate()] {
+        let options = FileOptions::default().compression_method(method);
+
+        let mut seekable = ZipArchive::new(Cursor::new(Vec::new()));
+        let mut streamable = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+        for i in 0..FILE_COUNT {
+            let file_name = format!("file{}.txt", i);
+            seekable.append(&file_name, &options, &mut payload.as_slice())?;
+            streamable.append(&file_name, &options, &mut payload.as_slice())?;
+        }
+
+        let (seekable_size, _) = seekable.finalize()?;
+        let (streamable_size, _) = streamable.finalize()?;
+
+        assert_eq!(
+            streamable_size - seekable_size,
+            FILE_COUNT * DATA_DESCRIPTOR_SIZE
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_all() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let entries: Vec<(String, Box<dyn std::io::Read>)> = vec![
+        ("file1.txt".to_owned(), Box::new(&b"Some string data"[..])),
+        (
+            "file2.txt".to_owned(),
+            Box::new(Cursor::new(vec![b'a'; 100])),
+        ),
+    ];
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    let outcomes = archive.append_all(entries, &options)?;
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].uncompressed_size, 16);
+    assert_eq!(outcomes[1].uncompressed_size, 100);
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+    assert_eq!(archive_read.file_entries[0].get_file_name(), "file1.txt");
+    assert_eq!(archive_read.file_entries[1].get_file_name(), "file2.txt");
+    assert_eq!(archive_read.file_entries[1].crc32, outcomes[1].crc32);
+
+    Ok(())
+}
+
+#[test]
+fn archive_streamable_buffered() -> Result<(), ArchiveError> {
+    const FILE_COUNT: usize = 50;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .last_modified_time(FileDateTime::Zero);
+
+    let mut archives = Vec::new();
+    for buffered in [false, true] {
+        let mut archive = if buffered {
+            ZipArchive::new_streamable_buffered(WriteCounter::default(), 8 * 1024)
+        } else {
+            ZipArchive::new_streamable(WriteCounter::default())
+        };
+        for index in 0..FILE_COUNT {
+            archive.append(&format!("file{index}.txt"), &options, &mut { &b"tiny"[..] })?;
+        }
+        let (archive_size, counter) = archive.finalize()?;
+        assert_eq!(counter.received.len() as u64, archive_size);
+        archives.push(counter);
+    }
+
+    //the same archive, written in far fewer writes
+    assert_eq!(archives[0].received, archives[1].received);
+    assert!(archives[0].write_count > FILE_COUNT * 3);
+    assert!(archives[1].write_count <= 2);
+
+    let archive_read = ArchiveReader::new(Cursor::new(archives.remove(1).received))?;
+    assert_eq!(archive_read.file_entries.len(), FILE_COUNT);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_reader_owned() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    for index in 0..3 {
+        let payload: Box<dyn std::io::Read> = Box::new(Cursor::new(vec![b'a'; index * 10]));
+        archive.append_reader_owned(&format!("file{index}.txt"), &options, payload)?;
+    }
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 3);
+    assert_eq!(archive_read.file_entries[2].get_file_name(), "file2.txt");
+    assert_eq!(archive_read.file_entries[2].uncompressed_size, 20);
+
+    Ok(())
+}
+
+#[test]
+fn archive_force_zip64() -> Result<(), ArchiveError> {
+    //zip64 end of central directory record signature
+    const ZIP64_EOCD_SIGNATURE: &[u8] = &[0x50, 0x4b, 0x06, 0x06];
+    const PAYLOAD: &[u8] = b"Some string data";
+
+    for streamable in [false, true] {
+        let cursor = Cursor::new(Vec::new());
+        let mut archive = if streamable {
+            ZipArchive::new_streamable(cursor)
+        } else {
+            ZipArchive::new(cursor)
+        };
+        archive.force_zip64(true);
+
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+        let outcome = archive.append("file1.txt", &options, &mut { PAYLOAD })?;
+        archive.append("file2.txt", &options, &mut { PAYLOAD })?;
+
+        let (_, cursor) = archive.finalize()?;
+        let buffer = cursor.into_inner();
+
+        assert!(buffer
+            .windows(ZIP64_EOCD_SIGNATURE.len())
+            .any(|w| w == ZIP64_EOCD_SIGNATURE));
+
+        //the sizes are read from the zip64 extra field
+        let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+        assert_eq!(archive_read.file_entries.len(), 2);
+        for entry in &archive_read.file_entries {
+            assert_eq!(entry.minimum_version_needed_to_extract, 45);
+            assert!(entry.has_zip64_extra_field());
+            assert_eq!(entry.uncompressed_size, PAYLOAD.len() as u64);
+            assert_eq!(entry.compressed_size, outcome.compressed_size);
+            assert_eq!(entry.crc32, outcome.crc32);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_virtual_tree() -> Result<(), ArchiveError> {
+    let mut tree = std::collections::BTreeMap::new();
+    tree.insert("site/index.html".to_owned(), b"<html></html>".to_vec());
+    tree.insert("site/css/style.css".to_owned(), b"body {}".to_vec());
+    tree.insert("site/empty/".to_owned(), Vec::new());
+    tree.insert("README".to_owned(), b"Some string data".to_vec());
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_virtual_tree(&tree, &FileOptions::default())?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let names: Vec<String> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+
+    assert_eq!(
+        names,
+        [
+            "README",
+            "site/",
+            "site/css/",
+            "site/css/style.css",
+            "site/empty/",
+            "site/index.html"
+        ]
+    );
+    assert_eq!(archive_read.file_entries[0].uncompressed_size, 16);
+
+    Ok(())
+}
+
+#[test]
+fn archive_entry_comment() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().set_file_comment("This is a store file");
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let options = FileOptions::default().set_file_comment("Fichier compressé");
+    archive.append_bytes("file2.txt", &options, b"Some string data")?;
+    archive.append_bytes("file3.txt", &FileOptions::default(), b"Some string data")?;
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(
+        entries[0].comment().as_deref(),
+        Some("This is a store file")
+    );
+    assert_eq!(entries[1].comment().as_deref(), Some("Fichier compressé"));
+    assert_eq!(entries[2].comment(), None);
+
+    Ok(())
+}
+
+#[test]
+fn archive_entry_comment_bytes() -> Result<(), ArchiveError> {
+    const UTF8_FLAG: u16 = 1 << 11;
+    //"Fichier compressé" in ISO-8859-1
+    const LATIN1: &[u8] = b"Fichier compress\xe9";
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().set_file_comment_bytes(LATIN1);
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let options = FileOptions::default().set_file_comment_bytes("Fichier compressé".as_bytes());
+    archive.append_bytes("file2.txt", &options, b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].file_comment.as_deref(), Some(LATIN1));
+    assert_eq!(entries[0].general_purpose_flags & UTF8_FLAG, 0);
+    assert_eq!(entries[1].comment().as_deref(), Some("Fichier compressé"));
+    assert_ne!(entries[1].general_purpose_flags & UTF8_FLAG, 0);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_bytes_single_pass() -> Result<(), ArchiveError> {
+    //signature, crc32, compressed and uncompressed sizes
+    const DATA_DESCRIPTOR_SIZE: u64 = 16;
+    let payload = b"Some string data".repeat(100);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflate())
+        .last_modified_time(FileDateTime::Zero);
+
+    //for a seekable archive, the result is the same as the patched local header
+    let mut appended = ZipArchive::new(Cursor::new(Vec::new()));
+    appended.append("file.txt", &options, &mut payload.as_slice())?;
+    let (_, appended) = appended.finalize()?;
+
+    let mut single_pass = ZipArchive::new(Cursor::new(Vec::new()));
+    single_pass.append_bytes("file.txt", &options, &payload)?;
+    let (_, single_pass) = single_pass.finalize()?;
+
+    assert_eq!(appended.into_inner(), single_pass.into_inner());
+
+    //for a streamable archive, the data descriptor is not needed
+    let mut appended = ZipArchive::new_streamable(Vec::new());
+    appended.append("file.txt", &options, &mut payload.as_slice())?;
+    let (appended_size, _) = appended.finalize()?;
+
+    let mut single_pass = ZipArchive::new_streamable(Vec::new());
+    let outcome = single_pass.append_bytes("file.txt", &options, &payload)?;
+    let (single_pass_size, buffer) = single_pass.finalize()?;
+
+    assert_eq!(appended_size - single_pass_size, DATA_DESCRIPTOR_SIZE);
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.general_purpose_flags & (1 << 3), 0);
+    assert_eq!(entry.crc32, outcome.crc32);
+    assert_eq!(entry.compressed_size, outcome.compressed_size);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_bytes_multi() -> Result<(), ArchiveError> {
+    let payload = b"<html>Some string data</html>".repeat(100);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    assert!(archive
+        .append_bytes_multi(&[], &options, &payload)?
+        .is_empty());
+    let outcomes =
+        archive.append_bytes_multi(&["index.html", "default.html"], &options, &payload)?;
+    let (_, buffer) = archive.finalize()?;
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].index, 0);
+    assert_eq!(outcomes[1].index, 1);
+    assert_eq!(outcomes[0].crc32, outcomes[1].crc32);
+    assert!(outcomes[0].compressed_size < payload.len() as u64);
+
+    //each name has its own local header and copy of the compressed bytes
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].get_file_name(), "index.html");
+    assert_eq!(entries[1].get_file_name(), "default.html");
+    for (entry, outcome) in entries.iter().zip(&outcomes) {
+        assert_eq!(entry.crc32, outcome.crc32);
+        assert_eq!(entry.compressed_size, outcome.compressed_size);
+        assert_eq!(entry.uncompressed_size, payload.len() as u64);
+    }
+    assert_ne!(entries[0].offset, entries[1].offset);
+
+    Ok(())
+}
+
+#[test]
+fn archive_patch_seek_failure() -> Result<(), ArchiveError> {
+    //the seek back at the end of the second entry, once its local header is patched, fails
+    let mut archive = ZipArchive::new(SeekFailingWriter::new(4));
+    let options = FileOptions::default();
+    archive.append("file1.txt", &options, &mut b"Some string data".as_slice())?;
+    let error = archive
+        .append("file2.txt", &options, &mut b"Other string data".as_slice())
+        .unwrap_err();
+    assert!(
+        matches!(error, ArchiveError::InconsistentArchive(_)),
+        "{:?}",
+        error
+    );
+
+    //the failed entry is left out of the central directory, the archive stays readable
+    let (_, writer) = archive.finalize()?;
+    let archive_read = ArchiveReader::new(Cursor::new(writer.into_inner()))?;
+    let names: Vec<_> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+    assert_eq!(names, ["file1.txt"]);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_manifest() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+    archive.append_directory("dir/", &options)?;
+    archive.append_bytes("dir/file1.txt", &options, b"Some string data")?;
+
+    let outcome = archive.append_manifest("MANIFEST", &options)?;
+    let (_, cursor) = archive.finalize()?;
+
+    let expected = format!(
+        "dir/\t0\t00000000\ndir/file1.txt\t16\t{:08x}\n",
+        crc32fast::hash(b"Some string data")
+    );
+    assert_eq!(outcome.uncompressed_size, expected.len() as u64);
+    assert_eq!(outcome.crc32, crc32fast::hash(expected.as_bytes()));
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 3);
+    assert_eq!(archive_read.file_entries[2].get_file_name(), "MANIFEST");
+
+    Ok(())
+}
+
+#[test]
+fn archive_custom_crc32_hasher() -> Result<(), ArchiveError> {
+    use archflow::crc32::Crc32;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static UPDATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Default)]
+    struct CountingHasher(crc32fast::Hasher);
+
+    impl Crc32 for CountingHasher {
+        fn update(&mut self, buf: &[u8]) {
+            UPDATED_BYTES.fetch_add(buf.len(), Ordering::Relaxed);
+            self.0.update(buf);
+        }
+
+        fn finalize(self) -> u32 {
+            self.0.finalize()
+        }
+    }
+
+    let payload = b"Some string data";
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().crc32_hasher::<CountingHasher>();
+    let outcome = archive.append("file1.txt", &options, &mut payload.as_slice())?;
+    let (_, cursor) = archive.finalize()?;
+
+    assert_eq!(UPDATED_BYTES.load(Ordering::Relaxed), payload.len());
+    assert_eq!(outcome.crc32, crc32fast::hash(payload));
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries[0].crc32, outcome.crc32);
+
+    Ok(())
+}
+
+#[test]
+fn archive_store_empty() -> Result<(), ArchiveError> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    let store_empty = options.clone().store_empty(true);
+
+    for mut archive in [
+        ZipArchive::new(Cursor::new(Vec::new())),
+        ZipArchive::new_streamable(Cursor::new(Vec::new())),
+    ] {
+        archive.append("deflated.txt", &options, &mut b"".as_slice())?;
+        archive.append("empty.txt", &store_empty, &mut b"".as_slice())?;
+        archive.append_bytes("empty_bytes.txt", &store_empty, b"")?;
+        archive.append(
+            "not_empty.txt",
+            &store_empty,
+            &mut b"Some string data".as_slice(),
+        )?;
+        let (_, cursor) = archive.finalize()?;
+
+        let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+        let entries = &archive_read.file_entries;
+
+        assert_eq!(
+            entries[0].compression_method,
+            CompressionMethod::Deflate().zip_code()
+        );
+        assert_ne!(entries[0].compressed_size, 0);
+
+        for entry in &entries[1..3] {
+            assert_eq!(
+                entry.compression_method,
+                CompressionMethod::Store().zip_code()
+            );
+            assert_eq!(entry.compressed_size, 0);
+            assert_eq!(entry.uncompressed_size, 0);
+        }
+
+        assert_eq!(
+            entries[3].compression_method,
+            CompressionMethod::Deflate().zip_code()
+        );
+        assert_eq!(entries[3].uncompressed_size, 16);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_gz_decompressed() -> Result<(), ArchiveError> {
+    use archflow::gz::std::GzStream;
+
+    let (_, mut gz) = GzStream::new(Vec::new()).compress(&mut b"first line\n".as_slice())?;
+    //log rotation often concatenates gzip members
+    let (_, second) = GzStream::new(Vec::new()).compress(&mut b"second line\n".as_slice())?;
+    gz.extend(second);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let outcome =
+        archive.append_gz_decompressed("app.log", &FileOptions::default(), gz.as_slice())?;
+    archive.finalize()?;
+
+    let expected = b"first line\nsecond line\n";
+    assert_eq!(outcome.uncompressed_size, expected.len() as u64);
+    assert_eq!(outcome.crc32, crc32fast::hash(expected));
+
+    Ok(())
+}
+
+#[test]
+fn archive_zip64_data_descriptor_without_signature() -> Result<(), ArchiveError> {
+    let payload = b"Some string data";
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .data_descriptor_signature(false);
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.force_zip64(true);
+    let outcome = archive.append("file1.txt", &options, &mut payload.as_slice())?;
+    let (_, buffer) = archive.finalize()?;
+
+    //local header with the zip64 extra field, then the payload
+    let descriptor_offset = 30 + "file1.txt".len() + 20 + payload.len();
+    let descriptor = &buffer[descriptor_offset..descriptor_offset + 24];
+
+    let mut expected = outcome.crc32.to_le_bytes().to_vec();
+    expected.extend((payload.len() as u64).to_le_bytes());
+    expected.extend((payload.len() as u64).to_le_bytes());
+    assert_eq!(&descriptor[..20], expected.as_slice());
+    //the central directory follows right after
+    assert_eq!(&descriptor[20..], &0x02014b50u32.to_le_bytes());
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.force_zip64(true);
+    archive.append(
+        "file1.txt",
+        &options.data_descriptor_signature(true),
+        &mut payload.as_slice(),
+    )?;
+    let (_, with_signature) = archive.finalize()?;
+    assert_eq!(with_signature.len(), buffer.len() + 4);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_path_auto() -> Result<(), ArchiveError> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Auto());
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_path(
+        "rust-mascot.png",
+        &options,
+        "tests/resources/rust-mascot.png",
+    )?;
+    archive.append_path(
+        "lorem_ipsum.txt",
+        &options,
+        "tests/resources/lorem_ipsum.txt",
+    )?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(
+        entries[0].compression_method,
+        CompressionMethod::Store().zip_code()
+    );
+    assert_eq!(
+        entries[1].compression_method,
+        CompressionMethod::Deflate().zip_code()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_with_archive_digest() -> Result<(), ArchiveError> {
+    const SUFFIX_SIZE: usize = "archflow-crc32:".len() + 8;
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.with_archive_digest(true)?;
+    archive.set_archive_comment("my comment");
+    archive.append_bytes("file1.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, buffer) = archive.finalize()?;
+
+    let (digested, suffix) = buffer.split_at(buffer.len() - SUFFIX_SIZE);
+    assert_eq!(
+        suffix,
+        format!("archflow-crc32:{:08x}", crc32fast::hash(digested)).as_bytes()
+    );
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    let comment = archive_read.central_directory_end.archive_comment.unwrap();
+    assert!(comment.starts_with(b"my comment"));
+    assert!(comment.ends_with(suffix));
+
+    //a seekable archive patches the bytes already written
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    assert!(matches!(
+        archive.with_archive_digest(true),
+        Err(ArchiveError::Unsupported(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn archive_sink_counter() -> Result<(), ArchiveError> {
+    use archflow::compress::sink::SinkCounter;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    let payload = b"Some string data".repeat(10);
+
+    let mut archive = ZipArchive::new(SinkCounter::new());
+    archive.append("file1.txt", &options, &mut payload.as_slice())?;
+    let (_, counter) = archive.finalize()?;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append("file1.txt", &options, &mut payload.as_slice())?;
+    let (_, cursor) = archive.finalize()?;
+
+    assert_eq!(counter.count(), cursor.into_inner().len() as u64);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_seekable() -> Result<(), ArchiveError> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut payload = Cursor::new(b"skipSome string data".to_vec());
+    payload.seek(SeekFrom::Start(4))?;
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let outcome = archive.append_seekable("file1.txt", &FileOptions::default(), &mut payload)?;
+    let (_, buffer) = archive.finalize()?;
+
+    assert_eq!(outcome.uncompressed_size, 16);
+    assert_eq!(outcome.crc32, crc32fast::hash(b"Some string data"));
+
+    //the local header is complete, there is no data descriptor
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.general_purpose_flags & (1 << 3), 0);
+    assert_eq!(entry.uncompressed_size, 16);
+
+    Ok(())
+}
+
+#[test]
+fn archive_dir_permissions() -> Result<(), ArchiveError> {
+    const S_IFREG: u32 = 0o100000;
+    const S_IFDIR: u32 = 0o040000;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default();
+    archive.append_directory("default/", &options)?;
+    archive.append_bytes("default/file.txt", &options, b"Some string data")?;
+
+    let options = FileOptions::default().unix_permissions(0o700);
+    archive.append_directory("shared/", &options)?;
+    archive.append_bytes("shared/file.txt", &options, b"Some string data")?;
+
+    let options = FileOptions::default()
+        .unix_permissions(0o600)
+        .dir_permissions(0o4750);
+    archive.append_directory("distinct/", &options)?;
+    archive.append_bytes("distinct/file.txt", &options, b"Some string data")?;
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let modes: Vec<Option<u32>> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.unix_mode())
+        .collect();
+
+    assert_eq!(
+        modes,
+        [
+            Some(S_IFDIR | 0o755),
+            Some(S_IFREG | 0o644),
+            Some(S_IFDIR | 0o700),
+            Some(S_IFREG | 0o700),
+            Some(S_IFDIR | 0o750),
+            Some(S_IFREG | 0o600),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_raw_extra_field() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().time_stamp(Some(1_700_000_000), None, None);
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entry = &archive_read.file_entries[0];
+
+    //the extended timestamp extra field, kept as read
+    assert_eq!(
+        entry.raw_extra_field.len(),
+        entry.extra_field_length as usize
+    );
+    assert_eq!(&entry.raw_extra_field[..2], &0x5455u16.to_le_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_bytes_store() -> Result<(), ArchiveError> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .last_modified_time(FileDateTime::Zero);
+
+    let mut appended = ZipArchive::new(Cursor::new(Vec::new()));
+    let mut stored = ZipArchive::new(Cursor::new(Vec::new()));
+    for (file_name, payload) in [
+        ("config.toml", b"key = \"value\"\n".as_slice()),
+        ("empty.txt", b"".as_slice()),
+        ("binary.bin", [0u8, 1, 2, 3].as_slice()),
+    ] {
+        appended.append(file_name, &options, &mut { payload })?;
+        let outcome = stored.append_bytes(file_name, &options, payload)?;
+        assert_eq!(outcome.crc32, crc32fast::hash(payload));
+    }
+
+    let (_, appended) = appended.finalize()?;
+    let (_, stored) = stored.finalize()?;
+    assert_eq!(appended.into_inner(), stored.into_inner());
+
+    Ok(())
+}
+
+#[test]
+fn archive_max_entry_size() -> Result<(), ArchiveError> {
+    let payload = b"Some string data";
+    let limited = FileOptions::default().max_entry_size(Some(10));
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    let result = archive.append("too_big.txt", &limited, &mut payload.as_slice());
+    assert!(matches!(
+        result,
+        Err(ArchiveError::EntrySizeLimitExceeded(10))
+    ));
+    let result = archive.append_bytes("too_big.bin", &limited, payload);
+    assert!(matches!(
+        result,
+        Err(ArchiveError::EntrySizeLimitExceeded(10))
+    ));
+
+    archive.append(
+        "file1.txt",
+        &FileOptions::default(),
+        &mut payload.as_slice(),
+    )?;
+    let (_, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.file_name_as_bytes, b"file1.txt");
+
+    //the valid entry is written after the aborted one
+    let offset = entry.offset as usize;
+    assert!(offset > 0);
+    assert_eq!(&buffer[offset..offset + 4], &0x04034b50u32.to_le_bytes());
+
+    Ok(())
+}
+
+/// Pseudo random, hence incompressible, bytes.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn archive_adaptive() -> Result<(), ArchiveError> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflate())
+        .adaptive(true);
+
+    let noise = noise(100_000);
+    let mut text = File::open("tests/resources/lorem_ipsum.txt")?;
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.append("noise.bin", &options, &mut noise.as_slice())?;
+    archive.append_bytes("noise_bytes.bin", &options, &noise)?;
+    archive.append("lorem_ipsum.txt", &options, &mut text)?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let methods: Vec<u16> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.compression_method)
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            CompressionMethod::Store().zip_code(),
+            CompressionMethod::Store().zip_code(),
+            CompressionMethod::Deflate().zip_code()
+        ]
+    );
+    assert_eq!(archive_read.file_entries[0].compressed_size, 100_000);
+    assert_eq!(archive_read.file_entries[0].crc32, crc32fast::hash(&noise));
+
+    Ok(())
+}
+
+#[test]
+fn archive_finalize_report() -> Result<(), ArchiveError> {
+    let options = FileOptions::default();
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    archive.append_bytes("file2.txt", &options, b"Some other data")?;
+    let report = archive.finalize_report()?;
+
+    assert_eq!(report.entry_count, 2);
+    assert!(!report.used_zip64);
+    assert_eq!(report.size, report.writer.get_ref().len() as u64);
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.force_zip64(true);
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let report = archive.finalize_report()?;
+
+    assert_eq!(report.entry_count, 1);
+    assert!(report.used_zip64);
+
+    Ok(())
+}
+
+#[test]
+fn archive_sort_central_directory_by_name() -> Result<(), ArchiveError> {
+    let options = FileOptions::default();
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.sort_central_directory_by_name(true);
+    for file_name in ["c.txt", "a.txt", "b/", "b/d.txt"] {
+        archive.append_bytes(file_name, &options, file_name.as_bytes())?;
+    }
+    let (_, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    let names: Vec<&[u8]> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.file_name_as_bytes.as_slice())
+        .collect();
+    assert_eq!(names, [b"a.txt".as_slice(), b"b/", b"b/d.txt", b"c.txt"]);
+
+    //the local headers are still in the append order
+    let offsets: Vec<u64> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.offset)
+        .collect();
+    assert_eq!(offsets[3], 0);
+    assert!(offsets[0] < offsets[1] && offsets[1] < offsets[2]);
+    for entry in &archive_read.file_entries {
+        let name_start = entry.offset as usize + 30;
+        let name_end = name_start + entry.file_name_len as usize;
+        assert_eq!(&buffer[name_start..name_end], entry.file_name_as_bytes);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_million_entries() -> Result<(), ArchiveError> {
+    use archflow::compress::sink::SinkCounter;
+    use archflow::overhead::{central_header_overhead, eocd_overhead, local_header_overhead};
+
+    const ENTRY_COUNT: usize = 1_000_000;
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+
+    //the archive bytes are counted, not kept: only the entries' metadata is held in memory
+    let mut archive = ZipArchive::new(SinkCounter::new());
+    for i in 0..ENTRY_COUNT {
+        archive.append_bytes(&format!("{:07}", i), &options, b"x")?;
+    }
+    let report = archive.finalize_report()?;
+
+    assert_eq!(report.entry_count, ENTRY_COUNT);
+    assert!(report.used_zip64);
+
+    let entry_size = local_header_overhead(7, 0) + 1 + central_header_overhead(7, 0, 0);
+    let expected = ENTRY_COUNT as u64 * entry_size + eocd_overhead(0, true);
+    assert_eq!(report.size, expected);
+    assert_eq!(report.writer.count(), expected);
+
+    Ok(())
+}
+
+#[test]
+fn archive_without_extended_timestamp() -> Result<(), ArchiveError> {
+    let options = FileOptions::default()
+        .time_stamp(Some(1_700_000_000), Some(1_700_000_000), None)
+        .extended_timestamp(false);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    archive.append_directory("dir/", &options)?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    for entry in &archive_read.file_entries {
+        assert_eq!(entry.extra_field_length, 0);
+        assert!(entry.extra_fields.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_text_file() -> Result<(), ArchiveError> {
+    let text = b"Some string data";
+    let binary = [0u8, 1, 2, 3];
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append(
+        "detected.txt",
+        &FileOptions::default(),
+        &mut text.as_slice(),
+    )?;
+    let options = FileOptions::default().detect_file_type(false);
+    archive.append("undetected.txt", &options, &mut text.as_slice())?;
+    let options = FileOptions::default().text_file(true);
+    archive.append("text.bin", &options, &mut binary.as_slice())?;
+    archive.append_bytes("text_bytes.bin", &options, &binary)?;
+    let options = FileOptions::default()
+        .detect_file_type(true)
+        .text_file(false);
+    archive.append("binary.txt", &options, &mut text.as_slice())?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let texts: Vec<bool> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.internal_file_attributes & 1 == 1)
+        .collect();
+    assert_eq!(texts, [true, false, true, true, false]);
+
+    Ok(())
+}
+
+#[test]
+fn archive_tee() -> Result<(), ArchiveError> {
+    use archflow::compress::tee::{TeeFailure, TeeWriter};
+
+    //the first writer fails once the local header is written
+    let tee = TeeWriter::new(Cursor::new([0u8; 40]), Vec::new()).on_failure(TeeFailure::Continue);
+    let mut archive = ZipArchive::new_streamable(tee);
+
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    archive.append("file1.txt", &FileOptions::default(), &mut in_file)?;
+    archive.append_bytes("file2.txt", &FileOptions::default(), b"Some string data")?;
+    let (archive_size, tee) = archive.finalize()?;
+
+    assert!(tee.first_error().is_some());
+    let (_, uploaded) = tee.into_inner();
+    assert_eq!(archive_size, uploaded.len() as u64);
+
+    let archive_read = ArchiveReader::new(Cursor::new(uploaded))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+
+    let tee = TeeWriter::new(Cursor::new([0u8; 40]), Vec::new());
+    let mut archive = ZipArchive::new_streamable(tee);
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    assert!(archive
+        .append("file1.txt", &FileOptions::default(), &mut in_file)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_and_capture() -> Result<(), ArchiveError> {
+    let lorem_ipsum = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let mut capture = Vec::new();
+    let options = FileOptions::default().compression_method(CompressionMethod::Zstd());
+    let outcome = archive.append_and_capture(
+        "file1.txt",
+        &options,
+        &mut lorem_ipsum.as_slice(),
+        &mut capture,
+    )?;
+    let (_, cursor) = archive.finalize()?;
+
+    assert_eq!(capture, lorem_ipsum);
+    assert_eq!(outcome.crc32, crc32fast::hash(&capture));
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries[0].crc32, outcome.crc32);
+
+    Ok(())
+}
+
+#[test]
+fn archive_empty() -> Result<(), ArchiveError> {
+    for force_zip64 in [false, true] {
+        let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+        archive.force_zip64(force_zip64);
+        let (archive_size, cursor) = archive.finalize()?;
+        let buffer = cursor.into_inner();
+        assert_eq!(archive_size, buffer.len() as u64);
+
+        let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+        assert!(archive_read.file_entries.is_empty());
+        let central_directory_end = &archive_read.central_directory_end;
+        assert_eq!(
+            central_directory_end.total_number_of_entries_in_the_central_directory,
+            0
+        );
+        assert_eq!(central_directory_end.central_directory_size, 0);
+    }
+
+    //only the end of central directory record
+    let archive = ZipArchive::new_streamable(Vec::new());
+    let (archive_size, buffer) = archive.finalize()?;
+    assert_eq!(archive_size, 22);
+    assert_eq!(&buffer[..4], b"PK\x05\x06");
+    assert!(buffer[4..].iter().all(|byte| *byte == 0));
+
+    Ok(())
+}
+
+#[test]
+fn archive_default_compression_level() -> Result<(), ArchiveError> {
+    use archflow::compression::Level;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.default_compression_level(Level::Best);
+
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    archive.append("best.txt", &FileOptions::default(), &mut in_file)?;
+    let options = FileOptions::default().compression_level(Level::Fastest);
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    archive.append("fastest.txt", &options, &mut in_file)?;
+    let options = FileOptions::default().compression_level(Level::None);
+    archive.append_bytes("none.txt", &options, b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    //the deflate level is recorded in the general purpose flags
+    assert_eq!(entries[0].general_purpose_flags & 0b110, 0b010);
+    assert_eq!(entries[1].general_purpose_flags & 0b110, 0b100);
+    assert!(entries[1].compressed_size > entries[0].compressed_size);
+    assert_eq!(entries[2].compressor, CompressionMethod::Store());
+
+    Ok(())
+}
+
+#[test]
+fn archive_path_separator() -> Result<(), ArchiveError> {
+    use archflow::types::PathSeparator;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes(
+        "dir/file1.txt",
+        &FileOptions::default(),
+        b"Some string data",
+    )?;
+    let options = FileOptions::default().path_separator(PathSeparator::Backward);
+    archive.append_bytes("dir/file2.txt", &options, b"Some string data")?;
+    archive.append_directory("dir/sub/", &options)?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let names: Vec<&[u8]> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.file_name_as_bytes.as_slice())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            b"dir/file1.txt".as_slice(),
+            b"dir\\file2.txt",
+            b"dir\\sub\\"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_file_modified_time() -> Result<(), ArchiveError> {
+    use common::std::get_out_file;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const MODIFIED: i32 = 1_600_000_000;
+
+    let mut file = create_new_clean_file("test_append_file.txt");
+    file.write_all(b"Some string data")?;
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(MODIFIED as u64))?;
+    drop(file);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let mut file = File::open(get_out_file("test_append_file.txt"))?;
+    archive.append_file("file_time.txt", &FileOptions::default(), &mut file)?;
+    let options = FileOptions::default().last_modified_time(FileDateTime::Zero);
+    let mut file = File::open(get_out_file("test_append_file.txt"))?;
+    archive.append_file("zero_time.txt", &options, &mut file)?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let times: Vec<(u16, u16)> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| (entry.last_mod_file_date, entry.last_mod_file_time))
+        .collect();
+    assert_eq!(
+        times,
+        [
+            FileDateTime::UnixCustom(MODIFIED).ms_dos(),
+            FileDateTime::Zero.ms_dos()
+        ]
+    );
+    assert!(archive_read.file_entries[0]
+        .get_extra_field_time_stamp()
+        .is_some());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn archive_append_path_executable() -> Result<(), ArchiveError> {
+    use common::std::get_out_file;
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut file = create_new_clean_file("test_script.sh");
+    file.write_all(b"#!/bin/sh\necho hello\n")?;
+    file.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+    drop(file);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+    archive.append_path("script.sh", &options, get_out_file("test_script.sh"))?;
+    archive.append_path(
+        "lorem_ipsum.txt",
+        &options,
+        "tests/resources/lorem_ipsum.txt",
+    )?;
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let script = &archive_read.file_entries[0];
+    assert_eq!(script.unix_mode(), Some(0o100755));
+    assert!(script.to_string().contains("rwxr-xr-x"));
+
+    //a file without execute bits keeps the default permissions
+    let text = &archive_read.file_entries[1];
+    assert_eq!(text.unix_mode(), Some(0o100644));
+
+    Ok(())
+}
+
+#[test]
+fn archive_seekable_compressed_sizes() -> Result<(), ArchiveError> {
+    use common::assert_compressed_sizes;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    archive.append("deflate.txt", &FileOptions::default(), &mut in_file)?;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+    archive.append("store.txt", &options, &mut b"Some string data".as_slice())?;
+    archive.append("empty.txt", &options, &mut b"".as_slice())?;
+
+    //the zip64 extra field is patched as well
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::BZip2())
+        .large_file(true);
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt")?;
+    archive.append("large.txt", &options, &mut in_file)?;
+    archive.append_bytes("bytes.txt", &options, b"Some string data")?;
+
+    let (_, cursor) = archive.finalize()?;
+    assert_compressed_sizes(&cursor.into_inner())
+}
+
+#[test]
+fn archive_options_from_entry() -> Result<(), ArchiveError> {
+    use archflow::types::DateTimeCS;
+
+    //2023-06-15 10:30:42
+    let date_time = DateTimeCS::from_msdos(43 << 9 | 6 << 5 | 15, 10 << 11 | 30 << 5 | 21);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::BZip2())
+        .last_modified_time(FileDateTime::Custom(date_time))
+        .unix_permissions(0o600)
+        .set_file_comment("file comment");
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+    let original = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+
+    //re-pack the entry
+    let entry = &original.file_entries[0];
+    let options = FileOptions::from_entry(entry);
+    assert_eq!(options.compression_method, CompressionMethod::BZip2());
+    assert_eq!(options.comment, Some(b"file comment".as_slice()));
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+    let repacked = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+
+    let repacked_entry = &repacked.file_entries[0];
+    assert_eq!(repacked_entry.compression_method, entry.compression_method);
+    assert_eq!(repacked_entry.last_mod_file_date, entry.last_mod_file_date);
+    assert_eq!(repacked_entry.last_mod_file_time, entry.last_mod_file_time);
+    assert_eq!(
+        repacked_entry.external_file_attributes,
+        entry.external_file_attributes
+    );
+    assert_eq!(repacked_entry.unix_mode(), Some(0o100600));
+    assert_eq!(repacked_entry.file_comment, entry.file_comment);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_raw() -> Result<(), ArchiveError> {
+    use flate2::{write::DeflateEncoder, Compression};
+
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&payload)?;
+    let compressed = encoder.finish()?;
+    let crc32 = crc32fast::hash(&payload);
+
+    let options = FileOptions::default();
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.report_duplicates(true);
+    archive.append_bytes("lorem_ipsum.txt", &options, &payload)?;
+    for file_name in ["copy/lorem_ipsum.txt", "lorem_ipsum.md"] {
+        let outcome = archive.append_raw(
+            file_name,
+            &options,
+            CompressionMethod::Deflate(),
+            crc32,
+            payload.len() as u64,
+            &compressed,
+        )?;
+        assert_eq!(outcome.compressed_size, compressed.len() as u64);
+    }
+    //both share the name stem, the base name without extension
+    let duplicates: Vec<&str> = archive
+        .duplicates()
+        .iter()
+        .map(|(duplicate, first)| {
+            assert_eq!(first, "lorem_ipsum.txt");
+            duplicate.as_str()
+        })
+        .collect();
+    assert_eq!(duplicates, ["copy/lorem_ipsum.txt", "lorem_ipsum.md"]);
+    let (_, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    common::assert_compressed_sizes(&buffer)?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    let original = &archive_read.file_entries[0];
+    for entry in &archive_read.file_entries[1..] {
+        assert_eq!(entry.compression_method, original.compression_method);
+        assert_eq!(entry.crc32, original.crc32);
+        assert_eq!(entry.compressed_size, original.compressed_size);
+        assert_eq!(entry.uncompressed_size, original.uncompressed_size);
+
+        //no data descriptor, even for a streamable archive
+        let flags = &buffer[entry.offset as usize + 6..entry.offset as usize + 8];
+        assert_eq!(u16::from_le_bytes([flags[0], flags[1]]) & 0x08, 0);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_unix_owner() -> Result<(), ArchiveError> {
+    use archflow::types::UidGidFormat;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .extended_timestamp(false);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("no_owner.txt", &options, b"Some string data")?;
+    let owner = options.clone().unix_owner(1000, 1000);
+    archive.append_bytes("any_size.txt", &owner, b"Some string data")?;
+    let owner_16 = owner.unix_uid_gid_format(UidGidFormat::Unix16);
+    archive.append_bytes("unix16.txt", &owner_16, b"Some string data")?;
+    let too_large = options
+        .unix_owner(70000, 1000)
+        .unix_uid_gid_format(UidGidFormat::Unix16);
+    archive.append_bytes("too_large.txt", &too_large, b"Some string data")?;
+    let (archive_size, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let central_lengths: Vec<u16> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.extra_field_length)
+        .collect();
+    assert_eq!(central_lengths, [0, 15, 4, 15]);
+
+    //each local header holds the whole field: 0, 15, 8 and 15 bytes
+    let names_len: u64 = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.file_name_len as u64)
+        .sum();
+    let payloads_len = 4 * b"Some string data".len() as u64;
+    let central_len: u64 = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| 46 + entry.file_name_len as u64 + entry.extra_field_length as u64)
+        .sum();
+    assert_eq!(
+        archive_size,
+        4 * 30 + names_len + (15 + 8 + 15) + payloads_len + central_len + 22
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_finalize_with_progress() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let options = FileOptions::default().extended_timestamp(false);
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    archive.append_bytes("dir/file2.txt", &options, b"Some string data")?;
+
+    let mut steps = Vec::new();
+    let (archive_size, _) =
+        archive.finalize_with_progress(|index, written| steps.push((index, written)))?;
+
+    //a central directory header is 46 bytes plus the name, the end record is 22 bytes
+    assert_eq!(steps, [(0, 46 + 9), (1, 46 + 9 + 46 + 13)]);
+    assert!(archive_size > steps[1].1 + 22);
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_sized() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let outcome = archive.append_sized("exact.txt", &options, 16, &mut { PAYLOAD })?;
+    assert_eq!(outcome.uncompressed_size, 16);
+
+    match archive.append_sized("truncated.txt", &options, 20, &mut { PAYLOAD }) {
+        Err(ArchiveError::LengthMismatch {
+            expected,
+            actual,
+            crc32,
+        }) => {
+            assert_eq!((expected, actual), (20, 16));
+            assert_eq!(crc32, crc32fast::hash(PAYLOAD));
+        }
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    match archive.append_sized("overrun.txt", &options, 4, &mut { PAYLOAD }) {
+        Err(ArchiveError::LengthMismatch {
+            expected,
+            actual,
+            crc32,
+        }) => {
+            assert_eq!((expected, actual), (4, 16));
+            assert_eq!(crc32, crc32fast::hash(&PAYLOAD[..4]));
+        }
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let (_, buffer) = archive.finalize()?;
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    assert_eq!(archive_read.file_entries[0].uncompressed_size, 16);
+
+    Ok(())
+}
+
+#[test]
+fn archive_inline_store_size() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+
+    for inline_store_size in [true, false] {
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Store())
+            .inline_store_size(inline_store_size);
+
+        let mut archive = ZipArchive::new_streamable(Vec::new());
+        archive.append_sized("file.txt", &options, 16, &mut { PAYLOAD })?;
+        let (_, buffer) = archive.finalize()?;
+
+        //the local header holds the sizes, or zeros before a data descriptor
+        let expected_size = if inline_store_size { 16u32 } else { 0 };
+        assert_eq!(&buffer[18..22], &expected_size.to_le_bytes());
+        assert_eq!(&buffer[22..26], &expected_size.to_le_bytes());
+
+        let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+        let entry = &archive_read.file_entries[0];
+        assert_eq!(entry.flags().has_data_descriptor, !inline_store_size);
+        assert_eq!(entry.crc32, crc32fast::hash(PAYLOAD));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_read_all_members() -> Result<(), ArchiveError> {
+    let mut first = ZipArchive::new_streamable(Vec::new());
+    first.append_bytes("a/file1.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, mut combined) = first.finalize()?;
+    let first_len = combined.len() as u64;
+
+    let mut second = ZipArchive::new(Cursor::new(Vec::new()));
+    second.set_archive_comment("second");
+    second.append_bytes("b/file1.txt", &FileOptions::default(), b"Some string data")?;
+    second.append_bytes("b/file2.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, cursor) = second.finalize()?;
+    combined.extend_from_slice(&cursor.into_inner());
+
+    let members = ArchiveReader::read_all_members(Cursor::new(combined.clone()))?;
+    assert_eq!(members.len(), 2);
+    assert_eq!((members[0].start, members[0].end), (0, first_len));
+    assert_eq!(members[1].start, first_len);
+    assert_eq!(members[1].end, combined.len() as u64);
+
+    let names: Vec<Vec<&[u8]>> = members
+        .iter()
+        .map(|member| {
+            member
+                .file_entries
+                .iter()
+                .map(|entry| entry.file_name_as_bytes.as_slice())
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        [
+            vec![b"a/file1.txt".as_slice()],
+            vec![b"b/file1.txt".as_slice(), b"b/file2.txt"]
+        ]
+    );
+
+    //the entries' offsets are relative to their archive
+    assert_eq!(members[1].file_entries[0].offset, 0);
+    let local_header = members[1].start + members[1].file_entries[1].offset;
+    assert_eq!(
+        &combined[local_header as usize..local_header as usize + 4],
+        b"PK\x03\x04"
+    );
+
+    //a single archive is a single member
+    let single =
+        ArchiveReader::read_all_members(Cursor::new(combined[..first_len as usize].to_vec()))?;
+    assert_eq!(single.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn archive_current_offset() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    assert_eq!(archive.current_offset(), 0);
+
+    let mut offsets = Vec::new();
+    for name in ["file1.txt", "file2.txt", "file3.txt"] {
+        offsets.push(archive.current_offset());
+        archive.append(
+            name,
+            &FileOptions::default(),
+            &mut b"Some string data".as_slice(),
+        )?;
+    }
+    archive.append_directory("dir/", &FileOptions::default())?;
+    let (_, buffer) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entry_offsets: Vec<u64> = archive_read.file_entries[..3]
+        .iter()
+        .map(|entry| entry.offset)
+        .collect();
+    assert_eq!(entry_offsets, offsets);
+
+    Ok(())
+}
+
+#[test]
+fn archive_set_entry_comment() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let mut indexes = Vec::new();
+    for name in ["file1.txt", "file2.txt", "file3.txt"] {
+        let outcome = archive.append_bytes(name, &FileOptions::default(), b"Some string data")?;
+        indexes.push(outcome.index);
+    }
+    assert_eq!(indexes, [0, 1, 2]);
+
+    archive.set_entry_comment(indexes[2], "Fichier compressé")?;
+    archive.set_entry_comment(indexes[0], "This is a store file")?;
+    assert!(matches!(
+        archive.set_entry_comment(3, "No such entry"),
+        Err(ArchiveError::EntryNotFound(3))
+    ));
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(
+        entries[0].comment().as_deref(),
+        Some("This is a store file")
+    );
+    assert_eq!(entries[1].comment(), None);
+    assert_eq!(entries[2].comment().as_deref(), Some("Fichier compressé"));
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_precompressed_frames() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let (head, tail) = payload.split_at(payload.len() / 2);
+    //two zstd frames, from separate compressions
+    let frames = [zstd::encode_all(head, 0)?, zstd::encode_all(tail, 0)?];
+    let compressed_size = (frames[0].len() + frames[1].len()) as u64;
+    let crc32 = crc32fast::hash(&payload);
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let outcome = archive.append_precompressed_frames(
+        "lorem_ipsum.txt",
+        &FileOptions::default(),
+        CompressionMethod::Zstd(),
+        crc32,
+        payload.len() as u64,
+        compressed_size,
+        frames.iter().map(Vec::as_slice),
+    )?;
+    assert_eq!(outcome.compressed_size, compressed_size);
+
+    //the declared length doesn't match the frames
+    let error = archive
+        .append_precompressed_frames(
+            "short.txt",
+            &FileOptions::default(),
+            CompressionMethod::Zstd(),
+            crc32,
+            payload.len() as u64,
+            compressed_size,
+            frames[..1].iter().map(Vec::as_slice),
+        )
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        ArchiveError::LengthMismatch { actual, .. } if actual == frames[0].len() as u64
+    ));
+    let (_, buffer) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.crc32, crc32);
+    assert_eq!(entry.compressed_size, compressed_size);
+
+    //the frames are written one after the other
+    let header = &buffer[entry.offset as usize..];
+    let extra_len = u16::from_le_bytes([header[28], header[29]]) as usize;
+    let payload_begin = entry.offset as usize + 30 + entry.file_name_len as usize + extra_len;
+    let written = &buffer[payload_begin..payload_begin + compressed_size as usize];
+    assert_eq!(zstd::decode_all(written)?, payload);
+
+    Ok(())
+}
+
+#[test]
+fn archive_align_stored_entries() -> Result<(), ArchiveError> {
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .align(4);
+    let deflated = stored
+        .clone()
+        .compression_method(CompressionMethod::Deflate());
+
+    for streamable in [false, true] {
+        let mut archive = if streamable {
+            ZipArchive::new_streamable(Cursor::new(Vec::new()))
+        } else {
+            ZipArchive::new(Cursor::new(Vec::new()))
+        };
+
+        for name in ["a", "ab.txt", "abc.txt", "dir/abcd.txt"] {
+            archive.append_bytes(name, &stored, b"Some string data")?;
+            archive.append(name, &stored, &mut b"Some string data".as_slice())?;
+        }
+        archive.append_bytes("deflated.txt", &deflated, b"Some string data")?;
+        archive.append_directory("dir/", &stored)?;
+        let (_, cursor) = archive.finalize()?;
+
+        let buffer = cursor.into_inner();
+        if !streamable {
+            common::assert_compressed_sizes(&buffer)?;
+        }
+        let archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+        for entry in &archive_read.file_entries {
+            let header = &buffer[entry.offset as usize..];
+            let extra_len = u16::from_le_bytes([header[28], header[29]]) as u64;
+            let data_offset = entry.offset + 30 + entry.file_name_len as u64 + extra_len;
+            let aligned = extra_len > 0 && data_offset.is_multiple_of(4);
+
+            let name = String::from_utf8_lossy(&entry.file_name_as_bytes);
+            //only the stored files are padded
+            assert_eq!(
+                aligned,
+                entry.compression_method == 0 && !name.ends_with('/'),
+                "entry {}, streamable: {}",
+                name,
+                streamable
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn archive_read_cp437_file_name() -> Result<(), ArchiveError> {
+    const UTF8_FLAG: u16 = 1 << 11;
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_bytes("r_adme.txt", &FileOptions::default(), b"Some string data")?;
+    archive.append_bytes("réadme.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+
+    //a legacy tool's name, in CP437 and not flagged as UTF-8: "é" is 0x82
+    let mut buffer = cursor.into_inner();
+    for index in 0..buffer.len() - 10 {
+        if &buffer[index..index + 10] == b"r_adme.txt" {
+            buffer[index + 1] = 0x82;
+        }
+    }
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].general_purpose_flags & UTF8_FLAG, 0);
+    assert_eq!(entries[0].get_file_name(), "réadme.txt");
+    assert_ne!(entries[1].general_purpose_flags & UTF8_FLAG, 0);
+    assert_eq!(entries[1].get_file_name(), "réadme.txt");
+
+    Ok(())
+}
+
+#[test]
+fn archive_rollback_last_entry() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let store = FileOptions::default().compression_method(CompressionMethod::Store());
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    assert!(!archive.rollback_last_entry()?);
+
+    archive.append_bytes("file1.txt", &FileOptions::default(), b"Some string data")?;
+    let offset = archive.current_offset();
+
+    //a speculative append, stored then compressed
+    archive.append_bytes("lorem_ipsum.txt", &store, &payload)?;
+    assert!(archive.rollback_last_entry()?);
+    assert_eq!(archive.current_offset(), offset);
+    let outcome = archive.append_bytes("lorem_ipsum.txt", &FileOptions::default(), &payload)?;
+    assert_eq!(outcome.index, 1);
+
+    let (archive_size, cursor) = archive.finalize()?;
+    let buffer = cursor.into_inner();
+    assert_eq!(buffer.len() as u64, archive_size);
+    common::assert_compressed_sizes(&buffer)?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].offset, offset);
+    assert_eq!(entries[1].compression_method, 8);
+
+    //a streamable archive can't
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &store, b"Some string data")?;
+    assert!(matches!(
+        archive.rollback_last_entry(),
+        Err(ArchiveError::Unsupported(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn archive_streamable_large_file() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .extended_timestamp(false)
+        .large_file(true);
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.append("file1.txt", &options, &mut { PAYLOAD })?;
+    let (_, buffer) = archive.finalize()?;
+
+    //the local header has the zip64 extra field, with placeholder sizes
+    let extra_len = u16::from_le_bytes([buffer[28], buffer[29]]) as usize;
+    assert_eq!(extra_len, 20);
+    let extra_field = &buffer[30 + 9..30 + 9 + extra_len];
+    assert_eq!(&extra_field[..4], &[0x01, 0x00, 0x10, 0x00]);
+    assert_eq!(&extra_field[4..], &[0; 16]);
+
+    //the zip64 data descriptor follows the payload, with the sizes on 8 bytes
+    let descriptor = &buffer[30 + 9 + extra_len + PAYLOAD.len()..][..24];
+    assert_eq!(&descriptor[..4], &[0x50, 0x4b, 0x07, 0x08]);
+    assert_eq!(descriptor[4..8], crc32fast::hash(PAYLOAD).to_le_bytes());
+    assert_eq!(descriptor[8..16], (PAYLOAD.len() as u64).to_le_bytes());
+    assert_eq!(descriptor[16..24], (PAYLOAD.len() as u64).to_le_bytes());
+
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.compressed_size, PAYLOAD.len() as u64);
+    assert_eq!(entry.version_needed_to_extract(), 45);
+
+    Ok(())
+}
+
+#[test]
+fn archive_streamable_large_file_mock_z64() -> Result<(), ArchiveError> {
+    const LARGE_SIZE: u64 = u32::MAX as u64 + 10;
+    let out_file_name = "test_streamable_large_file_mock_64.zip";
+
+    let out_file = create_new_clean_file(out_file_name);
+    let mut archive = ZipArchive::new_streamable(out_file);
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .large_file(true);
+    let mut file = MockReader::new(LARGE_SIZE as usize);
+    archive.append("zeros.txt", &options, &mut file)?;
+    archive.append_bytes("file.txt", &options, b"Some string data")?;
+    archive.finalize()?;
+
+    let out_file = File::open(common::std::get_out_file(out_file_name))?;
+    let archive_read = ArchiveReader::new(out_file)?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].uncompressed_size, LARGE_SIZE);
+    assert_eq!(entries[0].compressed_size, LARGE_SIZE);
+    assert_eq!(entries[1].offset, 30 + 9 + 20 + LARGE_SIZE + 24);
+
+    Ok(())
+}
+
+#[test]
+fn archive_read_lenient_bad_central_entry() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    for name in ["file1.txt", "file2.txt", "file3.txt"] {
+        archive.append_bytes(name, &FileOptions::default(), b"Some string data")?;
+    }
+    let (_, cursor) = archive.finalize()?;
+
+    //corrupt the signature of the second central directory entry
+    let mut buffer = cursor.into_inner();
+    let central_entries: Vec<usize> = (0..buffer.len() - 4)
+        .filter(|&index| buffer[index..index + 4] == [0x50, 0x4b, 0x01, 0x02])
+        .collect();
+    assert_eq!(central_entries.len(), 3);
+    buffer[central_entries[1]] = 0;
+
+    let result = ArchiveReader::new(Cursor::new(buffer.clone()));
+    assert!(matches!(result, Err(ArchiveError::BadArchiveStructure(_))));
+
+    let archive_read = ArchiveReader::new_lenient(Cursor::new(buffer))?;
+    let names: Vec<String> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+    assert_eq!(names, ["file1.txt", "file3.txt"]);
+    assert_eq!(archive_read.warnings.len(), 2);
+    assert!(archive_read.warnings[0].contains("resynchronized"));
+    assert!(archive_read.warnings[1].contains("declares 3 entries, only 2 found"));
+
+    Ok(())
+}
+
+#[test]
+fn archive_read_zip64_saturated_count() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.force_zip64(true);
+    for name in ["file1.txt", "file2.txt"] {
+        archive.append_bytes(name, &FileOptions::default(), b"Some string data")?;
+    }
+    let (_, cursor) = archive.finalize()?;
+
+    //the zip64 record declares 0xFFFF entries, a count it doesn't saturate
+    let mut buffer = cursor.into_inner();
+    let record = (0..buffer.len() - 4)
+        .find(|&index| buffer[index..index + 4] == [0x50, 0x4b, 0x06, 0x06])
+        .expect("zip64 end of central directory record");
+    for count_offset in [24, 32] {
+        buffer[record + count_offset..record + count_offset + 8]
+            .copy_from_slice(&(u16::MAX as u64).to_le_bytes());
+    }
+
+    let result = ArchiveReader::new(Cursor::new(buffer.clone()));
+    assert!(matches!(result, Err(ArchiveError::BadArchiveStructure(_))));
+
+    let archive_read = ArchiveReader::new_lenient(Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+    assert!(archive_read.warnings[0].contains("declares 65535 entries, only 2 found"));
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_time_budget() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let exhausted = FileOptions::default().time_budget(Duration::ZERO);
+    let generous = FileOptions::default().time_budget(Duration::from_secs(3600));
+
+    //the exceeded entry is rolled back and stored
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let outcome = archive.append("lorem_ipsum.txt", &exhausted, &mut payload.as_slice())?;
+    assert_eq!(outcome.compressed_size, payload.len() as u64);
+    let outcome = archive.append(
+        "lorem_ipsum_deflate.txt",
+        &generous,
+        &mut payload.as_slice(),
+    )?;
+    assert!(outcome.compressed_size < payload.len() as u64);
+    archive.append_bytes("file1.txt", &FileOptions::default(), b"Some string data")?;
+    let (_, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    common::assert_compressed_sizes(&buffer)?;
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].compressor, CompressionMethod::Store());
+    assert_eq!(entries[0].crc32, entries[1].crc32);
+    assert_eq!(entries[1].compressor, CompressionMethod::Deflate());
+
+    //streamed, the budget is checked before the compression starts
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let outcome = archive.append("lorem_ipsum.txt", &exhausted, &mut payload.as_slice())?;
+    assert_eq!(outcome.compressed_size, payload.len() as u64);
+    archive.finalize()?;
+
+    Ok(())
+}
+
+#[test]
+fn archive_append_time_budget_mid_compression() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let options = FileOptions::default().time_budget(Duration::from_millis(100));
+
+    //the deadline fires after a few chunks, the stored entry overwrites the compressed bytes
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    let mut reader = SlowReader::new(payload.clone(), 512, Duration::from_millis(30));
+    let outcome = archive.append("lorem_ipsum.txt", &options, &mut reader)?;
+    assert_eq!(outcome.compressed_size, payload.len() as u64);
+
+    //the deadline fires on the end of an incompressible payload, the stored entry is shorter
+    let mut seed = 0x2545_f491u32;
+    let noise: Vec<u8> = (0..4000)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as u8
+        })
+        .collect();
+    let mut reader = SlowReader::new(noise, 4000, Duration::from_millis(150));
+    let result = archive.append("noise.bin", &options, &mut reader);
+    assert!(matches!(result, Err(ArchiveError::InconsistentArchive(_))));
+
+    archive.append_bytes("file1.txt", &FileOptions::default(), b"Some string data")?;
+    let (archive_size, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    assert_eq!(buffer.len() as u64, archive_size);
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].compressor, CompressionMethod::Store());
+    assert_eq!(entries[1].get_file_name(), "file1.txt");
+
+    Ok(())
+}
+
+#[test]
+fn archive_read_entry_raw() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+
+    let mut archive = ZipArchive::new_streamable(Cursor::new(Vec::new()));
+    archive.append_bytes("file1.txt", &options, b"Some string data")?;
+    let outcome = archive.append("lorem_ipsum.txt", &options, &mut payload.as_slice())?;
+    let (_, cursor) = archive.finalize()?;
+
+    //repackage the entry, without recompressing it
+    let mut archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entry = &archive_read.file_entries[1];
+    let (crc32, uncompressed_size) = (entry.crc32, entry.uncompressed_size);
+    let mut compressed = Vec::new();
+    archive_read
+        .read_entry_raw(1)?
+        .read_to_end(&mut compressed)?;
+    assert_eq!(compressed.len() as u64, outcome.compressed_size);
+    assert!(matches!(
+        archive_read.read_entry_raw(2),
+        Err(ArchiveError::EntryNotFound(2))
+    ));
+
+    let mut decoder = flate2::read::DeflateDecoder::new(compressed.as_slice());
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed)?;
+    assert_eq!(decompressed, payload);
+
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.append_raw(
+        "lorem_ipsum.txt",
+        &FileOptions::default(),
+        CompressionMethod::Deflate(),
+        crc32,
+        uncompressed_size,
+        &compressed,
+    )?;
+    let (_, cursor) = archive.finalize()?;
+
+    let buffer = cursor.into_inner();
+    common::assert_compressed_sizes(&buffer)?;
+    let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries[0].crc32, outcome.crc32);
+
+    Ok(())
+}
+
+#[test]
+fn archive_check_data_descriptors() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.append(
+        "file1.txt",
+        &FileOptions::default(),
+        &mut b"Some string data".as_ref(),
+    )?;
+    let options = FileOptions::default().large_file(true);
+    archive.append("file2.txt", &options, &mut b"Some string data".as_ref())?;
+    let options = FileOptions::default().data_descriptor_signature(false);
+    archive.append("file3.txt", &options, &mut b"Some string data".as_ref())?;
+    let (_, mut buffer) = archive.finalize()?;
+
+    let mut archive_read = ArchiveReader::new(Cursor::new(buffer.clone()))?;
+    archive_read.check_data_descriptors()?;
+    assert!(
+        archive_read.warnings.is_empty(),
+        "{:?}",
+        archive_read.warnings
+    );
+
+    //a writer disagreeing on the first entry's CRC-32, right after the descriptor signature
+    let descriptor = (0..buffer.len() - 4)
+        .find(|&index| buffer[index..index + 4] == [0x50, 0x4b, 0x07, 0x08])
+        .unwrap();
+    buffer[descriptor + 4] ^= 0xFF;
+
+    let mut archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+    archive_read.check_data_descriptors()?;
+    assert_eq!(archive_read.warnings.len(), 1);
+    assert!(archive_read.warnings[0].contains("\"file1.txt\" disagrees"));
+
+    Ok(())
+}
+
+#[test]
+fn archive_directory_metadata() -> Result<(), ArchiveError> {
+    const MODIFIED: i32 = 1_600_000_000;
+
+    let options = FileOptions::default()
+        .last_modified_time(FileDateTime::UnixCustom(MODIFIED))
+        .set_file_comment("A directory");
+
+    for streamable in [false, true] {
+        let mut archive = if streamable {
+            ZipArchive::new_streamable(Cursor::new(Vec::new()))
+        } else {
+            ZipArchive::new(Cursor::new(Vec::new()))
+        };
+        archive.append_directory("dir", &options)?;
+        let (_, cursor) = archive.finalize()?;
+
+        let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+        let entry = &archive_read.file_entries[0];
+        assert_eq!(entry.get_file_name(), "dir/");
+        assert_eq!(entry.comment().as_deref(), Some("A directory"));
+        assert_eq!(
+            (entry.last_mod_file_date, entry.last_mod_file_time),
+            FileDateTime::UnixCustom(MODIFIED).ms_dos()
+        );
+        assert!(entry.get_extra_field_time_stamp().is_some());
+        assert!(!entry.flags().has_data_descriptor);
+    }
+
+    Ok(())
+}
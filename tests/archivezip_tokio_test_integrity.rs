@@ -1,6 +1,7 @@
 use std::path::Path;
+use std::time::Duration;
 
-use tokio::fs::File;
+use tokio::{fs::File, io::AsyncWriteExt};
 
 use archflow::{
     compress::tokio::archive::ZipArchive, compress::FileOptions, compression::CompressionMethod,
@@ -8,7 +9,7 @@ use archflow::{
 };
 mod common;
 
-use common::tokio::{create_new_clean_file, get_out_file};
+use common::tokio::{create_new_clean_file, get_out_file, SeekFailingWriter};
 
 #[tokio::test]
 async fn archive_multiple() -> Result<(), ArchiveError> {
@@ -165,3 +166,1285 @@ async fn archive_multiple_norm() -> Result<(), ArchiveError> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn archive_finalize_keep_writer() -> Result<(), ArchiveError> {
+    const TRAILER: &[u8] = b"SIGNATURE BLOCK";
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    archive
+        .append("file1.txt", &options, &mut b"Some string data".as_ref())
+        .await?;
+
+    let (archive_size, mut cursor) = archive.finalize_keep_writer().await?;
+
+    assert_eq!(cursor.position(), archive_size);
+    assert_eq!(cursor.get_ref().len() as u64, archive_size);
+
+    cursor.write_all(TRAILER).await?;
+
+    let buffer = cursor.into_inner();
+    assert_eq!(buffer.len() as u64, archive_size + TRAILER.len() as u64);
+    assert!(buffer.ends_with(TRAILER));
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(&buffer[..archive_size as usize]))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_streamable_overhead() -> Result<(), ArchiveError> {
+    //signature, crc32, compressed and uncompressed sizes
+    const DATA_DESCRIPTOR_SIZE: u64 = 16;
+    const FILE_COUNT: u64 = 5;
+    let payload = b"Some string data".repeat(100);
+
+    for method in [CompressionMethod::Store(), CompressionMethod::Deflate()] {
+        let options = FileOptions::default().compression_method(method);
+
+        let mut seekable = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+        let mut streamable = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+        for i in 0..FILE_COUNT {
+            let file_name = format!("file{}.txt", i);
+            seekable
+                .append(&file_name, &options, &mut payload.as_slice())
+                .await?;
+            streamable
+                .append(&file_name, &options, &mut payload.as_slice())
+                .await?;
+        }
+
+        let (seekable_size, _) = seekable.finalize().await?;
+        let (streamable_size, _) = streamable.finalize().await?;
+
+        assert_eq!(
+            streamable_size - seekable_size,
+            FILE_COUNT * DATA_DESCRIPTOR_SIZE
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_all() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+
+    let entries: Vec<(String, Box<dyn tokio::io::AsyncRead + Unpin + Send>)> = vec![
+        ("file1.txt".to_owned(), Box::new(&b"Some string data"[..])),
+        (
+            "file2.txt".to_owned(),
+            Box::new(std::io::Cursor::new(vec![b'a'; 100])),
+        ),
+    ];
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Zstd());
+    let outcomes = archive.append_all(entries, &options).await?;
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[1].uncompressed_size, 100);
+
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+    assert_eq!(archive_read.file_entries[1].get_file_name(), "file2.txt");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_reader_owned() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    for index in 0..3 {
+        let payload = std::io::Cursor::new(vec![b'a'; index * 10]);
+        archive
+            .append_reader_owned(&format!("file{index}.txt"), &options, payload)
+            .await?;
+    }
+
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 3);
+    assert_eq!(archive_read.file_entries[2].uncompressed_size, 20);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_streamable_buffered() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncSocket;
+
+    let mut archive = ZipArchive::new_streamable_buffered(MockAsyncSocket::default(), 1024);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    for index in 0..20 {
+        archive
+            .append_bytes(&format!("file{index}.txt"), &options, b"tiny")
+            .await?;
+    }
+    let (archive_size, socket) = archive.finalize().await?;
+
+    //everything buffered reached the socket
+    assert_eq!(socket.received.len() as u64, archive_size);
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(socket.received))?;
+    assert_eq!(archive_read.file_entries.len(), 20);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_streamable_buffered_retrieve_writer() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncSocket;
+
+    let mut archive = ZipArchive::new_streamable_buffered(MockAsyncSocket::default(), 1024);
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let archive_size = archive.get_archive_size()?;
+
+    //the buffered entry reaches the socket, without the central directory
+    let socket = archive.retrieve_writer().await?;
+    assert_eq!(socket.received.len() as u64, archive_size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_get_archive_size() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+    assert_eq!(archive.get_archive_size()?, 0);
+
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let size = archive.get_archive_size()?;
+    assert_eq!(size, archive.current_offset());
+
+    let (_, cursor) = archive.finalize().await?;
+    assert!(cursor.into_inner().len() as u64 > size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_force_zip64() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+    archive.force_zip64(true);
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    let outcome = archive
+        .append("file1.txt", &options, &mut { PAYLOAD })
+        .await?;
+
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let entry = &archive_read.file_entries[0];
+    assert!(entry.has_zip64_extra_field());
+    assert_eq!(entry.uncompressed_size, PAYLOAD.len() as u64);
+    assert_eq!(entry.compressed_size, outcome.compressed_size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_virtual_tree() -> Result<(), ArchiveError> {
+    let mut tree = std::collections::BTreeMap::new();
+    tree.insert("a/b/c.txt".to_owned(), b"Some string data".to_vec());
+    tree.insert("a/d.txt".to_owned(), b"Some string data".to_vec());
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive
+        .append_virtual_tree(&tree, &FileOptions::default())
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let names: Vec<String> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+
+    assert_eq!(names, ["a/", "a/b/", "a/b/c.txt", "a/d.txt"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_bytes_multi() -> Result<(), ArchiveError> {
+    let payload = b"<html>Some string data</html>".repeat(100);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    assert!(archive
+        .append_bytes_multi(&[], &options, &payload)
+        .await?
+        .is_empty());
+    let outcomes = archive
+        .append_bytes_multi(&["index.html", "default.html"], &options, &payload)
+        .await?;
+    let (_, buffer) = archive.finalize().await?;
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].index, 0);
+    assert_eq!(outcomes[1].index, 1);
+    assert_eq!(outcomes[0].crc32, outcomes[1].crc32);
+    assert!(outcomes[0].compressed_size < payload.len() as u64);
+
+    //each name has its own local header and copy of the compressed bytes
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].get_file_name(), "index.html");
+    assert_eq!(entries[1].get_file_name(), "default.html");
+    for (entry, outcome) in entries.iter().zip(&outcomes) {
+        assert_eq!(entry.crc32, outcome.crc32);
+        assert_eq!(entry.compressed_size, outcome.compressed_size);
+        assert_eq!(entry.uncompressed_size, payload.len() as u64);
+    }
+    assert_ne!(entries[0].offset, entries[1].offset);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_patch_seek_failure() -> Result<(), ArchiveError> {
+    //the seek back at the end of the second entry, once its local header is patched, fails
+    let mut archive = ZipArchive::new(SeekFailingWriter::new(4));
+    let options = FileOptions::default();
+    archive
+        .append("file1.txt", &options, &mut b"Some string data".as_slice())
+        .await?;
+    let error = archive
+        .append("file2.txt", &options, &mut b"Other string data".as_slice())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, ArchiveError::InconsistentArchive(_)),
+        "{:?}",
+        error
+    );
+
+    //the failed entry is left out of the central directory, the archive stays readable
+    let (_, writer) = archive.finalize().await?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(writer.into_inner()))?;
+    let names: Vec<_> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+    assert_eq!(names, ["file1.txt"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_manifest() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+    archive
+        .append_bytes("file1.txt", &options, b"Some string data")
+        .await?;
+
+    let outcome = archive.append_manifest("files.txt", &options).await?;
+    archive.finalize().await?;
+
+    let expected = format!(
+        "file1.txt\t16\t{:08x}\n",
+        crc32fast::hash(b"Some string data")
+    );
+    assert_eq!(outcome.crc32, crc32fast::hash(expected.as_bytes()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_store_empty() -> Result<(), ArchiveError> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflate())
+        .store_empty(true);
+
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+    archive
+        .append("empty.txt", &options, &mut b"".as_slice())
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(
+        entry.compression_method,
+        CompressionMethod::Store().zip_code()
+    );
+    assert_eq!(entry.compressed_size, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_gz_decompressed() -> Result<(), ArchiveError> {
+    use archflow::gz::tokio::GzStream;
+
+    let payload = b"Some string data";
+    let (_, gz) = GzStream::new(Vec::new())
+        .compress(&mut payload.as_slice())
+        .await?;
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let outcome = archive
+        .append_gz_decompressed("file1.txt", &FileOptions::default(), gz.as_slice())
+        .await?;
+    archive.finalize().await?;
+
+    assert_eq!(outcome.uncompressed_size, payload.len() as u64);
+    assert_eq!(outcome.crc32, crc32fast::hash(payload));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_prelude() -> Result<(), archflow::prelude::ArchiveError> {
+    use archflow::prelude::*;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflate())
+        .compression_level(Level::Best)
+        .last_modified_time(FileDateTime::Zero);
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive
+        .append_bytes("file1.txt", &options, b"Some string data")
+        .await?;
+    archive.finalize().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_path_auto() -> Result<(), ArchiveError> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Auto());
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive
+        .append_path(
+            "images/rust-mascot.png",
+            &options,
+            "tests/resources/rust-mascot.png",
+        )
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(
+        archive_read.file_entries[0].compression_method,
+        CompressionMethod::Store().zip_code()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_with_archive_digest() -> Result<(), ArchiveError> {
+    const SUFFIX_SIZE: usize = "archflow-crc32:".len() + 8;
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.with_archive_digest(true)?;
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let (_, buffer) = archive.finalize().await?;
+
+    let (digested, suffix) = buffer.split_at(buffer.len() - SUFFIX_SIZE);
+    assert_eq!(
+        suffix,
+        format!("archflow-crc32:{:08x}", crc32fast::hash(digested)).as_bytes()
+    );
+    ArchiveReader::new(std::io::Cursor::new(buffer))?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_multiple_mock_z64() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncReader;
+
+    const LARGE_SIZE: u64 = u32::MAX as u64 + 10;
+    let out_file_name = "test_multiple_mock_64.zip";
+
+    let out_file = create_new_clean_file(out_file_name).await;
+    let mut archive = ZipArchive::new_streamable(out_file);
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Zstd());
+    let mut file = MockAsyncReader::new(LARGE_SIZE as usize);
+    let outcome = archive.append("zeros1.txt", &options, &mut file).await?;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    let mut file = MockAsyncReader::new(u16::MAX as usize + 10);
+    archive.append("zeros2.txt", &options, &mut file).await?;
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .set_file_comment("This is a store file");
+    let mut file = MockAsyncReader::new(u8::MAX as usize + 10);
+    archive.append("zeros3.txt", &options, &mut file).await?;
+
+    archive.finalize().await?;
+
+    assert_eq!(outcome.uncompressed_size, LARGE_SIZE);
+
+    let out_file = std::fs::File::open(get_out_file(out_file_name))?;
+    let archive_read = ArchiveReader::new(out_file)?;
+
+    assert_eq!(
+        archive_read
+            .central_directory_end
+            .total_number_of_entries_in_the_central_directory,
+        3
+    );
+
+    let entry1 = &archive_read.file_entries[0];
+    assert_eq!("zeros1.txt", entry1.get_file_name());
+    assert_eq!(entry1.uncompressed_size, LARGE_SIZE);
+    assert_eq!(entry1.compressed_size, outcome.compressed_size);
+    assert_eq!(entry1.crc32, outcome.crc32);
+    assert!(entry1.has_zip64_extra_field());
+
+    let entry2 = &archive_read.file_entries[1];
+    assert_eq!("zeros2.txt", entry2.get_file_name());
+    assert_eq!(entry2.uncompressed_size, u16::MAX as u64 + 10);
+
+    let entry3 = &archive_read.file_entries[2];
+    assert_eq!("zeros3.txt", entry3.get_file_name());
+    assert_eq!(entry3.uncompressed_size, u8::MAX as u64 + 10);
+    assert_eq!(entry3.compressed_size, u8::MAX as u64 + 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_seekable_file_mock_z64() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncReader;
+    use std::io::{Read, Seek, SeekFrom};
+
+    const LARGE_SIZE: u64 = u32::MAX as u64 + 10;
+    let out_file_name = "test_seekable_mock_64.zip";
+
+    let out_file = create_new_clean_file(out_file_name).await;
+    let mut archive = ZipArchive::new(out_file);
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Zstd())
+        .large_file(true);
+    let mut file = MockAsyncReader::new(LARGE_SIZE as usize);
+    let outcome = archive.append("zeros.txt", &options, &mut file).await?;
+    archive
+        .append_bytes("small.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    archive.finalize().await?;
+
+    assert_eq!(outcome.uncompressed_size, LARGE_SIZE);
+
+    let mut out_file = std::fs::File::open(get_out_file(out_file_name))?;
+    let archive_read = ArchiveReader::new(out_file.try_clone()?)?;
+
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.uncompressed_size, LARGE_SIZE);
+    assert_eq!(entry.compressed_size, outcome.compressed_size);
+    assert_eq!(entry.crc32, outcome.crc32);
+    assert_eq!(archive_read.file_entries[1].get_file_name(), "small.txt");
+
+    //the local header was patched in place, the uncompressed size doesn't fit
+    let mut local_header = [0u8; 30 + 9 + 20];
+    out_file.seek(SeekFrom::Start(0))?;
+    out_file.read_exact(&mut local_header)?;
+    assert_eq!(&local_header[14..18], &outcome.crc32.to_le_bytes());
+    assert_eq!(
+        &local_header[18..22],
+        &(outcome.compressed_size as u32).to_le_bytes()
+    );
+    assert_eq!(&local_header[22..26], &[0xFF; 4]);
+
+    //then the zip64 extra field holds the actual sizes
+    let zip64_extra_field = &local_header[30 + 9..];
+    assert_eq!(&zip64_extra_field[..4], &[0x01, 0x00, 16, 0x00]);
+    assert_eq!(&zip64_extra_field[4..12], &LARGE_SIZE.to_le_bytes());
+    assert_eq!(
+        &zip64_extra_field[12..20],
+        &outcome.compressed_size.to_le_bytes()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_seekable() -> Result<(), ArchiveError> {
+    let mut payload = File::open("tests/resources/lorem_ipsum.txt").await?;
+    let size = payload.metadata().await?.len();
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let outcome = archive
+        .append_seekable("lorem_ipsum.txt", &FileOptions::default(), &mut payload)
+        .await?;
+    archive.finalize().await?;
+
+    assert_eq!(outcome.uncompressed_size, size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_max_entry_size() -> Result<(), ArchiveError> {
+    let payload = b"Some string data";
+    let limited = FileOptions::default().max_entry_size(Some(10));
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let result = archive
+        .append("too_big.txt", &limited, &mut payload.as_slice())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ArchiveError::EntrySizeLimitExceeded(10))
+    ));
+
+    archive
+        .append(
+            "file1.txt",
+            &FileOptions::default(),
+            &mut payload.as_slice(),
+        )
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    assert_eq!(
+        archive_read.file_entries[0].file_name_as_bytes,
+        b"file1.txt"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_text_file() -> Result<(), ArchiveError> {
+    let text = b"Some string data";
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let options = FileOptions::default().text_file(false);
+    archive
+        .append("binary.txt", &options, &mut text.as_slice())
+        .await?;
+    let options = FileOptions::default().text_file(true);
+    archive
+        .append("text.bin", &options, &mut [0u8, 1, 2, 3].as_slice())
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries[0].internal_file_attributes & 1, 0);
+    assert_eq!(archive_read.file_entries[1].internal_file_attributes & 1, 1);
+
+    Ok(())
+}
+
+/// Reads zeros, cancelling the token once `cancel_after` bytes were read.
+struct CancellingReader {
+    token: tokio_util::sync::CancellationToken,
+    read: usize,
+    cancel_after: usize,
+}
+
+impl tokio::io::AsyncRead for CancellingReader {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let len = buf.remaining().min(1024);
+        buf.put_slice(&vec![0u8; len]);
+        self.read += len;
+        if self.read >= self.cancel_after {
+            self.token.cancel();
+        }
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn archive_append_cancellable() -> Result<(), ArchiveError> {
+    use tokio_util::sync::CancellationToken;
+
+    let payload = b"Some string data";
+    let options = FileOptions::default();
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+
+    archive
+        .append_cancellable(
+            "file1.txt",
+            &options,
+            &mut payload.as_slice(),
+            CancellationToken::new(),
+        )
+        .await?;
+
+    let token = CancellationToken::new();
+    let mut reader = CancellingReader {
+        token: token.clone(),
+        read: 0,
+        cancel_after: 10_000,
+    };
+    let result = archive
+        .append_cancellable("endless.bin", &options, &mut reader, token)
+        .await;
+    assert!(matches!(result, Err(ArchiveError::Cancelled)));
+    assert!(reader.read < 20_000);
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = archive
+        .append_cancellable("cancelled.txt", &options, &mut payload.as_slice(), token)
+        .await;
+    assert!(matches!(result, Err(ArchiveError::Cancelled)));
+
+    let (_, cursor) = archive.finalize().await?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+    assert_eq!(
+        archive_read.file_entries[0].file_name_as_bytes,
+        b"file1.txt"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_adaptive() -> Result<(), ArchiveError> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Zstd())
+        .adaptive(true)
+        .adaptive_sampling(1024, 0.9);
+
+    //pseudo random, hence incompressible, bytes
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let noise: Vec<u8> = (0..10_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    let mut text = File::open("tests/resources/lorem_ipsum.txt").await?;
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive
+        .append("noise.bin", &options, &mut noise.as_slice())
+        .await?;
+    archive
+        .append("lorem_ipsum.txt", &options, &mut text)
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let noise_entry = &archive_read.file_entries[0];
+    assert_eq!(
+        noise_entry.compression_method,
+        CompressionMethod::Store().zip_code()
+    );
+    assert_eq!(noise_entry.crc32, crc32fast::hash(&noise));
+    assert_eq!(
+        archive_read.file_entries[1].compression_method,
+        CompressionMethod::Zstd().zip_code()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_finalize_report() -> Result<(), ArchiveError> {
+    let options = FileOptions::default();
+
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+    archive
+        .append("file1.txt", &options, &mut b"Some string data".as_slice())
+        .await?;
+    archive.append_directory("dir/", &options).await?;
+    let report = archive.finalize_report().await?;
+
+    assert_eq!(report.entry_count, 2);
+    assert!(!report.used_zip64);
+    assert_eq!(report.size, report.writer.get_ref().len() as u64);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_write_only_sink() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncSocket;
+
+    let mut archive = ZipArchive::new_streamable(MockAsyncSocket::default());
+
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    archive.append("file1.txt", &options, &mut in_file).await?;
+    archive
+        .append_bytes("file2.txt", &options, b"Some string data")
+        .await?;
+    archive.append_directory("dir/", &options).await?;
+    let (archive_size, socket) = archive.finalize().await?;
+
+    assert_eq!(archive_size, socket.received.len() as u64);
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(socket.received))?;
+    assert_eq!(archive_read.file_entries.len(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_tee() -> Result<(), ArchiveError> {
+    use archflow::compress::tee::TeeWriter;
+    use common::tokio::MockAsyncSocket;
+
+    let tee = TeeWriter::new(MockAsyncSocket::default(), Vec::new());
+    let mut archive = ZipArchive::new_streamable(tee);
+
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflate());
+    archive.append("file1.txt", &options, &mut in_file).await?;
+    archive
+        .append_bytes("file2.txt", &options, b"Some string data")
+        .await?;
+    let (archive_size, tee) = archive.finalize().await?;
+
+    let (socket, local) = tee.into_inner();
+    assert_eq!(archive_size, local.len() as u64);
+    assert_eq!(socket.received, local);
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(local))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_and_capture() -> Result<(), ArchiveError> {
+    use common::tokio::MockAsyncSocket;
+
+    let lorem_ipsum = tokio::fs::read("tests/resources/lorem_ipsum.txt").await?;
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let mut capture = MockAsyncSocket::default();
+    let options = FileOptions::default().adaptive(true);
+    let outcome = archive
+        .append_and_capture(
+            "file1.txt",
+            &options,
+            &mut lorem_ipsum.as_slice(),
+            &mut capture,
+        )
+        .await?;
+    archive.finalize().await?;
+
+    assert_eq!(capture.received, lorem_ipsum);
+    assert_eq!(outcome.uncompressed_size, lorem_ipsum.len() as u64);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_empty() -> Result<(), ArchiveError> {
+    let archive = ZipArchive::new_streamable(Vec::new());
+    let (archive_size, buffer) = archive.finalize().await?;
+    assert_eq!(archive_size, 22);
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    assert!(archive_read.file_entries.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_default_compression_level() -> Result<(), ArchiveError> {
+    use archflow::compression::Level;
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive.default_compression_level(Level::None);
+
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive
+        .append("none.txt", &FileOptions::default(), &mut in_file)
+        .await?;
+    let options = FileOptions::default().compression_level(Level::Best);
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive.append("best.txt", &options, &mut in_file).await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].compressor, CompressionMethod::Store());
+    assert_eq!(entries[0].compressed_size, entries[0].uncompressed_size);
+    assert_eq!(entries[1].general_purpose_flags & 0b110, 0b010);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_file_modified_time() -> Result<(), ArchiveError> {
+    use archflow::types::FileDateTime;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const MODIFIED: i32 = 1_600_000_000;
+
+    let file = create_new_clean_file("test_append_file.txt").await;
+    let mut file = file.into_std().await;
+    std::io::Write::write_all(&mut file, b"Some string data")?;
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(MODIFIED as u64))?;
+    drop(file);
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let mut file = File::open(get_out_file("test_append_file.txt")).await?;
+    archive
+        .append_file("file_time.txt", &FileOptions::default(), &mut file)
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(
+        (entry.last_mod_file_date, entry.last_mod_file_time),
+        FileDateTime::UnixCustom(MODIFIED).ms_dos()
+    );
+    assert_eq!(entry.uncompressed_size, 16);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_sort_central_directory_by_name() -> Result<(), ArchiveError> {
+    let options = FileOptions::default();
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    archive.sort_central_directory_by_name(true);
+    for file_name in ["z.txt", "m.txt", "a.txt"] {
+        archive
+            .append(file_name, &options, &mut file_name.as_bytes())
+            .await?;
+    }
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(cursor.into_inner()))?;
+    let names: Vec<&[u8]> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.file_name_as_bytes.as_slice())
+        .collect();
+    assert_eq!(names, [b"a.txt".as_slice(), b"m.txt", b"z.txt"]);
+    assert_eq!(archive_read.file_entries[2].offset, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_seekable_compressed_sizes() -> Result<(), ArchiveError> {
+    use common::assert_compressed_sizes;
+
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive
+        .append("deflate.txt", &FileOptions::default(), &mut in_file)
+        .await?;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+    archive
+        .append("store.txt", &options, &mut b"Some string data".as_slice())
+        .await?;
+    archive
+        .append("empty.txt", &options, &mut b"".as_slice())
+        .await?;
+
+    //the zip64 extra field is patched as well
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Xz())
+        .large_file(true);
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive.append("large.txt", &options, &mut in_file).await?;
+
+    let (_, cursor) = archive.finalize().await?;
+    assert_compressed_sizes(&cursor.into_inner())
+}
+
+#[tokio::test]
+async fn archive_append_raw() -> Result<(), ArchiveError> {
+    let payload = b"Some string data";
+    let crc32 = crc32fast::hash(payload);
+
+    let mut archive = ZipArchive::new_streamable(std::io::Cursor::new(Vec::new()));
+    archive.report_duplicates(true);
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), payload)
+        .await?;
+    archive
+        .append_raw(
+            "copy/file1.txt",
+            &FileOptions::default(),
+            CompressionMethod::Store(),
+            crc32,
+            payload.len() as u64,
+            payload,
+        )
+        .await?;
+    assert_eq!(
+        archive.duplicates(),
+        [("copy/file1.txt".to_owned(), "file1.txt".to_owned())]
+    );
+    let (_, cursor) = archive.finalize().await?;
+
+    let buffer = cursor.into_inner();
+    common::assert_compressed_sizes(&buffer)?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let raw_entry = &archive_read.file_entries[1];
+    assert_eq!(
+        raw_entry.compression_method,
+        CompressionMethod::Store().zip_code()
+    );
+    assert_eq!(raw_entry.crc32, crc32);
+    assert_eq!(raw_entry.compressed_size, payload.len() as u64);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_finalize_with_progress() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let options = FileOptions::default().extended_timestamp(false);
+    archive
+        .append_bytes("file1.txt", &options, b"Some string data")
+        .await?;
+    archive
+        .append_bytes("dir/file2.txt", &options, b"Some string data")
+        .await?;
+
+    let mut steps = Vec::new();
+    let (archive_size, buffer) = archive
+        .finalize_with_progress(|index, written| steps.push((index, written)))
+        .await?;
+
+    assert_eq!(steps, [(0, 46 + 9), (1, 46 + 9 + 46 + 13)]);
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+    assert!(archive_size > steps[1].1 + 22);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_sized() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+    let options = FileOptions::default().compression_method(CompressionMethod::Store());
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let outcome = archive
+        .append_sized("exact.txt", &options, 16, &mut { PAYLOAD })
+        .await?;
+    assert_eq!(outcome.uncompressed_size, 16);
+
+    let truncated = archive
+        .append_sized("truncated.txt", &options, 20, &mut { PAYLOAD })
+        .await;
+    assert!(matches!(
+        truncated,
+        Err(ArchiveError::LengthMismatch {
+            expected: 20,
+            actual: 16,
+            ..
+        })
+    ));
+
+    let overrun = archive
+        .append_sized("overrun.txt", &options, 4, &mut { PAYLOAD })
+        .await;
+    match overrun {
+        Err(ArchiveError::LengthMismatch {
+            expected,
+            actual,
+            crc32,
+        }) => {
+            assert_eq!((expected, actual), (4, 16));
+            assert_eq!(crc32, crc32fast::hash(&PAYLOAD[..4]));
+        }
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let (_, buffer) = archive.finalize().await?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    assert_eq!(archive_read.file_entries.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_current_offset() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+
+    let mut offsets = Vec::new();
+    for name in ["file1.txt", "file2.txt"] {
+        offsets.push(archive.current_offset());
+        archive
+            .append_bytes(name, &FileOptions::default(), b"Some string data")
+            .await?;
+    }
+    let (_, cursor) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(cursor)?;
+    let entry_offsets: Vec<u64> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.offset)
+        .collect();
+    assert_eq!(entry_offsets, offsets);
+    assert_eq!(offsets[0], 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_entry_comment_utf8_flag() -> Result<(), ArchiveError> {
+    const UTF8_FLAG: u16 = 1 << 11;
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let options = FileOptions::default().set_file_comment("Fichier compressé");
+    archive
+        .append_bytes("file1.txt", &options, b"Some string data")
+        .await?;
+    let options = FileOptions::default().set_file_comment("An ASCII comment");
+    archive
+        .append_bytes("file2.txt", &options, b"Some string data")
+        .await?;
+    let (_, buffer) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_ne!(entries[0].general_purpose_flags & UTF8_FLAG, 0);
+    assert_eq!(entries[0].comment().as_deref(), Some("Fichier compressé"));
+    assert_eq!(entries[1].general_purpose_flags & UTF8_FLAG, 0);
+    assert_eq!(entries[1].comment().as_deref(), Some("An ASCII comment"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_blocking_sink() -> Result<(), ArchiveError> {
+    use archflow::compress::blocking::BlockingSink;
+
+    //make sure the directory exists
+    drop(create_new_clean_file("test_blocking_sink.zip").await);
+    let out_file = std::fs::File::create(get_out_file("test_blocking_sink.zip"))?;
+
+    let mut archive = ZipArchive::new_streamable(BlockingSink::new(out_file));
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive
+        .append("lorem_ipsum.txt", &FileOptions::default(), &mut in_file)
+        .await?;
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let (archive_size, sink) = archive.finalize().await?;
+    let out_file = sink.into_inner().await?;
+    assert_eq!(out_file.metadata()?.len(), archive_size);
+
+    let archive_read =
+        ArchiveReader::new(std::fs::File::open(get_out_file("test_blocking_sink.zip"))?)?;
+    assert_eq!(archive_read.file_entries.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_set_entry_comment() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+
+    let first = archive
+        .append(
+            "file1.txt",
+            &FileOptions::default(),
+            &mut b"Some string data".as_slice(),
+        )
+        .await?;
+    let second = archive
+        .append_bytes("file2.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    assert_eq!((first.index, second.index), (0, 1));
+
+    archive.set_entry_comment(second.index, "Fichier compressé")?;
+    let (_, buffer) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].comment(), None);
+    assert_eq!(entries[1].comment().as_deref(), Some("Fichier compressé"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_precompressed_frames() -> Result<(), ArchiveError> {
+    const PAYLOAD: &[u8] = b"Some string data";
+    let frames: [&[u8]; 3] = [b"Some ", b"string ", b"data"];
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    let options = FileOptions::default();
+    archive
+        .append_precompressed_frames(
+            "file1.txt",
+            &options,
+            CompressionMethod::Store(),
+            crc32fast::hash(PAYLOAD),
+            PAYLOAD.len() as u64,
+            PAYLOAD.len() as u64,
+            frames,
+        )
+        .await?;
+    let (_, buffer) = archive.finalize().await?;
+
+    common::assert_compressed_sizes(&buffer)?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer.clone()))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.crc32, crc32fast::hash(PAYLOAD));
+    assert_eq!(entry.uncompressed_size, PAYLOAD.len() as u64);
+    assert!(buffer
+        .windows(PAYLOAD.len())
+        .any(|window| window == PAYLOAD));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_rollback_last_entry() -> Result<(), ArchiveError> {
+    let out_file = create_new_clean_file("test_rollback.zip").await;
+    let mut archive = ZipArchive::new(out_file);
+
+    let options = FileOptions::default();
+    archive
+        .append_bytes("file1.txt", &options, b"Some string data")
+        .await?;
+    let mut in_file = File::open("tests/resources/lorem_ipsum.txt").await?;
+    archive
+        .append("lorem_ipsum.txt", &options, &mut in_file)
+        .await?;
+    assert!(archive.rollback_last_entry().await?);
+    archive
+        .append_bytes("file2.txt", &options, b"Some string data")
+        .await?;
+    let (archive_size, _) = archive.finalize().await?;
+
+    let buffer = std::fs::read(get_out_file("test_rollback.zip"))?;
+    assert_eq!(buffer.len() as u64, archive_size);
+    common::assert_compressed_sizes(&buffer)?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let names: Vec<String> = archive_read
+        .file_entries
+        .iter()
+        .map(|entry| entry.get_file_name())
+        .collect();
+    assert_eq!(names, ["file1.txt", "file2.txt"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_time_budget() -> Result<(), ArchiveError> {
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let exhausted = FileOptions::default().time_budget(Duration::ZERO);
+
+    //the exceeded entry is rolled back and stored
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let outcome = archive
+        .append("lorem_ipsum.txt", &exhausted, &mut payload.as_slice())
+        .await?;
+    assert_eq!(outcome.compressed_size, payload.len() as u64);
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let (_, cursor) = archive.finalize().await?;
+
+    let buffer = cursor.into_inner();
+    common::assert_compressed_sizes(&buffer)?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    assert_eq!(
+        archive_read.file_entries[0].compressor,
+        CompressionMethod::Store()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_append_time_budget_mid_compression() -> Result<(), ArchiveError> {
+    use common::tokio::SlowAsyncReader;
+
+    let payload = std::fs::read("tests/resources/lorem_ipsum.txt")?;
+    let options = FileOptions::default().time_budget(Duration::from_millis(100));
+
+    //the deadline fires after a few chunks, the stored entry overwrites the compressed bytes
+    let mut archive = ZipArchive::new(std::io::Cursor::new(Vec::new()));
+    let mut reader = SlowAsyncReader::new(payload.clone(), 512, Duration::from_millis(30));
+    let outcome = archive
+        .append("lorem_ipsum.txt", &options, &mut reader)
+        .await?;
+    assert_eq!(outcome.compressed_size, payload.len() as u64);
+
+    archive
+        .append_bytes("file1.txt", &FileOptions::default(), b"Some string data")
+        .await?;
+    let (archive_size, cursor) = archive.finalize().await?;
+
+    let buffer = cursor.into_inner();
+    assert_eq!(buffer.len() as u64, archive_size);
+    common::assert_compressed_sizes(&buffer)?;
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries[0].compressor, CompressionMethod::Store());
+    assert_eq!(entries[1].get_file_name(), "file1.txt");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_directory_metadata() -> Result<(), ArchiveError> {
+    use archflow::types::FileDateTime;
+
+    const MODIFIED: i32 = 1_600_000_000;
+
+    let options = FileOptions::default()
+        .last_modified_time(FileDateTime::UnixCustom(MODIFIED))
+        .set_file_comment("A directory");
+
+    let mut archive = ZipArchive::new_streamable(Vec::new());
+    archive.append_directory("dir", &options).await?;
+    let (_, buffer) = archive.finalize().await?;
+
+    let archive_read = ArchiveReader::new(std::io::Cursor::new(buffer))?;
+    let entry = &archive_read.file_entries[0];
+    assert_eq!(entry.get_file_name(), "dir/");
+    assert_eq!(entry.comment().as_deref(), Some("A directory"));
+    assert_eq!(
+        (entry.last_mod_file_date, entry.last_mod_file_time),
+        FileDateTime::UnixCustom(MODIFIED).ms_dos()
+    );
+    assert!(entry.get_extra_field_time_stamp().is_some());
+    assert!(!entry.flags().has_data_descriptor);
+
+    Ok(())
+}
@@ -9,3 +9,39 @@ pub const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
 pub fn out_file_name(compressor: CompressionMethod, test_id: &str) -> String {
     ["test_", &compressor.to_string(), "_", test_id, ".zip"].join("")
 }
+
+/// Checks each entry's compressed size is the number of bytes between its local header and the next one, or
+/// the central directory for the last entry. The archive must have no data descriptor nor archive comment.
+#[allow(dead_code)]
+pub fn assert_compressed_sizes(buffer: &[u8]) -> Result<(), archflow::error::ArchiveError> {
+    use archflow::uncompress::ArchiveReader;
+
+    let archive_read = ArchiveReader::new(::std::io::Cursor::new(buffer.to_vec()))?;
+
+    let eocd = &buffer[buffer.len() - 22..];
+    let central_directory_offset = u32::from_le_bytes(eocd[16..20].try_into().unwrap()) as u64;
+
+    let mut entries: Vec<_> = archive_read.file_entries.iter().collect();
+    entries.sort_by_key(|entry| entry.offset);
+    let ends = entries
+        .iter()
+        .skip(1)
+        .map(|entry| entry.offset)
+        .chain([central_directory_offset]);
+
+    for (entry, end) in entries.iter().zip(ends) {
+        let header = &buffer[entry.offset as usize..];
+        let name_len = u16::from_le_bytes([header[26], header[27]]) as u64;
+        let extra_len = u16::from_le_bytes([header[28], header[29]]) as u64;
+        let payload_begin = entry.offset + 30 + name_len + extra_len;
+
+        assert_eq!(
+            entry.compressed_size,
+            end - payload_begin,
+            "entry {:?}",
+            String::from_utf8_lossy(&entry.file_name_as_bytes)
+        );
+    }
+
+    Ok(())
+}
@@ -6,10 +6,18 @@ const TEMP: &str = "/tmp";
 
 use std::{
     fs::{create_dir_all, remove_file, File},
-    io::Read,
-    path::Path,
+    io::{Cursor, Error, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
 };
 
+pub fn get_out_file(file_name: &str) -> PathBuf {
+    Path::new(TEMP)
+        .join(PACKAGE_NAME)
+        .join(ENGINE)
+        .join(file_name)
+}
+
 pub fn create_new_clean_file(file_name: &str) -> File {
     let out_dir = Path::new(TEMP).join(PACKAGE_NAME).join(ENGINE);
     if !out_dir.exists() {
@@ -55,6 +63,101 @@ impl Read for MockReader {
     }
 }
 
+/// A payload read by chunks, each read taking some time, e.g. a slow network source.
+pub struct SlowReader {
+    payload: Vec<u8>,
+    position: usize,
+    chunk: usize,
+    delay: Duration,
+}
+
+impl SlowReader {
+    pub fn new(payload: Vec<u8>, chunk: usize, delay: Duration) -> Self {
+        Self {
+            payload,
+            position: 0,
+            chunk,
+            delay,
+        }
+    }
+}
+
+impl Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        std::thread::sleep(self.delay);
+        let end = self
+            .payload
+            .len()
+            .min(self.position + self.chunk.min(buf.len()));
+        let size = end - self.position;
+        buf[..size].copy_from_slice(&self.payload[self.position..end]);
+        self.position = end;
+        Ok(size)
+    }
+}
+
+/// A seekable sink whose n-th seek from the start, one based, fails.
+pub struct SeekFailingWriter {
+    cursor: Cursor<Vec<u8>>,
+    fail_at: usize,
+    seek_count: usize,
+}
+
+impl SeekFailingWriter {
+    pub fn new(fail_at: usize) -> Self {
+        Self {
+            cursor: Cursor::new(Vec::new()),
+            fail_at,
+            seek_count: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.cursor.into_inner()
+    }
+}
+
+impl Write for SeekFailingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.cursor.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SeekFailingWriter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        if let SeekFrom::Start(_) = pos {
+            self.seek_count += 1;
+            if self.seek_count == self.fail_at {
+                return Err(Error::other("seek failure"));
+            }
+        }
+        self.cursor.seek(pos)
+    }
+}
+
+/// A write only sink counting the writes it receives, like the packets of a socket.
+#[derive(Default)]
+pub struct WriteCounter {
+    pub received: Vec<u8>,
+    pub write_count: usize,
+}
+
+impl Write for WriteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_count += 1;
+        self.received.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::MockReader;
@@ -1,7 +1,10 @@
 #![allow(dead_code)]
 use ::std::path::Path;
-use std::{path::PathBuf, task::Poll};
-use tokio::{fs::File, io::AsyncRead};
+use std::{path::PathBuf, task::Poll, time::Duration};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncSeek, AsyncWrite},
+};
 
 use super::PACKAGE_NAME;
 const ENGINE: &str = "tokio";
@@ -72,6 +75,153 @@ impl AsyncRead for MockAsyncReader {
     }
 }
 
+/// A payload read by chunks, each read taking some time, e.g. a slow network source.
+pub struct SlowAsyncReader {
+    payload: Vec<u8>,
+    position: usize,
+    chunk: usize,
+    delay: Duration,
+}
+
+impl SlowAsyncReader {
+    pub fn new(payload: Vec<u8>, chunk: usize, delay: Duration) -> Self {
+        Self {
+            payload,
+            position: 0,
+            chunk,
+            delay,
+        }
+    }
+}
+
+impl AsyncRead for SlowAsyncReader {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let reader = self.get_mut();
+        std::thread::sleep(reader.delay);
+        let end = reader
+            .payload
+            .len()
+            .min(reader.position + reader.chunk.min(buf.remaining()));
+        buf.put_slice(&reader.payload[reader.position..end]);
+        reader.position = end;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A write only sink, like a socket: it can't seek and accepts a few bytes at a time.
+#[derive(Default)]
+pub struct MockAsyncSocket {
+    pub received: Vec<u8>,
+    pending: bool,
+}
+
+impl AsyncWrite for MockAsyncSocket {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let socket = self.get_mut();
+
+        //every other write isn't ready yet
+        socket.pending = !socket.pending;
+        if socket.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        let size = buf.len().min(7);
+        socket.received.extend_from_slice(&buf[..size]);
+        Poll::Ready(Ok(size))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A seekable sink whose n-th seek from the start, one based, fails.
+pub struct SeekFailingWriter {
+    cursor: std::io::Cursor<Vec<u8>>,
+    fail_at: usize,
+    seek_count: usize,
+}
+
+impl SeekFailingWriter {
+    pub fn new(fail_at: usize) -> Self {
+        Self {
+            cursor: std::io::Cursor::new(Vec::new()),
+            fail_at,
+            seek_count: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.cursor.into_inner()
+    }
+}
+
+impl AsyncWrite for SeekFailingWriter {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        std::pin::Pin::new(&mut self.get_mut().cursor).poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for SeekFailingWriter {
+    fn start_seek(
+        self: std::pin::Pin<&mut Self>,
+        position: std::io::SeekFrom,
+    ) -> std::io::Result<()> {
+        let writer = self.get_mut();
+        if let std::io::SeekFrom::Start(_) = position {
+            writer.seek_count += 1;
+            if writer.seek_count == writer.fail_at {
+                return Err(std::io::Error::other("seek failure"));
+            }
+        }
+        std::pin::Pin::new(&mut writer.cursor).start_seek(position)
+    }
+
+    fn poll_complete(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<std::io::Result<u64>> {
+        std::pin::Pin::new(&mut self.get_mut().cursor).poll_complete(cx)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::MockAsyncReader;
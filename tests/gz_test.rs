@@ -0,0 +1,60 @@
+use std::io::Read;
+
+use archflow::{compression::Level, error::ArchiveError};
+use flate2::read::GzDecoder;
+
+mod common;
+
+const PAYLOAD: &[u8] = b"Some string data, some string data, some more string data";
+
+fn gunzip(gz: &[u8]) -> Vec<u8> {
+    let mut decoder = GzDecoder::new(gz);
+    let mut uncompressed = Vec::new();
+    decoder.read_to_end(&mut uncompressed).unwrap();
+    uncompressed
+}
+
+#[test]
+fn gz_std() -> Result<(), ArchiveError> {
+    use archflow::gz::std::GzStream;
+
+    let (gz_size, gz) = GzStream::new(Vec::new()).compress(&mut { PAYLOAD })?;
+
+    assert_eq!(gz_size, gz.len() as u64);
+    assert_eq!(&gz[..3], &[0x1f, 0x8b, 0x08]);
+    //the trailer ends with the uncompressed size
+    assert_eq!(&gz[gz.len() - 4..], &(PAYLOAD.len() as u32).to_le_bytes());
+    assert_eq!(gunzip(&gz), PAYLOAD);
+
+    Ok(())
+}
+
+#[test]
+fn gz_std_file() -> Result<(), ArchiveError> {
+    use archflow::gz::std::GzStream;
+    use common::std::create_new_clean_file;
+
+    let out_file = create_new_clean_file("test_lorem_ipsum.txt.gz");
+    let mut in_file = std::fs::File::open("tests/resources/lorem_ipsum.txt")?;
+
+    GzStream::new(out_file)
+        .compression_level(Level::Best)
+        .compress(&mut in_file)?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn gz_tokio() -> Result<(), ArchiveError> {
+    use archflow::gz::tokio::GzStream;
+
+    let (gz_size, gz) = GzStream::new(Vec::new())
+        .compression_level(Level::Fastest)
+        .compress(&mut { PAYLOAD })
+        .await?;
+
+    assert_eq!(gz_size, gz.len() as u64);
+    assert_eq!(gunzip(&gz), PAYLOAD);
+
+    Ok(())
+}
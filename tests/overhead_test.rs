@@ -0,0 +1,53 @@
+use std::io::Cursor;
+
+use archflow::{
+    compress::std::archive::ZipArchive,
+    compress::FileOptions,
+    compression::CompressionMethod,
+    error::ArchiveError,
+    overhead::{central_header_overhead, eocd_overhead, local_header_overhead},
+    uncompress::ArchiveReader,
+};
+
+const PAYLOAD: &[u8] = b"Some string data";
+
+fn stored_archive(force_zip64: bool) -> Result<(u64, Vec<u8>), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+    archive.force_zip64(force_zip64);
+    archive.set_archive_comment("archive comment");
+
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Store())
+        .set_file_comment("file comment");
+    archive.append_bytes("file1.txt", &options, PAYLOAD)?;
+    archive.append_bytes("dir/file2.txt", &options, PAYLOAD)?;
+
+    let (archive_size, cursor) = archive.finalize()?;
+    Ok((archive_size, cursor.into_inner()))
+}
+
+#[test]
+fn overhead_matches_archive_size() -> Result<(), ArchiveError> {
+    for force_zip64 in [false, true] {
+        let (archive_size, buffer) = stored_archive(force_zip64)?;
+        let archive_read = ArchiveReader::new(Cursor::new(buffer))?;
+
+        //with the zip64 extensions, the local header holds both sizes
+        let local_extra_len = if force_zip64 { 20 } else { 0 };
+
+        let mut expected = eocd_overhead("archive comment".len(), force_zip64);
+        for entry in &archive_read.file_entries {
+            expected += local_header_overhead(entry.file_name_len as usize, local_extra_len)
+                + entry.compressed_size
+                + central_header_overhead(
+                    entry.file_name_len as usize,
+                    entry.extra_field_length as usize,
+                    "file comment".len(),
+                );
+        }
+
+        assert_eq!(archive_size, expected, "force_zip64: {}", force_zip64);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::io::Cursor;
+
+use archflow::{
+    compress::std::archive::ZipArchive, compress::FileOptions, error::ArchiveError,
+    repackage::repackage_tar, types::DateTimeCS, uncompress::ArchiveReader,
+};
+
+mod common;
+
+fn build_tar() -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_mode(0o750);
+    header.set_mtime(1582248020);
+    header.set_size(0);
+    builder
+        .append_data(&mut header, "dir/", std::io::empty())
+        .unwrap();
+
+    let payload = b"Some string data";
+    let mut header = tar::Header::new_gnu();
+    header.set_mode(0o600);
+    header.set_mtime(1582248020);
+    header.set_size(payload.len() as u64);
+    builder
+        .append_data(&mut header, "dir/file.txt", &payload[..])
+        .unwrap();
+
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    builder
+        .append_link(&mut header, "dir/link.txt", "file.txt")
+        .unwrap();
+
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn repackage_tar_std() -> Result<(), ArchiveError> {
+    let mut archive = ZipArchive::new(Cursor::new(Vec::new()));
+
+    let count = repackage_tar(
+        Cursor::new(build_tar()),
+        &mut archive,
+        &FileOptions::default(),
+    )?;
+    //the symlink is skipped
+    assert_eq!(count, 2);
+
+    let (_, cursor) = archive.finalize()?;
+
+    let archive_read = ArchiveReader::new(Cursor::new(cursor.into_inner()))?;
+    let entries = &archive_read.file_entries;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].get_file_name(), "dir/");
+    assert_eq!(entries[0].unix_mode().map(|m| m & 0o777), Some(0o750));
+
+    assert_eq!(entries[1].get_file_name(), "dir/file.txt");
+    assert_eq!(entries[1].unix_mode().map(|m| m & 0o777), Some(0o600));
+    assert_eq!(entries[1].uncompressed_size, 16);
+    assert_eq!(
+        (entries[1].last_mod_file_date, entries[1].last_mod_file_time),
+        DateTimeCS::from_timestamp(1582248020).ms_dos()
+    );
+    assert_eq!(entries[1].crc32, crc32fast::hash(b"Some string data"));
+
+    Ok(())
+}